@@ -108,6 +108,43 @@ def embed_file(
     ```
     """
 
+def embed_bytes(
+    content: bytes,
+    content_type: str,
+    embedder: EmbeddingModel,
+    config: TextEmbedConfig | None = None,
+    metadata: dict[str, str] | None = None,
+    adapter: Adapter | None = None,
+) -> list[EmbedData]:
+    """
+    Embeds a document that is already in memory, e.g. an upload, and returns a list of EmbedData objects.
+
+    Args:
+        content: The content of the document.
+        content_type: A MIME type such as "application/pdf", a file name such as "report.pdf", or an extension such as "pdf".
+        embedder: The embedding model to use.
+        config: The configuration for the embedding model.
+        metadata: The metadata stored with every chunk, e.g. the id of the upload.
+        adapter: The adapter to use for storing the embeddings in a vector database.
+
+    Returns:
+        A list of EmbedData objects.
+
+    Example:
+    ```python
+    import embed_anything
+    model = embed_anything.EmbeddingModel.from_pretrained_hf(
+        embed_anything.WhichModel.Bert,
+        model_id="sentence-transformers/all-MiniLM-L6-v2",
+        revision="main",
+    )
+    with open("test_files/test.pdf", "rb") as f:
+        data = embed_anything.embed_bytes(
+            f.read(), "application/pdf", embedder=model, metadata={"upload_id": "42"}
+        )
+    ```
+    """
+
 def embed_files_batch(
     files: list[str],
     embedder: EmbeddingModel,
@@ -194,6 +231,33 @@ def embed_image_directory(
         A list of EmbedData objects.
     """
 
+def embed_multimodal_directory(
+    file_path: str,
+    text_embedder: EmbeddingModel,
+    vision_embedder: EmbeddingModel,
+    extensions: list[str] | None = None,
+    text_config: TextEmbedConfig | None = None,
+    image_config: ImageEmbedConfig | None = None,
+    adapter: Adapter | None = None,
+) -> list[EmbedData] | None:
+    """
+    Embeds a directory that mixes documents and images. Images are embedded with the vision
+    embedder and the other files with the text embedder. The metadata of every EmbedData has a
+    "modality" key that is either "text" or "image".
+
+    Args:
+        file_path: The path to the directory containing the files to embed.
+        text_embedder: The text embedding model, for the documents.
+        vision_embedder: The vision embedding model, such as CLIP or ColPali, for the images.
+        extensions: The document extensions to consider. Default is all supported document types.
+        text_config: The configuration for the text embedding model.
+        image_config: The configuration for the vision embedding model.
+        adapter: The adapter to use for storing the embeddings in a vector database.
+
+    Returns:
+        A list of EmbedData objects, or None if an adapter is used.
+    """
+
 def embed_webpage(
     url: str,
     embedder: EmbeddingModel,
@@ -225,6 +289,74 @@ def embed_webpage(
     ```
     """
 
+def embed_website(
+    urls: list[str],
+    embedder: EmbeddingModel,
+    crawl_config: CrawlConfig | None = None,
+    config: TextEmbedConfig | None = None,
+    adapter: Adapter | None = None,
+) -> list[EmbedData] | None:
+    """Crawls the pages reachable from the given URLs and embeds them. Every EmbedData gets the "url" of its page and the "depth" it was found at as metadata.
+
+    Args:
+        urls: The URLs the crawl starts from.
+        embedder: The embedding model to use.
+        crawl_config: Which pages to visit, and how fast.
+        config: The configuration for the embedding model.
+        adapter: The adapter to use for storing the embeddings. The embeddings of every page are sent to it as soon as the page is embedded.
+
+    Returns:
+        A list of EmbedData objects, or None when an adapter is used.
+
+    Example:
+    ```python
+    import embed_anything
+    model = embed_anything.EmbeddingModel.from_pretrained_hf(
+        embed_anything.WhichModel.Bert,
+        model_id="sentence-transformers/all-MiniLM-L6-v2",
+    )
+    data = embed_anything.embed_website(
+        ["https://example.com/docs/"],
+        embedder=model,
+        crawl_config=embed_anything.CrawlConfig(max_depth=3, allowed_path_prefixes=["/docs"]),
+    )
+    ```
+    """
+
+def embed_feed(
+    url: str,
+    embedder: EmbeddingModel,
+    feed_config: FeedConfig | None = None,
+    config: TextEmbedConfig | None = None,
+    adapter: Adapter | None = None,
+) -> list[EmbedData] | None:
+    """Embeds the pages listed in a sitemap, a sitemap index, or an RSS or Atom feed. Every EmbedData gets the "url" of its page as metadata, and the "lastmod", "published" and "title" the feed lists for it, if any.
+
+    Args:
+        url: The URL of the sitemap or feed.
+        embedder: The embedding model to use.
+        feed_config: Which pages to fetch, and how fast.
+        config: The configuration for the embedding model.
+        adapter: The adapter to use for storing the embeddings. The embeddings of every page are sent to it as soon as the page is embedded.
+
+    Returns:
+        A list of EmbedData objects, or None when an adapter is used.
+
+    Example:
+    ```python
+    import embed_anything
+    model = embed_anything.EmbeddingModel.from_pretrained_hf(
+        embed_anything.WhichModel.Bert,
+        model_id="sentence-transformers/all-MiniLM-L6-v2",
+    )
+    data = embed_anything.embed_feed(
+        "https://example.com/sitemap.xml",
+        embedder=model,
+        feed_config=embed_anything.FeedConfig(modified_since="2024-06-01T00:00:00Z"),
+    )
+    ```
+    """
+
 def embed_html(
     file_name: str,
     embedder: EmbeddingModel,
@@ -503,6 +635,15 @@ class TextEmbedConfig:
         semantic_encoder: The semantic encoder for the Text Embedding model. Default is None.
         use_ocr: A flag indicating whether to use OCR for the Text Embedding model. Default is False.
         tesseract_path: The path to the Tesseract OCR executable. Default is None and uses the system path.
+        extraction_workers: The number of files extracted in parallel when embedding a directory or a batch of files. Default is None and uses one worker per CPU core.
+        queue_depth: The maximum number of chunks waiting to be embedded, or waiting for the adapter, before extraction is paused. Default is 1000.
+        deduplication: Embed chunks that repeat across the files of a run only once. Either "exact", or "whitespace" to also match chunks that only differ in whitespace. Default is None.
+        token_chunking: A flag indicating whether chunk_size is measured in tokens of the model's tokenizer instead of characters. The chunk size is capped at the model's maximum length, and defaults to it when chunk_size is None. Default is False.
+        file_type_configs: Overrides of chunk_size, overlap_ratio and use_ocr per file extension, e.g. {"md": FileTypeConfig(chunk_size=2000)}. Default is None.
+        file_filter: Which files of a directory are embedded besides their extension. Default is None, which skips the files listed in .gitignore files.
+        content_extraction: Which part of every HTML page is embedded, e.g. only its main content. Default is None, which embeds the whole page.
+        pdf_config: Which pages of PDFs are extracted, and whether chunks may run over page breaks. Default is None, which splits every page on its own.
+        deduplication_cache_size: With deduplication, how many distinct chunks the embeddings are remembered for. Chunks that were forgotten are embedded again when they repeat. Default is 10000.
     """
 
     def __init__(
@@ -516,6 +657,15 @@ class TextEmbedConfig:
         semantic_encoder: EmbeddingModel | None = None,
         use_ocr: bool | None = False,
         tesseract_path: str | None = None,
+        extraction_workers: int | None = None,
+        queue_depth: int | None = 1000,
+        deduplication: str | None = None,
+        token_chunking: bool | None = False,
+        file_type_configs: dict[str, FileTypeConfig] | None = None,
+        file_filter: FileFilter | None = None,
+        content_extraction: ContentExtraction | None = None,
+        pdf_config: PdfConfig | None = None,
+        deduplication_cache_size: int | None = 10000,
     ):
         self.chunk_size = chunk_size
         self.overlap_ratio = overlap_ratio
@@ -526,6 +676,15 @@ class TextEmbedConfig:
         self.semantic_encoder = semantic_encoder
         self.use_ocr = use_ocr
         self.tesseract_path = tesseract_path
+        self.extraction_workers = extraction_workers
+        self.queue_depth = queue_depth
+        self.deduplication = deduplication
+        self.token_chunking = token_chunking
+        self.file_type_configs = file_type_configs
+        self.file_filter = file_filter
+        self.content_extraction = content_extraction
+        self.pdf_config = pdf_config
+        self.deduplication_cache_size = deduplication_cache_size
     chunk_size: int | None
     overlap_ratio: float | None
     batch_size: int | None
@@ -535,6 +694,197 @@ class TextEmbedConfig:
     semantic_encoder: EmbeddingModel | None
     use_ocr: bool | None
     tesseract_path: str | None
+    extraction_workers: int | None
+    queue_depth: int | None
+    deduplication: str | None
+    token_chunking: bool | None
+    file_type_configs: dict[str, FileTypeConfig] | None
+    file_filter: FileFilter | None
+    content_extraction: ContentExtraction | None
+    pdf_config: PdfConfig | None
+    deduplication_cache_size: int | None
+
+class FileTypeConfig:
+    """
+    Overrides the settings of a TextEmbedConfig for one file type. Settings left to None are taken from the TextEmbedConfig.
+
+    Attributes:
+        chunk_size: The chunk size for files of this type.
+        overlap_ratio: The overlap ratio for files of this type.
+        use_ocr: A flag indicating whether to use OCR for files of this type.
+    """
+
+    def __init__(
+        self,
+        chunk_size: int | None = None,
+        overlap_ratio: float | None = None,
+        use_ocr: bool | None = None,
+    ):
+        self.chunk_size = chunk_size
+        self.overlap_ratio = overlap_ratio
+        self.use_ocr = use_ocr
+    chunk_size: int | None
+    overlap_ratio: float | None
+    use_ocr: bool | None
+
+class FileFilter:
+    """
+    Controls which files of a directory are embedded, on top of their extension.
+
+    Attributes:
+        include: Glob patterns, relative to the directory, that a file has to match, in .gitignore syntax. Default is every file.
+        exclude: Glob patterns of files and directories to skip, e.g. "node_modules". Takes precedence over include.
+        respect_ignore_files: A flag indicating whether files listed in .gitignore and .ignore files, and the .git directory, are skipped. Default is True.
+        max_depth: How many directories deep to descend, where 1 only looks at the directory itself. Default is no limit.
+        follow_symlinks: A flag indicating whether symbolic links are followed. Default is False.
+        max_file_size: Files larger than this many bytes are skipped. Default is no limit.
+        archives: A flag indicating whether .zip, .tar, .tar.gz and .tgz archives are picked up, so the documents in them are embedded with their path in the "entry_path" metadata. Default is False.
+    """
+
+    def __init__(
+        self,
+        include: list[str] | None = None,
+        exclude: list[str] | None = None,
+        respect_ignore_files: bool | None = True,
+        max_depth: int | None = None,
+        follow_symlinks: bool | None = False,
+        max_file_size: int | None = None,
+        archives: bool | None = False,
+    ):
+        self.include = include
+        self.exclude = exclude
+        self.respect_ignore_files = respect_ignore_files
+        self.max_depth = max_depth
+        self.follow_symlinks = follow_symlinks
+        self.max_file_size = max_file_size
+        self.archives = archives
+    include: list[str] | None
+    exclude: list[str] | None
+    respect_ignore_files: bool | None
+    max_depth: int | None
+    follow_symlinks: bool | None
+    max_file_size: int | None
+    archives: bool | None
+
+class ContentExtraction:
+    """
+    Controls which part of an HTML page is embedded, with embed_webpage, embed_website, embed_feed and for .html files.
+
+    Attributes:
+        main_content: A flag indicating whether only the main content of the page is kept, found by scoring its blocks of text like readability does. Navigation, banners, sidebars and footers are left out. Default is False.
+        keep_selectors: CSS selectors of the elements that hold the content, e.g. "article". When one of them matches, only the matching elements are kept. Default is None.
+        drop_selectors: CSS selectors of the elements that are removed before anything else, e.g. ".cookie-banner". Default is None.
+    """
+
+    def __init__(
+        self,
+        main_content: bool | None = False,
+        keep_selectors: list[str] | None = None,
+        drop_selectors: list[str] | None = None,
+    ):
+        self.main_content = main_content
+        self.keep_selectors = keep_selectors
+        self.drop_selectors = drop_selectors
+    main_content: bool | None
+    keep_selectors: list[str] | None
+    drop_selectors: list[str] | None
+
+class PdfConfig:
+    """
+    Controls how the pages of a PDF are extracted and split into chunks. Every chunk gets its "page_start" and "page_end" as metadata, the "section_path" of the outline entries it is under, and the "pdf_title", "pdf_author", "pdf_subject", "pdf_keywords", "pdf_created", "pdf_modified" and "pdf_page_count" of the PDF when it has them.
+
+    Attributes:
+        cross_page_chunks: A flag indicating whether chunks may run over page breaks. The text of the whole PDF is then held in memory. Default is False, which splits every page on its own.
+        page_range: The first and last page to extract, numbered from 1, e.g. (1, 10). Default is None, which extracts every page.
+        extract_tables: A flag indicating whether tables are rebuilt as markdown tables from the position of the text. A table is kept in one chunk, or split by rows with the header repeated, and its chunks get a "chunk_type" of "table" as metadata. Not used with OCR. Default is False.
+        auto_ocr: A flag indicating whether OCR is only used on the pages without a text layer, such as scanned pages, and the text of the other pages is extracted. Needs tesseract and poppler, like use_ocr, which are only used when a page needs them. Ignored when use_ocr is True. Default is False.
+    """
+
+    def __init__(
+        self,
+        cross_page_chunks: bool | None = False,
+        page_range: tuple[int, int] | None = None,
+        extract_tables: bool | None = False,
+        auto_ocr: bool | None = False,
+    ):
+        self.cross_page_chunks = cross_page_chunks
+        self.page_range = page_range
+        self.extract_tables = extract_tables
+        self.auto_ocr = auto_ocr
+    cross_page_chunks: bool | None
+    page_range: tuple[int, int] | None
+    extract_tables: bool | None
+    auto_ocr: bool | None
+
+class CrawlConfig:
+    """
+    Controls which pages embed_website visits, and how fast.
+
+    Attributes:
+        max_depth: How many links away from a seed URL to go, where 0 only visits the seeds. Default is 2.
+        max_pages: Stops the crawl after this many pages. Default is 1000.
+        allowed_domains: The hosts links are followed to, subdomains included. Default is the hosts of the seed URLs.
+        allowed_path_prefixes: The paths links are followed to, e.g. "/docs". Default is every path.
+        respect_robots_txt: A flag indicating whether robots.txt files are respected. Default is True.
+        delay: The minimum number of seconds between two requests to the same host. Default is 0.5.
+        user_agent: The user agent of the requests, also used to pick the rules of robots.txt files. Default is "EmbedAnything".
+    """
+
+    def __init__(
+        self,
+        max_depth: int | None = None,
+        max_pages: int | None = None,
+        allowed_domains: list[str] | None = None,
+        allowed_path_prefixes: list[str] | None = None,
+        respect_robots_txt: bool | None = None,
+        delay: float | None = None,
+        user_agent: str | None = None,
+    ):
+        self.max_depth = max_depth
+        self.max_pages = max_pages
+        self.allowed_domains = allowed_domains
+        self.allowed_path_prefixes = allowed_path_prefixes
+        self.respect_robots_txt = respect_robots_txt
+        self.delay = delay
+        self.user_agent = user_agent
+    max_depth: int | None
+    max_pages: int | None
+    allowed_domains: list[str] | None
+    allowed_path_prefixes: list[str] | None
+    respect_robots_txt: bool | None
+    delay: float | None
+    user_agent: str | None
+
+class FeedConfig:
+    """
+    Controls which pages embed_feed fetches, and how fast.
+
+    Attributes:
+        modified_since: An RFC 3339 date, e.g. "2024-06-01T00:00:00Z". Pages and sitemaps last modified or published before it are skipped. Default is None.
+        max_pages: Stops after this many pages. Default is 1000.
+        max_sitemaps: The maximum number of sitemaps read when following sitemap indexes. Default is 100.
+        delay: The minimum number of seconds between two requests. Default is 0.5.
+        user_agent: The user agent of the requests. Default is "EmbedAnything".
+    """
+
+    def __init__(
+        self,
+        modified_since: str | None = None,
+        max_pages: int | None = None,
+        max_sitemaps: int | None = None,
+        delay: float | None = None,
+        user_agent: str | None = None,
+    ):
+        self.modified_since = modified_since
+        self.max_pages = max_pages
+        self.max_sitemaps = max_sitemaps
+        self.delay = delay
+        self.user_agent = user_agent
+    modified_since: str | None
+    max_pages: int | None
+    max_sitemaps: int | None
+    delay: float | None
+    user_agent: str | None
 
 class ImageEmbedConfig:
     """
@@ -542,11 +892,14 @@ class ImageEmbedConfig:
 
     Attributes:
         buffer_size: The buffer size for the Image Embedding model. Default is 100.
+        file_filter: Which images of a directory are embedded besides their extension. Default is None.
     """
 
-    def __init__(self, buffer_size: int | None = None):
+    def __init__(self, buffer_size: int | None = None, file_filter: FileFilter | None = None):
         self.buffer_size = buffer_size
+        self.file_filter = file_filter
     buffer_size: int | None
+    file_filter: FileFilter | None
 
 class EmbeddingModel:
     """
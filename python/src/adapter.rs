@@ -0,0 +1,44 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use embed_anything::adapter::Adapter;
+use pyo3::prelude::*;
+
+use crate::EmbedData;
+
+/// Passes the embeddings to a Python adapter object, such as the ones in
+/// `embed_anything.vectordb`, by calling its `upsert` method.
+pub struct PyAdapter {
+    adapter: PyObject,
+}
+
+impl PyAdapter {
+    pub fn new(adapter: PyObject) -> Self {
+        Self { adapter }
+    }
+}
+
+#[async_trait]
+impl Adapter for PyAdapter {
+    async fn create_index(&self, index_name: &str, dimension: usize, metric: &str) -> Result<()> {
+        Python::with_gil(|py| {
+            self.adapter
+                .call_method1(py, "create_index", (dimension, metric, index_name))
+                .map_err(|e| anyhow!(e.to_string()))?;
+            Ok(())
+        })
+    }
+
+    async fn upsert(&self, data: &[embed_anything::embeddings::embed::EmbedData]) -> Result<()> {
+        Python::with_gil(|py| {
+            let converted_data = data
+                .iter()
+                .cloned()
+                .map(|data| EmbedData { inner: data })
+                .collect::<Vec<EmbedData>>();
+            self.adapter
+                .call_method1(py, "upsert", (converted_data,))
+                .map_err(|e| anyhow!(e.to_string()))?;
+            Ok(())
+        })
+    }
+}
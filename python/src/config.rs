@@ -1,6 +1,9 @@
 use crate::EmbeddingModel;
 use embed_anything::config::SplittingStrategy;
+use embed_anything::dedup::Deduplication;
+use pyo3::exceptions::PyValueError;
 use pyo3::prelude::*;
+use std::collections::HashMap;
 
 #[pyclass]
 #[derive(Default)]
@@ -12,7 +15,7 @@ pub struct TextEmbedConfig {
 #[pymethods]
 impl TextEmbedConfig {
     #[new]
-    #[pyo3(signature = (chunk_size=None, batch_size=None, late_chunking=None, buffer_size=None, overlap_ratio=None, splitting_strategy=None, semantic_encoder=None, use_ocr=None, tesseract_path=None))]
+    #[pyo3(signature = (chunk_size=None, batch_size=None, late_chunking=None, buffer_size=None, overlap_ratio=None, splitting_strategy=None, semantic_encoder=None, use_ocr=None, tesseract_path=None, extraction_workers=None, queue_depth=None, deduplication=None, token_chunking=None, file_type_configs=None, file_filter=None, content_extraction=None, pdf_config=None, deduplication_cache_size=None))]
     pub fn new(
         chunk_size: Option<usize>,
         batch_size: Option<usize>,
@@ -23,6 +26,15 @@ impl TextEmbedConfig {
         semantic_encoder: Option<&EmbeddingModel>,
         use_ocr: Option<bool>,
         tesseract_path: Option<&str>,
+        extraction_workers: Option<usize>,
+        queue_depth: Option<usize>,
+        deduplication: Option<&str>,
+        token_chunking: Option<bool>,
+        file_type_configs: Option<HashMap<String, PyRef<FileTypeConfig>>>,
+        file_filter: Option<PyRef<FileFilter>>,
+        content_extraction: Option<PyRef<ContentExtraction>>,
+        pdf_config: Option<PyRef<PdfConfig>>,
+        deduplication_cache_size: Option<usize>,
     ) -> Self {
         let strategy = match splitting_strategy {
             Some(strategy) => {
@@ -42,15 +54,43 @@ impl TextEmbedConfig {
             None => SplittingStrategy::Sentence,
         };
 
-        Self {
-            inner: embed_anything::config::TextEmbedConfig::default()
-                .with_chunk_size(chunk_size.unwrap_or(1000), overlap_ratio)
-                .with_batch_size(batch_size.unwrap_or(32))
-                .with_buffer_size(buffer_size.unwrap_or(100))
-                .with_splitting_strategy(strategy)
-                .with_late_chunking(late_chunking.unwrap_or(false))
-                .with_ocr(use_ocr.unwrap_or(false), tesseract_path),
+        let mut inner = embed_anything::config::TextEmbedConfig::default()
+            .with_chunk_size(chunk_size.unwrap_or(1000), overlap_ratio)
+            .with_batch_size(batch_size.unwrap_or(32))
+            .with_buffer_size(buffer_size.unwrap_or(100))
+            .with_splitting_strategy(strategy)
+            .with_late_chunking(late_chunking.unwrap_or(false))
+            .with_ocr(use_ocr.unwrap_or(false), tesseract_path)
+            .with_queue_depth(queue_depth.unwrap_or(1000));
+        if let Some(workers) = extraction_workers {
+            inner = inner.with_extraction_workers(workers);
+        }
+        if token_chunking.unwrap_or(false) {
+            inner = inner.with_token_chunking(chunk_size);
+        }
+        for (extension, file_type_config) in file_type_configs.unwrap_or_default() {
+            inner = inner.with_file_type_config(&extension, file_type_config.inner.clone());
+        }
+        if let Some(file_filter) = file_filter {
+            inner = inner.with_file_filter(file_filter.inner.clone());
+        }
+        if let Some(content_extraction) = content_extraction {
+            inner = inner.with_content_extraction(content_extraction.inner.clone());
+        }
+        if let Some(pdf_config) = pdf_config {
+            inner = inner.with_pdf_config(pdf_config.inner.clone());
+        }
+        match deduplication {
+            Some("exact") => inner = inner.with_deduplication(Deduplication::Exact),
+            Some("whitespace") => inner = inner.with_deduplication(Deduplication::Whitespace),
+            Some(_) => panic!("Unknown deduplication provided!"),
+            None => {}
         }
+        if let Some(size) = deduplication_cache_size {
+            inner = inner.with_deduplication_cache_size(size);
+        }
+
+        Self { inner }
     }
 
     #[getter]
@@ -64,6 +104,213 @@ impl TextEmbedConfig {
     }
 }
 
+#[pyclass]
+#[derive(Clone, Default)]
+pub struct FileTypeConfig {
+    pub inner: embed_anything::config::FileTypeConfig,
+}
+
+#[pymethods]
+impl FileTypeConfig {
+    #[new]
+    #[pyo3(signature = (chunk_size=None, overlap_ratio=None, use_ocr=None))]
+    pub fn new(chunk_size: Option<usize>, overlap_ratio: Option<f32>, use_ocr: Option<bool>) -> Self {
+        Self {
+            inner: embed_anything::config::FileTypeConfig {
+                chunk_size,
+                overlap_ratio,
+                use_ocr,
+            },
+        }
+    }
+
+    #[getter]
+    pub fn chunk_size(&self) -> Option<usize> {
+        self.inner.chunk_size
+    }
+
+    #[getter]
+    pub fn overlap_ratio(&self) -> Option<f32> {
+        self.inner.overlap_ratio
+    }
+
+    #[getter]
+    pub fn use_ocr(&self) -> Option<bool> {
+        self.inner.use_ocr
+    }
+}
+
+#[pyclass]
+#[derive(Clone, Default)]
+pub struct FileFilter {
+    pub inner: embed_anything::file_loader::FileFilter,
+}
+
+#[pymethods]
+impl FileFilter {
+    #[new]
+    #[pyo3(signature = (include=None, exclude=None, respect_ignore_files=None, max_depth=None, follow_symlinks=None, max_file_size=None, archives=None))]
+    pub fn new(
+        include: Option<Vec<String>>,
+        exclude: Option<Vec<String>>,
+        respect_ignore_files: Option<bool>,
+        max_depth: Option<usize>,
+        follow_symlinks: Option<bool>,
+        max_file_size: Option<u64>,
+        archives: Option<bool>,
+    ) -> Self {
+        let defaults = embed_anything::file_loader::FileFilter::default();
+        Self {
+            inner: embed_anything::file_loader::FileFilter {
+                include: include.unwrap_or_default(),
+                exclude: exclude.unwrap_or_default(),
+                respect_ignore_files: respect_ignore_files
+                    .unwrap_or(defaults.respect_ignore_files),
+                max_depth,
+                follow_symlinks: follow_symlinks.unwrap_or(defaults.follow_symlinks),
+                max_file_size,
+                archives: archives.unwrap_or(defaults.archives),
+            },
+        }
+    }
+}
+
+#[pyclass]
+#[derive(Clone, Default)]
+pub struct ContentExtraction {
+    pub inner: embed_anything::file_processor::html_processor::ContentExtraction,
+}
+
+#[pymethods]
+impl ContentExtraction {
+    #[new]
+    #[pyo3(signature = (main_content=None, keep_selectors=None, drop_selectors=None))]
+    pub fn new(
+        main_content: Option<bool>,
+        keep_selectors: Option<Vec<String>>,
+        drop_selectors: Option<Vec<String>>,
+    ) -> Self {
+        Self {
+            inner: embed_anything::file_processor::html_processor::ContentExtraction {
+                main_content: main_content.unwrap_or(false),
+                keep_selectors: keep_selectors.unwrap_or_default(),
+                drop_selectors: drop_selectors.unwrap_or_default(),
+            },
+        }
+    }
+}
+
+#[pyclass]
+#[derive(Clone, Default)]
+pub struct PdfConfig {
+    pub inner: embed_anything::file_processor::pdf_processor::PdfConfig,
+}
+
+#[pymethods]
+impl PdfConfig {
+    #[new]
+    #[pyo3(signature = (cross_page_chunks=None, page_range=None, extract_tables=None, auto_ocr=None))]
+    pub fn new(
+        cross_page_chunks: Option<bool>,
+        page_range: Option<(usize, usize)>,
+        extract_tables: Option<bool>,
+        auto_ocr: Option<bool>,
+    ) -> Self {
+        Self {
+            inner: embed_anything::file_processor::pdf_processor::PdfConfig {
+                cross_page_chunks: cross_page_chunks.unwrap_or(false),
+                page_range: page_range.map(|(first, last)| first..=last),
+                extract_tables: extract_tables.unwrap_or(false),
+                auto_ocr: auto_ocr.unwrap_or(false),
+            },
+        }
+    }
+}
+
+#[pyclass]
+#[derive(Clone, Default)]
+pub struct CrawlConfig {
+    pub inner: embed_anything::crawler::CrawlConfig,
+}
+
+#[pymethods]
+impl CrawlConfig {
+    #[new]
+    #[pyo3(signature = (max_depth=None, max_pages=None, allowed_domains=None, allowed_path_prefixes=None, respect_robots_txt=None, delay=None, user_agent=None))]
+    pub fn new(
+        max_depth: Option<usize>,
+        max_pages: Option<usize>,
+        allowed_domains: Option<Vec<String>>,
+        allowed_path_prefixes: Option<Vec<String>>,
+        respect_robots_txt: Option<bool>,
+        delay: Option<f64>,
+        user_agent: Option<String>,
+    ) -> Self {
+        let mut inner = embed_anything::crawler::CrawlConfig::default();
+        if let Some(max_depth) = max_depth {
+            inner.max_depth = max_depth;
+        }
+        if let Some(max_pages) = max_pages {
+            inner.max_pages = Some(max_pages);
+        }
+        if let Some(allowed_domains) = allowed_domains {
+            inner.allowed_domains = allowed_domains;
+        }
+        if let Some(allowed_path_prefixes) = allowed_path_prefixes {
+            inner.allowed_path_prefixes = allowed_path_prefixes;
+        }
+        if let Some(respect_robots_txt) = respect_robots_txt {
+            inner.respect_robots_txt = respect_robots_txt;
+        }
+        if let Some(delay) = delay {
+            inner.delay = std::time::Duration::from_secs_f64(delay.max(0.0));
+        }
+        if let Some(user_agent) = user_agent {
+            inner.user_agent = user_agent;
+        }
+        Self { inner }
+    }
+}
+
+#[pyclass]
+#[derive(Clone, Default)]
+pub struct FeedConfig {
+    pub inner: embed_anything::feeds::FeedConfig,
+}
+
+#[pymethods]
+impl FeedConfig {
+    #[new]
+    #[pyo3(signature = (modified_since=None, max_pages=None, max_sitemaps=None, delay=None, user_agent=None))]
+    pub fn new(
+        modified_since: Option<&str>,
+        max_pages: Option<usize>,
+        max_sitemaps: Option<usize>,
+        delay: Option<f64>,
+        user_agent: Option<String>,
+    ) -> PyResult<Self> {
+        let mut inner = embed_anything::feeds::FeedConfig::default();
+        if let Some(modified_since) = modified_since {
+            let since = chrono::DateTime::parse_from_rfc3339(modified_since)
+                .map_err(|e| PyValueError::new_err(format!("Invalid modified_since: {}", e)))?;
+            inner = inner.with_modified_since(since.to_utc());
+        }
+        if let Some(max_pages) = max_pages {
+            inner.max_pages = Some(max_pages);
+        }
+        if let Some(max_sitemaps) = max_sitemaps {
+            inner.max_sitemaps = max_sitemaps;
+        }
+        if let Some(delay) = delay {
+            inner.delay = std::time::Duration::from_secs_f64(delay.max(0.0));
+        }
+        if let Some(user_agent) = user_agent {
+            inner.user_agent = user_agent;
+        }
+        Ok(Self { inner })
+    }
+}
+
 #[pyclass]
 #[derive(Clone, Default)]
 pub struct ImageEmbedConfig {
@@ -73,11 +320,13 @@ pub struct ImageEmbedConfig {
 #[pymethods]
 impl ImageEmbedConfig {
     #[new]
-    #[pyo3(signature = (buffer_size=None))]
-    pub fn new(buffer_size: Option<usize>) -> Self {
-        Self {
-            inner: embed_anything::config::ImageEmbedConfig::new(buffer_size),
+    #[pyo3(signature = (buffer_size=None, file_filter=None))]
+    pub fn new(buffer_size: Option<usize>, file_filter: Option<PyRef<FileFilter>>) -> Self {
+        let mut inner = embed_anything::config::ImageEmbedConfig::new(buffer_size);
+        if let Some(file_filter) = file_filter {
+            inner = inner.with_file_filter(file_filter.inner.clone());
         }
+        Self { inner }
     }
 
     #[getter]
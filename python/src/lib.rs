@@ -1,5 +1,8 @@
+pub mod adapter;
 pub mod config;
 pub mod models;
+use adapter::PyAdapter;
+use embed_anything::adapter::Adapter;
 use embed_anything::embeddings::embed::{TextEmbedder, VisionEmbedder};
 use embed_anything::{self, config::TextEmbedConfig, emb_audio, embeddings::embed::{Embedder, EmbeddingResult}, file_processor::audio::audio_processor, FileLoadingError};
 use models::colbert::ColbertModel;
@@ -510,25 +513,7 @@ pub fn embed_file(
             file_name
         )));
     };
-    let adapter = match adapter {
-        Some(adapter) => {
-            let callback = move |data: Vec<embed_anything::embeddings::embed::EmbedData>| {
-                Python::with_gil(|py| {
-                    let upsert_fn = adapter.getattr(py, "upsert").unwrap();
-                    let converted_data = data
-                        .into_iter()
-                        .map(|data| EmbedData { inner: data })
-                        .collect::<Vec<EmbedData>>();
-                    upsert_fn
-                        .call1(py, (converted_data,))
-                        .map_err(|e| PyValueError::new_err(e.to_string()))
-                        .unwrap();
-                });
-            };
-            Some(callback)
-        }
-        None => None,
-    };
+    let adapter = adapter.map(|adapter| Arc::new(PyAdapter::new(adapter)) as Arc<dyn Adapter>);
 
     let embeddings = rt
         .block_on(async {
@@ -536,14 +521,7 @@ pub fn embed_file(
                 file_name,
                 embedding_model,
                 config,
-                adapter.map(|f| {
-                    Box::new(f)
-                        as Box<
-                            dyn FnOnce(Vec<embed_anything::embeddings::embed::EmbedData>)
-                                + Send
-                                + Sync,
-                        >
-                }),
+                adapter,
             )
             .await
         })
@@ -564,6 +542,47 @@ pub fn embed_file(
     }))
 }
 
+#[pyfunction]
+#[pyo3(signature = (content, content_type, embedder, config=None, metadata=None, adapter=None))]
+pub fn embed_bytes(
+    content: &[u8],
+    content_type: &str,
+    embedder: &EmbeddingModel,
+    config: Option<&config::TextEmbedConfig>,
+    metadata: Option<HashMap<String, String>>,
+    adapter: Option<PyObject>,
+) -> PyResult<Option<Vec<EmbedData>>> {
+    let config = config.map(|c| &c.inner);
+    let embedding_model = &embedder.inner;
+    let rt = Builder::new_multi_thread().enable_all().build().unwrap();
+    let adapter = adapter.map(|adapter| Arc::new(PyAdapter::new(adapter)) as Arc<dyn Adapter>);
+
+    let embeddings = rt
+        .block_on(async {
+            embed_anything::embed_bytes(
+                content,
+                content_type,
+                embedding_model,
+                config,
+                metadata,
+                adapter,
+            )
+            .await
+        })
+        .map_err(|e| match e.downcast_ref::<FileLoadingError>() {
+            Some(FileLoadingError::UnsupportedFileType(file)) => {
+                PyValueError::new_err(file.clone())
+            }
+            _ => PyValueError::new_err(e.to_string()),
+        })?;
+
+    Ok(embeddings.map(|embs| {
+        embs.into_iter()
+            .map(|data| EmbedData { inner: data })
+            .collect()
+    }))
+}
+
 #[pyfunction]
 #[pyo3(signature = (files, embedder, config=None, adapter=None))]
 pub fn embed_files_batch(
@@ -575,25 +594,7 @@ pub fn embed_files_batch(
     let config = config.map(|c| &c.inner);
     let embedding_model = &embedder.inner;
     let rt = Builder::new_multi_thread().enable_all().build().unwrap();
-    let adapter = match adapter {
-        Some(adapter) => {
-            let callback = move |data: Vec<embed_anything::embeddings::embed::EmbedData>| {
-                Python::with_gil(|py| {
-                    let upsert_fn = adapter.getattr(py, "upsert").unwrap();
-                    let converted_data = data
-                        .into_iter()
-                        .map(|data| EmbedData { inner: data })
-                        .collect::<Vec<EmbedData>>();
-                    upsert_fn
-                        .call1(py, (converted_data,))
-                        .map_err(|e| PyValueError::new_err(e.to_string()))
-                        .unwrap();
-                });
-            };
-            Some(callback)
-        }
-        None => None,
-    };
+    let adapter = adapter.map(|adapter| Arc::new(PyAdapter::new(adapter)) as Arc<dyn Adapter>);
 
     let embeddings = rt
         .block_on(async {
@@ -601,14 +602,7 @@ pub fn embed_files_batch(
                 files,
                 embedding_model,
                 config,
-                adapter.map(|f| {
-                    Box::new(f)
-                        as Box<
-                            dyn FnMut(Vec<embed_anything::embeddings::embed::EmbedData>)
-                                + Send
-                                + Sync,
-                        >
-                }),
+                adapter,
             )
             .await
         })
@@ -669,25 +663,7 @@ pub fn embed_directory(
 
     let rt = Builder::new_multi_thread().enable_all().build().unwrap();
     println!("Runtime created");
-    let adapter = match adapter {
-        Some(adapter) => {
-            let callback = move |data: Vec<embed_anything::embeddings::embed::EmbedData>| {
-                Python::with_gil(|py| {
-                    let upsert_fn = adapter.getattr(py, "upsert").unwrap();
-                    let converted_data = data
-                        .into_iter()
-                        .map(|data| EmbedData { inner: data })
-                        .collect::<Vec<EmbedData>>();
-                    upsert_fn
-                        .call1(py, (converted_data,))
-                        .map_err(|e| PyValueError::new_err(e.to_string()))
-                        .unwrap();
-                });
-            };
-            Some(callback)
-        }
-        None => None,
-    };
+    let adapter = adapter.map(|adapter| Arc::new(PyAdapter::new(adapter)) as Arc<dyn Adapter>);
 
     let data = rt.block_on(async {
         embed_anything::embed_directory_stream(
@@ -695,12 +671,7 @@ pub fn embed_directory(
             embedding_model,
             extensions,
             config,
-            adapter.map(|f| {
-                Box::new(f)
-                    as Box<
-                        dyn FnMut(Vec<embed_anything::embeddings::embed::EmbedData>) + Send + Sync,
-                    >
-            }),
+            adapter,
         )
         .await
         .map_err(|e| PyValueError::new_err(e.to_string()))
@@ -714,6 +685,44 @@ pub fn embed_directory(
     Ok(data)
 }
 
+#[pyfunction]
+#[pyo3(signature = (directory, text_embedder, vision_embedder, extensions=None, text_config=None, image_config=None, adapter=None))]
+pub fn embed_multimodal_directory(
+    directory: PathBuf,
+    text_embedder: &EmbeddingModel,
+    vision_embedder: &EmbeddingModel,
+    extensions: Option<Vec<String>>,
+    text_config: Option<&config::TextEmbedConfig>,
+    image_config: Option<&config::ImageEmbedConfig>,
+    adapter: Option<PyObject>,
+) -> PyResult<Option<Vec<EmbedData>>> {
+    let text_config = text_config.map(|c| &c.inner);
+    let image_config = image_config.map(|c| &c.inner);
+    let rt = Builder::new_multi_thread().enable_all().build().unwrap();
+    let adapter = adapter.map(|adapter| Arc::new(PyAdapter::new(adapter)) as Arc<dyn Adapter>);
+
+    let data = rt
+        .block_on(async {
+            embed_anything::embed_multimodal_directory(
+                directory,
+                &text_embedder.inner,
+                &vision_embedder.inner,
+                extensions,
+                text_config,
+                image_config,
+                adapter,
+            )
+            .await
+        })
+        .map_err(|e| PyValueError::new_err(e.to_string()))?;
+
+    Ok(data.map(|data| {
+        data.into_iter()
+            .map(|data| EmbedData { inner: data })
+            .collect::<Vec<_>>()
+    }))
+}
+
 #[pyfunction]
 #[pyo3(signature = (directory, embedder, config=None, adapter = None))]
 pub fn embed_image_directory(
@@ -727,37 +736,14 @@ pub fn embed_image_directory(
     let rt = Builder::new_multi_thread().enable_all().build().unwrap();
     println!("Runtime created");
 
-    let adapter = match adapter {
-        Some(adapter) => {
-            let callback = move |data: Vec<embed_anything::embeddings::embed::EmbedData>| {
-                Python::with_gil(|py| {
-                    let upsert_fn = adapter.getattr(py, "upsert").unwrap();
-                    let converted_data = data
-                        .into_iter()
-                        .map(|data| EmbedData { inner: data })
-                        .collect::<Vec<EmbedData>>();
-                    upsert_fn
-                        .call1(py, (converted_data,))
-                        .map_err(|e| PyValueError::new_err(e.to_string()))
-                        .unwrap();
-                });
-            };
-            Some(callback)
-        }
-        None => None,
-    };
+    let adapter = adapter.map(|adapter| Arc::new(PyAdapter::new(adapter)) as Arc<dyn Adapter>);
 
     let data = rt.block_on(async {
         embed_anything::embed_image_directory(
             directory,
             embedding_model,
             config,
-            adapter.map(|f| {
-                Box::new(f)
-                    as Box<
-                        dyn FnMut(Vec<embed_anything::embeddings::embed::EmbedData>) + Send + Sync,
-                    >
-            }),
+            adapter,
         )
         .await
         .map_err(|e| PyValueError::new_err(e.to_string()))
@@ -782,37 +768,14 @@ pub fn embed_webpage(
     let embedding_model = &embedder.inner;
     let config = config.map(|c| &c.inner);
     let rt = Builder::new_multi_thread().enable_all().build().unwrap();
-    let adapter = match adapter {
-        Some(adapter) => {
-            let callback = move |data: Vec<embed_anything::embeddings::embed::EmbedData>| {
-                Python::with_gil(|py| {
-                    let upsert_fn = adapter.getattr(py, "upsert").unwrap();
-                    let converted_data = data
-                        .into_iter()
-                        .map(|data| EmbedData { inner: data })
-                        .collect::<Vec<EmbedData>>();
-                    upsert_fn
-                        .call1(py, (converted_data,))
-                        .map_err(|e| PyValueError::new_err(e.to_string()))
-                        .unwrap();
-                });
-            };
-            Some(callback)
-        }
-        None => None,
-    };
+    let adapter = adapter.map(|adapter| Arc::new(PyAdapter::new(adapter)) as Arc<dyn Adapter>);
 
     let data = rt.block_on(async {
         embed_anything::embed_webpage(
             url,
             embedding_model,
             config,
-            adapter.map(|f| {
-                Box::new(f)
-                    as Box<
-                        dyn FnOnce(Vec<embed_anything::embeddings::embed::EmbedData>) + Send + Sync,
-                    >
-            }),
+            adapter,
         )
         .await
         .map_err(|e| PyValueError::new_err(e.to_string()))
@@ -826,13 +789,72 @@ pub fn embed_webpage(
     Ok(data)
 }
 
+#[pyfunction]
+#[pyo3(signature = (urls, embedder, crawl_config=None, config=None, adapter=None))]
+pub fn embed_website(
+    urls: Vec<String>,
+    embedder: &EmbeddingModel,
+    crawl_config: Option<&config::CrawlConfig>,
+    config: Option<&config::TextEmbedConfig>,
+    adapter: Option<PyObject>,
+) -> PyResult<Option<Vec<EmbedData>>> {
+    let embedding_model = &embedder.inner;
+    let crawl_config = crawl_config.map(|c| &c.inner);
+    let config = config.map(|c| &c.inner);
+    let rt = Builder::new_multi_thread().enable_all().build().unwrap();
+    let adapter = adapter.map(|adapter| Arc::new(PyAdapter::new(adapter)) as Arc<dyn Adapter>);
+
+    let data = rt
+        .block_on(async {
+            embed_anything::embed_website(urls, embedding_model, crawl_config, config, adapter)
+                .await
+        })
+        .map_err(|e| PyValueError::new_err(e.to_string()))?;
+    Ok(data.map(|data| {
+        data.into_iter()
+            .map(|data| EmbedData { inner: data })
+            .collect()
+    }))
+}
+
+#[pyfunction]
+#[pyo3(signature = (url, embedder, feed_config=None, config=None, adapter=None))]
+pub fn embed_feed(
+    url: &str,
+    embedder: &EmbeddingModel,
+    feed_config: Option<&config::FeedConfig>,
+    config: Option<&config::TextEmbedConfig>,
+    adapter: Option<PyObject>,
+) -> PyResult<Option<Vec<EmbedData>>> {
+    let embedding_model = &embedder.inner;
+    let feed_config = feed_config.map(|c| &c.inner);
+    let config = config.map(|c| &c.inner);
+    let rt = Builder::new_multi_thread().enable_all().build().unwrap();
+    let adapter = adapter.map(|adapter| Arc::new(PyAdapter::new(adapter)) as Arc<dyn Adapter>);
+
+    let data = rt
+        .block_on(async {
+            embed_anything::embed_feed(url, embedding_model, feed_config, config, adapter).await
+        })
+        .map_err(|e| PyValueError::new_err(e.to_string()))?;
+    Ok(data.map(|data| {
+        data.into_iter()
+            .map(|data| EmbedData { inner: data })
+            .collect()
+    }))
+}
+
 #[pymodule]
 fn _embed_anything(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(embed_file, m)?)?;
+    m.add_function(wrap_pyfunction!(embed_bytes, m)?)?;
     m.add_function(wrap_pyfunction!(embed_directory, m)?)?;
     m.add_function(wrap_pyfunction!(embed_image_directory, m)?)?;
+    m.add_function(wrap_pyfunction!(embed_multimodal_directory, m)?)?;
     m.add_function(wrap_pyfunction!(embed_query, m)?)?;
     m.add_function(wrap_pyfunction!(embed_webpage, m)?)?;
+    m.add_function(wrap_pyfunction!(embed_website, m)?)?;
+    m.add_function(wrap_pyfunction!(embed_feed, m)?)?;
     m.add_function(wrap_pyfunction!(embed_audio_file, m)?)?;
     m.add_class::<ColpaliModel>()?;
     m.add_class::<ColbertModel>()?;
@@ -841,6 +863,12 @@ fn _embed_anything(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<WhichModel>()?;
     m.add_class::<EmbedData>()?;
     m.add_class::<config::TextEmbedConfig>()?;
+    m.add_class::<config::FileTypeConfig>()?;
+    m.add_class::<config::FileFilter>()?;
+    m.add_class::<config::ContentExtraction>()?;
+    m.add_class::<config::PdfConfig>()?;
+    m.add_class::<config::CrawlConfig>()?;
+    m.add_class::<config::FeedConfig>()?;
     m.add_class::<ONNXModel>()?;
     m.add_class::<Reranker>()?;
     m.add_class::<Dtype>()?;
@@ -0,0 +1,268 @@
+//! Adapters write embeddings to a vector database as they are produced, instead of the embedding
+//! functions collecting them in memory.
+
+use std::{future::Future, sync::Arc, sync::Mutex, time::Duration};
+
+use anyhow::{bail, Result};
+use async_trait::async_trait;
+
+use crate::embeddings::embed::EmbedData;
+
+/// A vector database that the `embed_*` functions stream embeddings into.
+///
+/// Only [Adapter::upsert] is required. Failed calls are retried following
+/// [Adapter::retry_policy], and embeddings are grouped in batches of [Adapter::batch_size].
+///
+/// # Example
+///
+/// ```rust
+/// use anyhow::Result;
+/// use async_trait::async_trait;
+/// use embed_anything::adapter::Adapter;
+/// use embed_anything::embeddings::embed::EmbedData;
+///
+/// struct StdoutAdapter;
+///
+/// #[async_trait]
+/// impl Adapter for StdoutAdapter {
+///     async fn upsert(&self, data: &[EmbedData]) -> Result<()> {
+///         println!("Upserting {} embeddings", data.len());
+///         Ok(())
+///     }
+///
+///     fn batch_size(&self) -> Option<usize> {
+///         Some(500)
+///     }
+/// }
+/// ```
+#[async_trait]
+pub trait Adapter: Send + Sync {
+    /// Creates the index the embeddings are written to. Defaults to doing nothing, for databases
+    /// where the index is created up front.
+    async fn create_index(&self, _index_name: &str, _dimension: usize, _metric: &str) -> Result<()> {
+        Ok(())
+    }
+
+    /// Inserts the embeddings, or replaces them if they are already in the index.
+    async fn upsert(&self, data: &[EmbedData]) -> Result<()>;
+
+    /// Deletes the embeddings of every chunk that came from one of `files`, as recorded in the
+    /// `file_name` metadata. Adapters that implement it must also override
+    /// [Adapter::supports_delete].
+    async fn delete(&self, _files: &[String]) -> Result<()> {
+        bail!("This adapter does not support deleting embeddings")
+    }
+
+    /// Whether [Adapter::delete] is implemented. Defaults to false.
+    fn supports_delete(&self) -> bool {
+        false
+    }
+
+    /// The number of embeddings per call to [Adapter::upsert]. Defaults to None, which upserts
+    /// every batch of the pipeline as it is.
+    fn batch_size(&self) -> Option<usize> {
+        None
+    }
+
+    fn retry_policy(&self) -> RetryPolicy {
+        RetryPolicy::default()
+    }
+}
+
+/// How often, and after how long, a failed adapter call is retried. The backoff doubles after
+/// every attempt, up to `max_backoff`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(10),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// A policy that gives up after the first failure.
+    pub fn none() -> Self {
+        Self {
+            max_retries: 0,
+            ..Default::default()
+        }
+    }
+
+    /// Runs `call` until it succeeds or the retries are used up.
+    pub async fn retry<F, Fut>(&self, mut call: F) -> Result<()>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<()>>,
+    {
+        let mut backoff = self.initial_backoff;
+        let mut attempt = 0;
+        loop {
+            match call().await {
+                Ok(()) => return Ok(()),
+                Err(e) if attempt < self.max_retries => {
+                    eprintln!("Adapter call failed, retrying in {:?}: {:?}", backoff, e);
+                    tokio::time::sleep(backoff).await;
+                    backoff = (backoff * 2).min(self.max_backoff);
+                    attempt += 1;
+                }
+                Err(e) => return Err(e.context(format!("Adapter call failed {} times", attempt + 1))),
+            }
+        }
+    }
+}
+
+/// Turns a closure into an [Adapter] that only supports [Adapter::upsert].
+///
+/// ```rust
+/// use std::sync::Arc;
+/// use embed_anything::adapter::{Adapter, FnAdapter};
+///
+/// let adapter: Arc<dyn Adapter> = Arc::new(FnAdapter::new(|embeddings| {
+///     println!("Got {} embeddings", embeddings.len());
+/// }));
+/// ```
+pub struct FnAdapter<F> {
+    callback: Mutex<F>,
+}
+
+impl<F: FnMut(Vec<EmbedData>) + Send> FnAdapter<F> {
+    pub fn new(callback: F) -> Self {
+        Self {
+            callback: Mutex::new(callback),
+        }
+    }
+}
+
+#[async_trait]
+impl<F: FnMut(Vec<EmbedData>) + Send> Adapter for FnAdapter<F> {
+    async fn upsert(&self, data: &[EmbedData]) -> Result<()> {
+        let mut callback = self.callback.lock().unwrap();
+        callback(data.to_vec());
+        Ok(())
+    }
+
+    fn retry_policy(&self) -> RetryPolicy {
+        RetryPolicy::none()
+    }
+}
+
+/// Upserts the embeddings produced by a pipeline, regrouped into the adapter's batch size.
+pub(crate) struct AdapterSink {
+    adapter: Arc<dyn Adapter>,
+    pending: Vec<EmbedData>,
+}
+
+impl AdapterSink {
+    pub(crate) fn new(adapter: Arc<dyn Adapter>) -> Self {
+        Self {
+            adapter,
+            pending: Vec::new(),
+        }
+    }
+
+    pub(crate) async fn push(&mut self, embeddings: Vec<EmbedData>) -> Result<()> {
+        let Some(batch_size) = self.adapter.batch_size() else {
+            return self.upsert(&embeddings).await;
+        };
+
+        self.pending.extend(embeddings);
+        while self.pending.len() >= batch_size.max(1) {
+            let batch = self.pending.drain(..batch_size.max(1)).collect::<Vec<_>>();
+            self.upsert(&batch).await?;
+        }
+        Ok(())
+    }
+
+    /// Upserts the embeddings that did not fill a whole batch.
+    pub(crate) async fn flush(&mut self) -> Result<()> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        let batch = std::mem::take(&mut self.pending);
+        self.upsert(&batch).await
+    }
+
+    async fn upsert(&self, batch: &[EmbedData]) -> Result<()> {
+        self.adapter
+            .retry_policy()
+            .retry(|| self.adapter.upsert(batch))
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::embeddings::embed::EmbeddingResult;
+
+    /// Records the size of every upsert and fails the first `failures` calls.
+    struct FlakyAdapter {
+        failures: Mutex<u32>,
+        upserts: Mutex<Vec<usize>>,
+    }
+
+    #[async_trait]
+    impl Adapter for FlakyAdapter {
+        async fn upsert(&self, data: &[EmbedData]) -> Result<()> {
+            let mut failures = self.failures.lock().unwrap();
+            if *failures > 0 {
+                *failures -= 1;
+                bail!("connection reset");
+            }
+            self.upserts.lock().unwrap().push(data.len());
+            Ok(())
+        }
+
+        fn batch_size(&self) -> Option<usize> {
+            Some(4)
+        }
+
+        fn retry_policy(&self) -> RetryPolicy {
+            RetryPolicy {
+                max_retries: 2,
+                initial_backoff: Duration::from_millis(1),
+                max_backoff: Duration::from_millis(2),
+            }
+        }
+    }
+
+    fn embeddings(count: usize) -> Vec<EmbedData> {
+        (0..count)
+            .map(|i| EmbedData::new(EmbeddingResult::DenseVector(vec![i as f32]), None, None))
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_batches_and_retries() {
+        let adapter = Arc::new(FlakyAdapter {
+            failures: Mutex::new(2),
+            upserts: Mutex::new(Vec::new()),
+        });
+        let mut sink = AdapterSink::new(adapter.clone());
+        sink.push(embeddings(3)).await.unwrap();
+        sink.push(embeddings(6)).await.unwrap();
+        sink.flush().await.unwrap();
+
+        assert_eq!(*adapter.upserts.lock().unwrap(), vec![4, 4, 1]);
+    }
+
+    #[tokio::test]
+    async fn test_gives_up_after_retries() {
+        let adapter = Arc::new(FlakyAdapter {
+            failures: Mutex::new(3),
+            upserts: Mutex::new(Vec::new()),
+        });
+        let mut sink = AdapterSink::new(adapter.clone());
+        assert!(sink.push(embeddings(4)).await.is_err());
+        assert!(adapter.upserts.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,345 @@
+//! Reading the documents inside `.zip`, `.tar`, `.tar.gz` and `.tgz` archives in memory, so
+//! document dumps can be embedded without unpacking them first.
+
+use std::{
+    fs::File,
+    io::{self, BufReader, Read},
+    path::Path,
+};
+
+use anyhow::{bail, Result};
+use flate2::read::GzDecoder;
+
+/// Limits that protect against archives that decompress to far more data than they hold, such as
+/// zip bombs. An archive that exceeds one of them fails as a whole.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ArchiveLimits {
+    /// The maximum number of entries, including the ones that are skipped. Defaults to 10,000.
+    pub max_entries: usize,
+    /// The maximum decompressed size of one entry in bytes. Defaults to 100 MiB.
+    pub max_entry_size: u64,
+    /// The maximum decompressed size of all the entries that are read, in bytes. For `.tar.gz`
+    /// archives, the entries that are skipped count as well, as they are decompressed too.
+    /// Defaults to 1 GiB.
+    pub max_total_size: u64,
+    /// The maximum ratio between the decompressed and the compressed size, per entry for zip
+    /// archives and for the whole archive otherwise. Defaults to 100.
+    pub max_compression_ratio: f64,
+}
+
+impl Default for ArchiveLimits {
+    fn default() -> Self {
+        Self {
+            max_entries: 10_000,
+            max_entry_size: 100 * 1024 * 1024,
+            max_total_size: 1024 * 1024 * 1024,
+            max_compression_ratio: 100.0,
+        }
+    }
+}
+
+/// A file read from an archive.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArchiveEntry {
+    /// The path of the entry inside the archive, e.g. `reports/2024.pdf`.
+    pub path: String,
+    pub bytes: Vec<u8>,
+}
+
+/// Returns true if `path` has the extension of an archive [read_archive] can read.
+pub fn is_archive(path: impl AsRef<Path>) -> bool {
+    archive_kind(path.as_ref()).is_some()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ArchiveKind {
+    Zip,
+    Tar,
+    TarGz,
+}
+
+fn archive_kind(path: &Path) -> Option<ArchiveKind> {
+    let name = path.file_name()?.to_string_lossy().to_lowercase();
+    if name.ends_with(".zip") {
+        Some(ArchiveKind::Zip)
+    } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+        Some(ArchiveKind::TarGz)
+    } else if name.ends_with(".tar") {
+        Some(ArchiveKind::Tar)
+    } else {
+        None
+    }
+}
+
+/// Reads the files of the archive at `path` whose entry path is accepted by `keep`. Directories
+/// are skipped, and so are archives nested in the archive unless `keep` accepts them.
+pub fn read_archive(
+    path: impl AsRef<Path>,
+    limits: &ArchiveLimits,
+    keep: impl Fn(&str) -> bool,
+) -> Result<Vec<ArchiveEntry>> {
+    let path = path.as_ref();
+    let archive_size = std::fs::metadata(path)?.len();
+    let reader = BufReader::new(File::open(path)?);
+    match archive_kind(path) {
+        Some(ArchiveKind::Zip) => read_zip(reader, limits, keep),
+        Some(ArchiveKind::Tar) => read_tar(reader, archive_size, limits, keep),
+        Some(ArchiveKind::TarGz) => {
+            let reader = DecompressedLimit::new(GzDecoder::new(reader), archive_size, limits);
+            read_tar(reader, archive_size, limits, keep)
+        }
+        None => bail!("Not an archive: {}", path.display()),
+    }
+}
+
+fn read_zip(
+    reader: impl Read + std::io::Seek,
+    limits: &ArchiveLimits,
+    keep: impl Fn(&str) -> bool,
+) -> Result<Vec<ArchiveEntry>> {
+    let mut archive = zip::ZipArchive::new(reader)?;
+    if archive.len() > limits.max_entries {
+        bail!("The archive has more than {} entries", limits.max_entries);
+    }
+
+    let mut budget = Budget::new(limits);
+    let mut entries = Vec::new();
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i)?;
+        if !file.is_file() || !keep(file.name()) {
+            continue;
+        }
+        let path = file.name().to_string();
+        let compressed_size = file.compressed_size();
+        // The sizes in the header can lie, so they are checked again against what is read.
+        budget.check_entry(&path, file.size())?;
+        let bytes = budget.read(&path, &mut file)?;
+        check_ratio(&path, bytes.len() as u64, compressed_size, limits)?;
+        entries.push(ArchiveEntry { path, bytes });
+    }
+    Ok(entries)
+}
+
+fn read_tar(
+    reader: impl Read,
+    archive_size: u64,
+    limits: &ArchiveLimits,
+    keep: impl Fn(&str) -> bool,
+) -> Result<Vec<ArchiveEntry>> {
+    let mut archive = tar::Archive::new(reader);
+    let mut budget = Budget::new(limits);
+    let mut entries = Vec::new();
+    for (i, entry) in archive.entries()?.enumerate() {
+        if i >= limits.max_entries {
+            bail!("The archive has more than {} entries", limits.max_entries);
+        }
+        let mut entry = entry?;
+        let path = entry.path()?.to_string_lossy().to_string();
+        if !entry.header().entry_type().is_file() || !keep(&path) {
+            continue;
+        }
+        budget.check_entry(&path, entry.size())?;
+        let bytes = budget.read(&path, &mut entry)?;
+        entries.push(ArchiveEntry { path, bytes });
+    }
+    check_ratio("the archive", budget.total, archive_size, limits)?;
+    Ok(entries)
+}
+
+/// Fails as soon as a decompressed stream goes over [ArchiveLimits::max_total_size] or
+/// [ArchiveLimits::max_compression_ratio], counting every byte that is decompressed, including the
+/// ones of the entries that are skipped.
+struct DecompressedLimit<R> {
+    inner: R,
+    compressed_size: u64,
+    max_compression_ratio: f64,
+    max_total_size: u64,
+    total: u64,
+}
+
+impl<R> DecompressedLimit<R> {
+    fn new(inner: R, compressed_size: u64, limits: &ArchiveLimits) -> Self {
+        Self {
+            inner,
+            compressed_size,
+            max_compression_ratio: limits.max_compression_ratio,
+            max_total_size: limits.max_total_size,
+            total: 0,
+        }
+    }
+}
+
+impl<R: Read> Read for DecompressedLimit<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.total += read as u64;
+        if self.total > self.max_total_size {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("The archive decompresses to more than {} bytes", self.max_total_size),
+            ));
+        }
+        let ratio = self.total as f64 / self.compressed_size.max(1) as f64;
+        if ratio > self.max_compression_ratio {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "The compression ratio of the archive is more than {}",
+                    self.max_compression_ratio
+                ),
+            ));
+        }
+        Ok(read)
+    }
+}
+
+/// Keeps track of the bytes read from an archive so far.
+struct Budget<'a> {
+    limits: &'a ArchiveLimits,
+    total: u64,
+}
+
+impl<'a> Budget<'a> {
+    fn new(limits: &'a ArchiveLimits) -> Self {
+        Self { limits, total: 0 }
+    }
+
+    fn check_entry(&self, path: &str, size: u64) -> Result<()> {
+        if size > self.limits.max_entry_size {
+            bail!("{} is larger than {} bytes", path, self.limits.max_entry_size);
+        }
+        if self.total + size > self.limits.max_total_size {
+            bail!("The archive decompresses to more than {} bytes", self.limits.max_total_size);
+        }
+        Ok(())
+    }
+
+    /// Reads an entry, stopping as soon as it goes over the limits.
+    fn read(&mut self, path: &str, entry: &mut impl Read) -> Result<Vec<u8>> {
+        let limit = self
+            .limits
+            .max_entry_size
+            .min(self.limits.max_total_size - self.total);
+        let mut bytes = Vec::new();
+        entry.take(limit.saturating_add(1)).read_to_end(&mut bytes)?;
+        self.check_entry(path, bytes.len() as u64)?;
+        self.total += bytes.len() as u64;
+        Ok(bytes)
+    }
+}
+
+fn check_ratio(what: &str, size: u64, compressed_size: u64, limits: &ArchiveLimits) -> Result<()> {
+    let ratio = size as f64 / compressed_size.max(1) as f64;
+    if ratio > limits.max_compression_ratio {
+        bail!(
+            "The compression ratio of {} is {:.0}, more than {}",
+            what,
+            ratio,
+            limits.max_compression_ratio
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Write};
+    use tempdir::TempDir;
+    use zip::write::SimpleFileOptions;
+
+    fn write_zip(path: &Path, files: &[(&str, &[u8])]) {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, content) in files {
+            writer.start_file(*name, SimpleFileOptions::default()).unwrap();
+            writer.write_all(content).unwrap();
+        }
+        std::fs::write(path, writer.finish().unwrap().into_inner()).unwrap();
+    }
+
+    #[test]
+    fn test_read_zip() {
+        let temp_dir = TempDir::new("example").unwrap();
+        let path = temp_dir.path().join("dump.zip");
+        write_zip(
+            &path,
+            &[("docs/a.md", b"# A"), ("image.png", b"png"), ("b.txt", b"B")],
+        );
+
+        let entries = read_archive(&path, &ArchiveLimits::default(), |entry| {
+            !entry.ends_with(".png")
+        })
+        .unwrap();
+        assert_eq!(
+            entries,
+            vec![
+                ArchiveEntry { path: "docs/a.md".into(), bytes: b"# A".to_vec() },
+                ArchiveEntry { path: "b.txt".into(), bytes: b"B".to_vec() },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_read_tar_gz() {
+        let temp_dir = TempDir::new("example").unwrap();
+        let path = temp_dir.path().join("dump.tar.gz");
+        let encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        let mut builder = tar::Builder::new(encoder);
+        let content = b"Hello from a tarball";
+        let mut header = tar::Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_cksum();
+        builder.append_data(&mut header, "notes/hello.txt", &content[..]).unwrap();
+        std::fs::write(&path, builder.into_inner().unwrap().finish().unwrap()).unwrap();
+
+        let entries = read_archive(&path, &ArchiveLimits::default(), |_| true).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].path, "notes/hello.txt");
+        assert_eq!(entries[0].bytes, content);
+    }
+
+    #[test]
+    fn test_rejects_zip_bomb() {
+        let temp_dir = TempDir::new("example").unwrap();
+        let path = temp_dir.path().join("bomb.zip");
+        write_zip(&path, &[("zeros.txt", &vec![0u8; 1024 * 1024])]);
+
+        let error = read_archive(&path, &ArchiveLimits::default(), |_| true).unwrap_err();
+        assert!(error.to_string().contains("compression ratio"));
+
+        let limits = ArchiveLimits {
+            max_entry_size: 1024,
+            ..Default::default()
+        };
+        let error = read_archive(&path, &limits, |_| true).unwrap_err();
+        assert!(error.to_string().contains("larger than"));
+    }
+
+    #[test]
+    fn test_rejects_tar_gz_bomb_in_skipped_entry() {
+        let temp_dir = TempDir::new("example").unwrap();
+        let path = temp_dir.path().join("bomb.tar.gz");
+        let encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        let mut builder = tar::Builder::new(encoder);
+        let files = [("zeros.bin", vec![0u8; 4 * 1024 * 1024]), ("a.txt", b"A".to_vec())];
+        for (name, content) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_cksum();
+            builder.append_data(&mut header, name, &content[..]).unwrap();
+        }
+        std::fs::write(&path, builder.into_inner().unwrap().finish().unwrap()).unwrap();
+
+        let keep = |entry: &str| entry.ends_with(".txt");
+        let error = read_archive(&path, &ArchiveLimits::default(), keep).unwrap_err();
+        assert!(error.to_string().contains("compression ratio"));
+
+        let limits = ArchiveLimits {
+            max_total_size: 1024 * 1024,
+            max_compression_ratio: f64::INFINITY,
+            ..Default::default()
+        };
+        let error = read_archive(&path, &limits, keep).unwrap_err();
+        assert!(error.to_string().contains("decompresses to more than"));
+    }
+}
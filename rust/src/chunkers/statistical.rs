@@ -1,4 +1,6 @@
-use std::{cmp::max, sync::Arc};
+use std::{cmp::max, ops::Range, sync::Arc};
+
+use anyhow::{anyhow, bail};
 
 use crate::embeddings::{
     embed::{Embedder, TextEmbedder},
@@ -6,7 +8,7 @@ use crate::embeddings::{
     select_device,
 };
 use candle_core::Tensor;
-use itertools::{enumerate, Itertools};
+use itertools::enumerate;
 use text_splitter::{ChunkConfig, TextSplitter};
 // use text_splitter::{ChunkConfig, TextSplitter};
 use tokenizers::Tokenizer;
@@ -21,11 +23,14 @@ pub struct StatisticalChunker {
     pub max_split_tokens: usize,
     pub split_token_tolerance: usize,
     pub tokenizer: Tokenizer,
+    /// Sizes the splits the text is first cut into.
+    pub split_tokenizer: Tokenizer,
     pub verbose: bool,
 }
 impl Default for StatisticalChunker {
     fn default() -> Self {
         let tokenizer = Tokenizer::from_pretrained("BEE-spoke-data/cl100k_base-mlm", None).unwrap();
+        let split_tokenizer = Tokenizer::from_pretrained("bert-base-cased", None).unwrap();
         let encoder = Arc::new(Embedder::Text(TextEmbedder::Jina(Box::new(
             JinaEmbedder::default(),
         ))));
@@ -40,12 +45,15 @@ impl Default for StatisticalChunker {
             max_split_tokens: 512,
             split_token_tolerance: 10,
             tokenizer,
+            split_tokenizer,
             verbose: false,
         }
     }
 }
 
 impl StatisticalChunker {
+    /// Fails if `encoder` does not return one dense vector per text, e.g. a sparse or multi-vector
+    /// model, as splits are compared by the cosine similarity of their embeddings.
     #[allow(clippy::too_many_arguments)]
     pub fn new(
         encoder: Arc<Embedder>,
@@ -57,8 +65,11 @@ impl StatisticalChunker {
         split_token_tolerance: usize,
         tokenizer: Tokenizer,
         verbose: bool,
-    ) -> Self {
-        Self {
+    ) -> anyhow::Result<Self> {
+        check_encoder(&encoder)?;
+        let split_tokenizer =
+            Tokenizer::from_pretrained("bert-base-cased", None).map_err(|e| anyhow!(e))?;
+        Ok(Self {
             encoder,
             device: select_device(),
             threshold_adjustment,
@@ -68,8 +79,17 @@ impl StatisticalChunker {
             max_split_tokens,
             split_token_tolerance,
             tokenizer,
+            split_tokenizer,
             verbose,
-        }
+        })
+    }
+
+    /// Creates a chunker with the default thresholds that scores splits with `encoder`.
+    pub fn from_encoder(encoder: Arc<Embedder>) -> anyhow::Result<Self> {
+        check_encoder(&encoder)?;
+        let tokenizer = Tokenizer::from_pretrained("BEE-spoke-data/cl100k_base-mlm", None)
+            .map_err(|e| anyhow!(e))?;
+        Self::new(encoder, 0.01, true, 5, 100, 512, 10, tokenizer, false)
     }
 
     pub fn split_into_sentences(&self, text: &str, chunk_size: usize) -> Option<Vec<String>> {
@@ -108,73 +128,80 @@ impl StatisticalChunker {
         Some(chunks)
     }
 
-    pub async fn chunk(&self, text: &str, batch_size: usize) -> Vec<String> {
-        let splitter = TextSplitter::new(
-            ChunkConfig::new(50)
-                .with_sizer(Tokenizer::from_pretrained("bert-base-cased", None).unwrap()),
-        );
-        let splits = splitter.chunks(text).collect::<Vec<_>>();
-        // let splits = self.split_into_sentences(text, 50).unwrap();
+    pub async fn chunk(&self, text: &str, batch_size: usize) -> anyhow::Result<Vec<String>> {
+        let chunks = self.chunk_indices(text, batch_size).await?;
+        Ok(chunks.into_iter().map(|(_, chunk)| chunk.to_string()).collect())
+    }
+
+    /// Splits `text` into semantic chunks, and returns them with their byte offsets in `text`. A
+    /// chunk is a contiguous slice of `text`.
+    pub async fn chunk_indices<'a>(
+        &self,
+        text: &'a str,
+        batch_size: usize,
+    ) -> anyhow::Result<Vec<(usize, &'a str)>> {
+        let splitter = TextSplitter::new(ChunkConfig::new(50).with_sizer(&self.split_tokenizer));
+        let splits: Vec<Range<usize>> = splitter
+            .chunk_indices(text)
+            .map(|(offset, split)| offset..offset + split.len())
+            .collect();
         if self.verbose {
             for split in splits.iter() {
-                println!("-----Split---\n{}", split);
+                println!("-----Split---\n{}", &text[split.clone()]);
             }
         }
-        let mut chunks: Vec<String> = Vec::new();
-        let mut last_chunk = String::new();
-
-        for i in &(0..splits.len()).chunks(batch_size) {
-            let indices = i.collect::<Vec<_>>();
-            let mut batch_splits = indices
-                .into_iter()
-                .map(|idx| splits[idx])
-                .collect::<Vec<_>>();
-
-            if !last_chunk.is_empty() {
-                batch_splits = vec![&last_chunk[..]]
-                    .into_iter()
-                    .chain(batch_splits)
-                    .collect::<Vec<&str>>();
+        let mut chunks: Vec<Range<usize>> = Vec::new();
+        let mut last_chunk: Option<Range<usize>> = None;
+
+        // Slice chunks rather than `Itertools::chunks`, which is not `Send` across the await below.
+        for batch in splits.chunks(batch_size) {
+            let mut batch_splits = batch.to_vec();
+            if let Some(last_chunk) = last_chunk.take() {
+                batch_splits.insert(0, last_chunk);
             }
+            let texts: Vec<&str> = batch_splits.iter().map(|split| &text[split.clone()]).collect();
 
-            let encoded_splits = self
-                .encoder
-                .embed(&batch_splits, Some(16), None)
-                .await
-                .unwrap();
+            let encoded_splits = self.encoder.embed(&texts, Some(16), None).await?;
             let encoded_splits = encoded_splits
                 .into_iter()
-                .map(|x| x.to_dense().unwrap())
-                .collect::<Vec<_>>();
+                .map(|x| x.to_dense())
+                .collect::<anyhow::Result<Vec<_>>>()?;
 
-            let similarities = self._calculate_similarity_scores(&encoded_splits);
-            let calculated_threshold = self._find_optimal_threshold(&batch_splits, &similarities);
-
-            let split_indices = self._find_split_indices(&similarities, calculated_threshold);
-            let doc_chunks: Vec<String> = self._split_documents(&batch_splits, split_indices);
-
-            if doc_chunks.len() > 1 {
-                //add doc chunks to chunks
-                chunks.extend(doc_chunks[..doc_chunks.len() - 1].iter().cloned());
-                // last chunk is last element of doc_chunks
-                last_chunk = doc_chunks.last().unwrap().clone();
+            let similarities = self._calculate_similarity_scores(&encoded_splits)?;
+            // Too few splits to estimate a threshold, keep them together.
+            let groups = if similarities.len() < 2 {
+                vec![0..batch_splits.len()]
             } else {
-                last_chunk = doc_chunks[0].clone();
-            }
-        }
-        if !last_chunk.is_empty() {
-            chunks.push(last_chunk);
+                let calculated_threshold = self._find_optimal_threshold(&texts, &similarities)?;
+                let split_indices = self._find_split_indices(&similarities, calculated_threshold);
+                self._split_documents(&texts, split_indices)?
+            };
+            let mut doc_chunks: Vec<Range<usize>> = groups
+                .into_iter()
+                .map(|group| batch_splits[group.start].start..batch_splits[group.end - 1].end)
+                .collect();
+
+            // The last chunk may continue in the next batch.
+            last_chunk = doc_chunks.pop();
+            chunks.extend(doc_chunks);
         }
+        chunks.extend(last_chunk);
 
         if self.verbose {
             for chunk in chunks.iter() {
-                println!("-----Chunk---\n{}", chunk);
+                println!("-----Chunk---\n{}", &text[chunk.clone()]);
             }
         }
-        chunks
+        Ok(chunks
+            .into_iter()
+            .map(|chunk| (chunk.start, &text[chunk]))
+            .collect())
     }
 
-    fn _calculate_similarity_scores(&self, encoded_splits: &[Vec<f32>]) -> Vec<f32> {
+    fn _calculate_similarity_scores(
+        &self,
+        encoded_splits: &[Vec<f32>],
+    ) -> anyhow::Result<Vec<f32>> {
         let embed_dim = encoded_splits[0].len();
         let mut raw_similarities: Vec<f32> = Vec::new();
 
@@ -182,61 +209,49 @@ impl StatisticalChunker {
             encoded_splits.iter().flatten().copied().collect::<Vec<_>>(),
             (encoded_splits.len(), embed_dim),
             &self.device,
-        )
-        .unwrap();
+        )?;
 
         for i in 1..encoded_splits.len() {
             let window_start = max(0, i as isize - self.window_size as isize) as usize;
-            let indexes = Tensor::arange(window_start as i64, i as i64, &self.device).unwrap();
-            let encoded_splits_window = encoded_splits_tensor.index_select(&indexes, 0).unwrap();
+            let indexes = Tensor::arange(window_start as i64, i as i64, &self.device)?;
+            let encoded_splits_window = encoded_splits_tensor.index_select(&indexes, 0)?;
 
-            let cumulative_context = encoded_splits_window.mean_keepdim(0).unwrap();
+            let cumulative_context = encoded_splits_window.mean_keepdim(0)?;
             let cumulative_context_norm = cumulative_context
-                .sqr()
-                .unwrap()
-                .get(0)
-                .unwrap()
-                .sum(0)
-                .unwrap()
-                .sqrt()
-                .unwrap();
+                .sqr()?
+                .get(0)?
+                .sum(0)?
+                .sqrt()?;
             let encoded_splits_tensor_norm = encoded_splits_tensor
-                .get(i)
-                .unwrap()
-                .sqr()
-                .unwrap()
-                .sum(0)
-                .unwrap()
-                .sqrt()
-                .unwrap();
-            let norm = (encoded_splits_tensor_norm * cumulative_context_norm).unwrap();
+                .get(i)?
+                .sqr()?
+                .sum(0)?
+                .sqrt()?;
+            let norm = (encoded_splits_tensor_norm * cumulative_context_norm)?;
             let curr_sim_score = encoded_splits_tensor
-                .get(i)
-                .unwrap()
-                .reshape((1, embed_dim))
-                .unwrap()
-                .matmul(&cumulative_context.transpose(0, 1).unwrap())
-                .unwrap()
-                .squeeze(1)
-                .unwrap();
+                .get(i)?
+                .reshape((1, embed_dim))?
+                .matmul(&cumulative_context.transpose(0, 1)?)?
+                .squeeze(1)?;
 
             let curr_sim_score_scaled = curr_sim_score
-                .broadcast_div(&norm)
-                .unwrap()
-                .get(0)
-                .unwrap()
-                .to_vec0::<f32>()
-                .unwrap();
+                .broadcast_div(&norm)?
+                .get(0)?
+                .to_vec0::<f32>()?;
             raw_similarities.push(curr_sim_score_scaled);
         }
-        raw_similarities
+        Ok(raw_similarities)
     }
 
-    fn _find_optimal_threshold(&self, batch_splits: &[&str], similarities: &Vec<f32>) -> f32 {
+    fn _find_optimal_threshold(
+        &self,
+        batch_splits: &[&str],
+        similarities: &Vec<f32>,
+    ) -> anyhow::Result<f32> {
         let tokens = self
             .tokenizer
             .encode_batch(batch_splits.to_vec(), true)
-            .unwrap();
+            .map_err(|e| anyhow!(e))?;
         let token_counts = tokens
             .iter()
             .map(|tokens| tokens.get_ids().len())
@@ -290,7 +305,7 @@ impl StatisticalChunker {
             }
             iteration += 1;
         }
-        calculated_threshold
+        Ok(calculated_threshold)
     }
     fn _find_split_indices(&self, similarities: &Vec<f32>, threshold: f32) -> Vec<usize> {
         let mut split_indices = Vec::new();
@@ -302,46 +317,60 @@ impl StatisticalChunker {
         split_indices
     }
 
-    fn _split_documents(&self, docs: &[&str], split_indices: Vec<usize>) -> Vec<String> {
-        let tokens = self.tokenizer.encode_batch(docs.to_vec(), true).unwrap();
+    /// Groups the consecutive `docs` into chunks, and returns the range of the indices of the docs
+    /// of every chunk.
+    fn _split_documents(
+        &self,
+        docs: &[&str],
+        split_indices: Vec<usize>,
+    ) -> anyhow::Result<Vec<Range<usize>>> {
+        let tokens = self
+            .tokenizer
+            .encode_batch(docs.to_vec(), true)
+            .map_err(|e| anyhow!(e))?;
         let token_counts = tokens
             .iter()
             .map(|tokens| tokens.get_ids().len())
             .collect::<Vec<_>>();
 
-        let mut chunks: Vec<String> = Vec::new();
-        let mut current_split = Vec::new();
+        let mut chunks: Vec<Range<usize>> = Vec::new();
+        let mut current_start = 0;
         let mut current_tokens_count = 0;
 
-        for (doc_idx, doc) in enumerate(docs) {
-            let doc_token_count = token_counts[doc_idx];
+        for (doc_idx, doc_token_count) in enumerate(token_counts) {
             if split_indices.contains(&(doc_idx + 1))
                 && self.min_split_tokens <= current_tokens_count + doc_token_count
                 && current_tokens_count + doc_token_count <= self.max_split_tokens
             {
-                current_split.push(doc);
-
-                chunks.push(current_split.into_iter().join("\n"));
-                current_split = Vec::new();
+                chunks.push(current_start..doc_idx + 1);
+                current_start = doc_idx + 1;
                 current_tokens_count = 0;
                 continue;
             }
-            if current_tokens_count + doc_token_count > self.max_split_tokens {
-                if current_tokens_count >= self.min_split_tokens {
-                    chunks.push(current_split.into_iter().join("\n"));
-                }
-                current_split = Vec::new();
+            // A chunk shorter than `min_split_tokens` is kept as well, so that no text is lost.
+            if current_tokens_count + doc_token_count > self.max_split_tokens
+                && current_start < doc_idx
+            {
+                chunks.push(current_start..doc_idx);
+                current_start = doc_idx;
                 current_tokens_count = 0;
             }
-            current_split.push(doc);
             current_tokens_count += doc_token_count;
         }
 
-        if !current_split.is_empty() {
-            chunks.push(current_split.into_iter().join("\n"));
+        if current_start < docs.len() {
+            chunks.push(current_start..docs.len());
         }
 
-        chunks
+        Ok(chunks)
+    }
+}
+
+fn check_encoder(encoder: &Embedder) -> anyhow::Result<()> {
+    match encoder {
+        Embedder::Text(embedder) if embedder.is_dense() => Ok(()),
+        Embedder::Text(_) => bail!("Semantic splitting requires a dense text encoder"),
+        Embedder::Vision(_) => bail!("Semantic splitting requires a text encoder"),
     }
 }
 
@@ -349,7 +378,12 @@ impl StatisticalChunker {
 mod tests {
     use std::path::PathBuf;
 
-    use crate::{extract_document, file_processor::pdf_processor::OcrConfig};
+    use crate::embeddings::embed::EmbeddingResult;
+    use crate::embeddings::local::bert::BertEmbed;
+    use crate::file_processor::html_processor::ContentExtraction;
+    use crate::file_processor::markdown_processor::ChunkSizing;
+    use crate::file_processor::pdf_processor::{OcrConfig, PdfConfig};
+    use crate::{extract_document, Chunking};
 
     use super::*;
 
@@ -357,19 +391,46 @@ mod tests {
     async fn test_statistical_chunker() {
         let text = extract_document(
             &PathBuf::from("../test_files/attention.pdf"),
-            10,
-            0,
+            &Chunking {
+                chunk_size: 10,
+                overlap: 0,
+                sizing: ChunkSizing::Characters,
+            },
             OcrConfig {
                 use_ocr: false,
                 tesseract_path: None,
             },
+            &ContentExtraction::default(),
+            &PdfConfig::default(),
         )
         .unwrap();
         let chunker = StatisticalChunker {
             verbose: true,
             ..Default::default()
         };
-        let chunks = chunker.chunk(&text.chunks.join("\n"), 10).await;
+        let chunks = chunker.chunk(&text.text, 10).await.unwrap();
         assert!(chunks.len() > 0);
     }
+
+    struct MultiVectorEmbedder;
+
+    impl BertEmbed for MultiVectorEmbedder {
+        fn embed(
+            &self,
+            text_batch: &[&str],
+            _batch_size: Option<usize>,
+            _late_chunking: Option<bool>,
+        ) -> anyhow::Result<Vec<EmbeddingResult>> {
+            Ok(text_batch
+                .iter()
+                .map(|_| EmbeddingResult::MultiVector(vec![vec![1.0]]))
+                .collect())
+        }
+    }
+
+    #[test]
+    fn test_rejects_multi_vector_encoder() {
+        let encoder = Embedder::Text(TextEmbedder::ColBert(Box::new(MultiVectorEmbedder)));
+        assert!(StatisticalChunker::from_encoder(Arc::new(encoder)).is_err());
+    }
 }
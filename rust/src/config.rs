@@ -1,5 +1,14 @@
+use crate::archive::ArchiveLimits;
+use crate::dedup::Deduplication;
 use crate::embeddings::embed::Embedder;
+use crate::file_loader::FileFilter;
+use crate::file_processor::html_processor::ContentExtraction;
+use crate::file_processor::pdf_processor::PdfConfig;
+use crate::progress::{ProgressCallback, ProgressEvent};
+use crate::report::{FileReport, ReportCallback};
+use std::collections::HashMap;
 use std::sync::Arc;
+use tokio_util::sync::CancellationToken;
 
 /// Configuration for text embedding.
 ///
@@ -38,6 +47,10 @@ pub struct TextEmbedConfig {
     /// Controls the size of each "batch" of data sent to the embedder. The default value depends
     /// largely on the embedder, but will be set to 32 when using [TextEmbedConfig::default()]
     pub batch_size: Option<usize>,
+    /// Measures `chunk_size` in tokens of the embedder's tokenizer instead of characters, so chunks
+    /// are never truncated by the model. The chunk size is capped at the model's maximum length,
+    /// and defaults to it when `chunk_size` is None. Defaults to false.
+    pub token_chunking: Option<bool>,
     /// When using an adapter, this controls the size of the buffer. Defaults to 100.
     pub buffer_size: Option<usize>,
     /// Controls how documents are split into segments. See [SplittingStrategy] for options.
@@ -45,11 +58,52 @@ pub struct TextEmbedConfig {
     pub splitting_strategy: SplittingStrategy,
     /// When embedding a PDF, controls whether **o**ptical **c**haracter **r**ecognition is used on
     /// the PDF to extract text. This process involves rendering the PDF as a series of images, and
-    /// extracting text from the images. Defaults to false.
+    /// extracting text from the images. Defaults to false. To only use OCR on the pages that have
+    /// no text, see [PdfConfig::auto_ocr].
     pub use_ocr: Option<bool>,
     pub tesseract_path: Option<String>,
     /// When embedding a document, controls whether late chunking is used. Use this to take larger context into account for embedding. Defaults to false.
     pub late_chunking: Option<bool>,
+    /// When embedding a batch of files or a directory, receives a [FileReport] for every file once
+    /// it has been processed, including files that failed. Defaults to None.
+    pub report_callback: Option<ReportCallback>,
+    /// When embedding a batch of files or a directory, controls how many files are extracted in
+    /// parallel. Defaults to None, which uses one worker per available CPU core.
+    pub extraction_workers: Option<usize>,
+    /// When embedding a batch of files or a directory, controls how many chunks may wait to be
+    /// embedded, and how many embedded chunks may wait for the adapter, before extraction is
+    /// paused. Bounds the memory used when the model is slower than extraction. Defaults to 1000.
+    pub queue_depth: Option<usize>,
+    /// When embedding a batch of files or a directory, receives [ProgressEvent]s instead of a
+    /// progress bar being drawn on the terminal. Defaults to None.
+    pub progress_callback: Option<ProgressCallback>,
+    /// When embedding a batch of files or a directory, stops the job once cancelled. The chunks
+    /// that are already buffered are still embedded and passed on. Defaults to None.
+    pub cancellation_token: Option<CancellationToken>,
+    /// When embedding a batch of files or a directory, embeds chunks that are duplicates of an
+    /// earlier chunk of the run only once. Every occurrence still gets its own embedding, with the
+    /// metadata of its file and a reference to the first occurrence. Ignored with late chunking.
+    /// Defaults to None, or no deduplication.
+    pub deduplication: Option<Deduplication>,
+    /// With deduplication, how many distinct chunks the embeddings are remembered for. The least
+    /// recently seen chunks are forgotten, and embedded again when they repeat. Defaults to 10000.
+    pub deduplication_cache_size: Option<usize>,
+    /// Overrides of the chunking and OCR settings for some file types, keyed by lowercase
+    /// extension without the dot, e.g. "md". Defaults to no overrides.
+    pub file_type_configs: HashMap<String, FileTypeConfig>,
+    /// When embedding a directory, controls which files are picked up besides their extension,
+    /// e.g. glob patterns and `.gitignore` files. Defaults to [FileFilter::default].
+    pub file_filter: Option<FileFilter>,
+    /// When embedding a batch of files or a directory, limits how much data is read from the
+    /// `.zip`, `.tar`, `.tar.gz` and `.tgz` archives among the files, whose documents are embedded
+    /// in memory. Defaults to [ArchiveLimits::default].
+    pub archive_limits: Option<ArchiveLimits>,
+    /// When embedding HTML, controls which part of every page is embedded, e.g. only its main
+    /// content without navigation and footers. Defaults to None, which embeds the whole page.
+    pub content_extraction: Option<ContentExtraction>,
+    /// When embedding a PDF, controls which pages are extracted, and whether chunks may run over
+    /// page breaks. Defaults to None, which splits every page of the PDF on its own.
+    pub pdf_config: Option<PdfConfig>,
 }
 
 impl Default for TextEmbedConfig {
@@ -58,11 +112,24 @@ impl Default for TextEmbedConfig {
             chunk_size: Some(1000),
             overlap_ratio: Some(0.0),
             batch_size: Some(32),
+            token_chunking: None,
             buffer_size: Some(100),
             splitting_strategy: SplittingStrategy::Sentence,
             late_chunking: None,
             use_ocr: None,
             tesseract_path: None,
+            report_callback: None,
+            extraction_workers: None,
+            queue_depth: Some(1000),
+            progress_callback: None,
+            cancellation_token: None,
+            deduplication: None,
+            deduplication_cache_size: Some(10_000),
+            file_type_configs: HashMap::new(),
+            file_filter: None,
+            archive_limits: None,
+            content_extraction: None,
+            pdf_config: None,
         }
     }
 }
@@ -95,6 +162,14 @@ impl TextEmbedConfig {
         self
     }
 
+    /// Measure chunks in tokens of the embedder's tokenizer. Without a `chunk_size`, chunks are as
+    /// long as the model's maximum sequence length.
+    pub fn with_token_chunking(mut self, chunk_size: Option<usize>) -> Self {
+        self.token_chunking = Some(true);
+        self.chunk_size = chunk_size;
+        self
+    }
+
     pub fn with_batch_size(mut self, size: usize) -> Self {
         self.batch_size = Some(size);
         self
@@ -125,11 +200,146 @@ impl TextEmbedConfig {
         self
     }
 
+    /// Receive a [FileReport] for every file of a batch or directory run, e.g. to log or retry the
+    /// files that failed.
+    pub fn with_report_callback(
+        mut self,
+        callback: impl Fn(FileReport) + Send + Sync + 'static,
+    ) -> Self {
+        self.report_callback = Some(Arc::new(callback));
+        self
+    }
+
+    pub fn with_extraction_workers(mut self, workers: usize) -> Self {
+        self.extraction_workers = Some(workers);
+        self
+    }
+
+    pub fn with_queue_depth(mut self, depth: usize) -> Self {
+        self.queue_depth = Some(depth);
+        self
+    }
+
+    /// Receive [ProgressEvent]s, e.g. to show the progress of a job in a GUI.
+    pub fn with_progress_callback(
+        mut self,
+        callback: impl Fn(ProgressEvent) + Send + Sync + 'static,
+    ) -> Self {
+        self.progress_callback = Some(Arc::new(callback));
+        self
+    }
+
+    /// Cancel the job from elsewhere by calling [CancellationToken::cancel] on a clone of `token`.
+    pub fn with_cancellation_token(mut self, token: CancellationToken) -> Self {
+        self.cancellation_token = Some(token);
+        self
+    }
+
+    /// Embed repeated chunks, such as license headers or footers, only once per run.
+    pub fn with_deduplication(mut self, deduplication: Deduplication) -> Self {
+        self.deduplication = Some(deduplication);
+        self
+    }
+
+    pub fn with_deduplication_cache_size(mut self, size: usize) -> Self {
+        self.deduplication_cache_size = Some(size);
+        self
+    }
+
+    /// Override the chunking and OCR settings for files with `extension`, e.g. larger chunks for
+    /// markdown files or OCR for PDFs only.
+    pub fn with_file_type_config(mut self, extension: &str, config: FileTypeConfig) -> Self {
+        self.file_type_configs
+            .insert(normalize_extension(extension), config);
+        self
+    }
+
+    /// Skip files in a directory, e.g. `node_modules` or files larger than a few megabytes.
+    pub fn with_file_filter(mut self, filter: FileFilter) -> Self {
+        self.file_filter = Some(filter);
+        self
+    }
+
+    /// Limit how much data is read from archives, e.g. to accept larger document dumps.
+    pub fn with_archive_limits(mut self, limits: ArchiveLimits) -> Self {
+        self.archive_limits = Some(limits);
+        self
+    }
+
+    /// Embed only part of every HTML page, see [ContentExtraction].
+    pub fn with_content_extraction(mut self, extraction: ContentExtraction) -> Self {
+        self.content_extraction = Some(extraction);
+        self
+    }
+
+    /// Extract only some pages of PDFs, or let chunks run over page breaks, see [PdfConfig].
+    pub fn with_pdf_config(mut self, pdf_config: PdfConfig) -> Self {
+        self.pdf_config = Some(pdf_config);
+        self
+    }
+
+    /// Returns the config that applies to files with `extension`, with its overrides applied.
+    pub fn for_file_type(&self, extension: &str) -> TextEmbedConfig {
+        let mut config = self.clone();
+        if let Some(file_type) = self.file_type_configs.get(&normalize_extension(extension)) {
+            if let Some(chunk_size) = file_type.chunk_size {
+                config.chunk_size = Some(chunk_size);
+            }
+            if let Some(overlap_ratio) = file_type.overlap_ratio {
+                config.overlap_ratio = Some(overlap_ratio);
+            }
+            if let Some(use_ocr) = file_type.use_ocr {
+                config.use_ocr = Some(use_ocr);
+            }
+        }
+        config
+    }
+
     pub fn build(self) -> TextEmbedConfig {
         self
     }
 }
 
+/// Settings of a [TextEmbedConfig] overridden for one file type. Settings left to None are taken
+/// from the [TextEmbedConfig].
+///
+/// # Example
+///
+/// ```rust
+/// use embed_anything::config::{FileTypeConfig, TextEmbedConfig};
+/// let config = TextEmbedConfig::default()
+///     .with_ocr(false, None)
+///     .with_file_type_config("md", FileTypeConfig::default().with_chunk_size(2000))
+///     .with_file_type_config("pdf", FileTypeConfig::default().with_ocr(true));
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FileTypeConfig {
+    pub chunk_size: Option<usize>,
+    pub overlap_ratio: Option<f32>,
+    pub use_ocr: Option<bool>,
+}
+
+impl FileTypeConfig {
+    pub fn with_chunk_size(mut self, size: usize) -> Self {
+        self.chunk_size = Some(size);
+        self
+    }
+
+    pub fn with_overlap_ratio(mut self, overlap_ratio: f32) -> Self {
+        self.overlap_ratio = Some(overlap_ratio);
+        self
+    }
+
+    pub fn with_ocr(mut self, use_ocr: bool) -> Self {
+        self.use_ocr = Some(use_ocr);
+        self
+    }
+}
+
+fn normalize_extension(extension: &str) -> String {
+    extension.trim_start_matches('.').to_lowercase()
+}
+
 #[derive(Clone)]
 pub enum SplittingStrategy {
     /// Splits text-based content by sentence, resulting in one embedding per sentence.
@@ -145,18 +355,69 @@ pub enum SplittingStrategy {
 #[derive(Clone)]
 pub struct ImageEmbedConfig {
     pub buffer_size: Option<usize>, // Required for adapter. Default is 100.
+    /// Receives [ProgressEvent]s instead of a progress bar being drawn on the terminal.
+    pub progress_callback: Option<ProgressCallback>,
+    /// Stops the job once cancelled. The images that are already buffered are still embedded.
+    pub cancellation_token: Option<CancellationToken>,
+    /// Controls which images in a directory are picked up besides their extension.
+    pub file_filter: Option<FileFilter>,
 }
 
 impl Default for ImageEmbedConfig {
     fn default() -> Self {
         Self {
             buffer_size: Some(100),
+            progress_callback: None,
+            cancellation_token: None,
+            file_filter: None,
         }
     }
 }
 
 impl ImageEmbedConfig {
     pub fn new(buffer_size: Option<usize>) -> Self {
-        Self { buffer_size }
+        Self {
+            buffer_size,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_progress_callback(
+        mut self,
+        callback: impl Fn(ProgressEvent) + Send + Sync + 'static,
+    ) -> Self {
+        self.progress_callback = Some(Arc::new(callback));
+        self
+    }
+
+    pub fn with_cancellation_token(mut self, token: CancellationToken) -> Self {
+        self.cancellation_token = Some(token);
+        self
+    }
+
+    pub fn with_file_filter(mut self, filter: FileFilter) -> Self {
+        self.file_filter = Some(filter);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_for_file_type() {
+        let config = TextEmbedConfig::default()
+            .with_chunk_size(500, Some(0.1))
+            .with_file_type_config(".MD", FileTypeConfig::default().with_chunk_size(2000))
+            .with_file_type_config("pdf", FileTypeConfig::default().with_ocr(true));
+
+        let markdown = config.for_file_type("md");
+        assert_eq!(markdown.chunk_size, Some(2000));
+        assert_eq!(markdown.overlap_ratio, Some(0.1));
+        assert_eq!(markdown.use_ocr, None);
+
+        assert_eq!(config.for_file_type("PDF").use_ocr, Some(true));
+        assert_eq!(config.for_file_type("txt").chunk_size, Some(500));
     }
 }
@@ -0,0 +1,594 @@
+//! Crawls websites from a set of seed URLs, so a whole site or documentation section can be
+//! embedded instead of one page at a time.
+
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use regex::Regex;
+use scraper::{Html, Selector};
+use url::Url;
+
+/// Which pages a [Crawler] visits, and how fast.
+///
+/// # Example
+///
+/// ```rust
+/// use std::time::Duration;
+/// use embed_anything::crawler::CrawlConfig;
+/// let config = CrawlConfig::default()
+///     .with_max_depth(3)
+///     .with_allowed_path_prefixes(&["/docs"])
+///     .with_delay(Duration::from_secs(1));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct CrawlConfig {
+    /// How many links away from a seed URL to go, where 0 only visits the seeds. Defaults to 2.
+    pub max_depth: usize,
+    /// Stops the crawl after this many pages. Defaults to 1000.
+    pub max_pages: Option<usize>,
+    /// The hosts links are followed to. Subdomains of a host are allowed as well. Defaults to the
+    /// hosts of the seed URLs.
+    pub allowed_domains: Vec<String>,
+    /// The paths links are followed to, e.g. `/docs`. Defaults to every path.
+    pub allowed_path_prefixes: Vec<String>,
+    /// Skips the pages the `robots.txt` of a host disallows for `user_agent`, and waits for its
+    /// `Crawl-delay`. Defaults to true.
+    pub respect_robots_txt: bool,
+    /// The minimum time between two requests to the same host. Defaults to 500ms.
+    pub delay: Duration,
+    pub user_agent: String,
+    /// The timeout of every request. Defaults to 30s.
+    pub timeout: Duration,
+}
+
+impl Default for CrawlConfig {
+    fn default() -> Self {
+        Self {
+            max_depth: 2,
+            max_pages: Some(1000),
+            allowed_domains: Vec::new(),
+            allowed_path_prefixes: Vec::new(),
+            respect_robots_txt: true,
+            delay: Duration::from_millis(500),
+            user_agent: "EmbedAnything".to_string(),
+            timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+impl CrawlConfig {
+    pub fn with_max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = depth;
+        self
+    }
+
+    pub fn with_max_pages(mut self, pages: usize) -> Self {
+        self.max_pages = Some(pages);
+        self
+    }
+
+    pub fn with_allowed_domains(mut self, domains: &[&str]) -> Self {
+        self.allowed_domains = domains.iter().map(|d| d.to_lowercase()).collect();
+        self
+    }
+
+    pub fn with_allowed_path_prefixes(mut self, prefixes: &[&str]) -> Self {
+        self.allowed_path_prefixes = prefixes.iter().map(|p| p.to_string()).collect();
+        self
+    }
+
+    pub fn with_respect_robots_txt(mut self, respect_robots_txt: bool) -> Self {
+        self.respect_robots_txt = respect_robots_txt;
+        self
+    }
+
+    pub fn with_delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    pub fn with_user_agent(mut self, user_agent: &str) -> Self {
+        self.user_agent = user_agent.to_string();
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Whether `url` is on an allowed domain and under an allowed path.
+    fn allows(&self, url: &Url) -> bool {
+        let Some(host) = url.host_str().map(str::to_lowercase) else {
+            return false;
+        };
+        let domain_allowed = self
+            .allowed_domains
+            .iter()
+            .any(|domain| host == *domain || host.ends_with(&format!(".{}", domain)));
+        let path_allowed = self.allowed_path_prefixes.is_empty()
+            || self
+                .allowed_path_prefixes
+                .iter()
+                .any(|prefix| url.path().starts_with(prefix.as_str()));
+        domain_allowed && path_allowed
+    }
+}
+
+/// A page returned by [Crawler::next_page].
+#[derive(Debug, Clone)]
+pub struct CrawledPage {
+    /// The canonical URL of the page if it declares one, otherwise the URL it was fetched from.
+    pub url: String,
+    /// How many links away from a seed URL the page was found.
+    pub depth: usize,
+    pub html: String,
+}
+
+/// Visits the pages reachable from the seed URLs breadth first.
+///
+/// Every URL is fetched once, without its fragment, and a page whose canonical URL was already
+/// returned is skipped. Redirect targets are queued like links, so they are only fetched if the
+/// crawl may visit them and robots.txt allows it. Pages that fail to load are logged and skipped.
+pub struct Crawler {
+    config: CrawlConfig,
+    client: reqwest::Client,
+    queue: VecDeque<(Url, usize)>,
+    /// URLs that were queued, so they are only fetched once.
+    queued: HashSet<String>,
+    /// Canonical URLs of the pages that were returned.
+    returned: HashSet<String>,
+    hosts: HashMap<String, Host>,
+}
+
+/// What the crawler knows about a host.
+struct Host {
+    robots: Option<RobotsTxt>,
+    next_request: Instant,
+}
+
+impl Crawler {
+    pub fn new(seeds: &[String], mut config: CrawlConfig) -> Result<Self> {
+        let seeds = seeds
+            .iter()
+            .map(|seed| Url::parse(seed))
+            .collect::<Result<Vec<_>, _>>()?;
+        if config.allowed_domains.is_empty() {
+            config.allowed_domains = seeds
+                .iter()
+                .filter_map(|seed| seed.host_str().map(str::to_lowercase))
+                .collect();
+        }
+        // Redirects are queued like links, so their targets are checked before they are fetched.
+        let client = reqwest::Client::builder()
+            .user_agent(config.user_agent.clone())
+            .timeout(config.timeout)
+            .redirect(reqwest::redirect::Policy::none())
+            .build()?;
+
+        let mut crawler = Self {
+            config,
+            client,
+            queue: VecDeque::new(),
+            queued: HashSet::new(),
+            returned: HashSet::new(),
+            hosts: HashMap::new(),
+        };
+        for seed in seeds {
+            crawler.enqueue(seed, 0);
+        }
+        Ok(crawler)
+    }
+
+    /// Fetches pages until one can be returned. Returns `None` once every reachable page has been
+    /// visited, or `max_pages` pages have been returned.
+    pub async fn next_page(&mut self) -> Option<CrawledPage> {
+        loop {
+            if self
+                .config
+                .max_pages
+                .is_some_and(|max_pages| self.returned.len() >= max_pages)
+            {
+                return None;
+            }
+            let (url, depth) = self.queue.pop_front()?;
+            if !self.allowed_by_robots(&url).await {
+                continue;
+            }
+
+            let html = match self.fetch(&url).await {
+                Ok(Fetched::Page(html)) => html,
+                // A redirect target is queued like a link to it, at the same depth, and only
+                // fetched once it passes the same checks.
+                Ok(Fetched::Redirect(target)) => {
+                    self.enqueue(target, depth);
+                    continue;
+                }
+                Ok(Fetched::Skipped) => continue,
+                Err(e) => {
+                    eprintln!("Skipping {}: {:?}", url, e);
+                    continue;
+                }
+            };
+            let links = parse_links(&html, &url);
+
+            if depth < self.config.max_depth {
+                for link in links.links {
+                    self.enqueue(link, depth + 1);
+                }
+            }
+            let canonical = links.canonical.unwrap_or(url);
+            // A canonical URL is not fetched again when it is linked to later on.
+            self.queued.insert(canonical.to_string());
+            if !self.returned.insert(canonical.to_string()) {
+                continue;
+            }
+            return Some(CrawledPage {
+                url: canonical.to_string(),
+                depth,
+                html,
+            });
+        }
+    }
+
+    fn enqueue(&mut self, mut url: Url, depth: usize) {
+        url.set_fragment(None);
+        if !matches!(url.scheme(), "http" | "https") || !self.config.allows(&url) {
+            return;
+        }
+        if self.queued.insert(url.to_string()) {
+            self.queue.push_back((url, depth));
+        }
+    }
+
+    async fn allowed_by_robots(&mut self, url: &Url) -> bool {
+        if !self.config.respect_robots_txt {
+            return true;
+        }
+        let host = host_key(url);
+        if self.hosts.get(&host).is_none_or(|host| host.robots.is_none()) {
+            let robots = self.fetch_robots(url).await;
+            self.host(url).robots = Some(robots);
+        }
+        let robots = self.hosts[&host].robots.as_ref();
+        let path = match url.query() {
+            Some(query) => format!("{}?{}", url.path(), query),
+            None => url.path().to_string(),
+        };
+        robots.is_none_or(|robots| robots.is_allowed(&path))
+    }
+
+    /// Fetches the `robots.txt` of the host of `url`. A missing or unreadable file allows
+    /// everything.
+    async fn fetch_robots(&mut self, url: &Url) -> RobotsTxt {
+        let Ok(robots_url) = url.join("/robots.txt") else {
+            return RobotsTxt::default();
+        };
+        match self.get(&robots_url).await {
+            Ok(response) if response.status().is_success() => match response.text().await {
+                Ok(content) => RobotsTxt::parse(&content, &self.config.user_agent),
+                Err(_) => RobotsTxt::default(),
+            },
+            _ => RobotsTxt::default(),
+        }
+    }
+
+    /// Fetches the page at `url` without following redirects.
+    async fn fetch(&mut self, url: &Url) -> Result<Fetched> {
+        let response = self.get(url).await?.error_for_status()?;
+        if response.status().is_redirection() {
+            let target = response
+                .headers()
+                .get(reqwest::header::LOCATION)
+                .and_then(|location| location.to_str().ok())
+                .and_then(|location| url.join(location).ok());
+            return Ok(target.map_or(Fetched::Skipped, Fetched::Redirect));
+        }
+        let is_html = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|content_type| content_type.to_str().ok())
+            .is_none_or(|content_type| content_type.contains("html"));
+        if !is_html {
+            return Ok(Fetched::Skipped);
+        }
+        Ok(Fetched::Page(response.text().await?))
+    }
+
+    /// Sends a GET request once the delay since the last request to the host has passed.
+    async fn get(&mut self, url: &Url) -> reqwest::Result<reqwest::Response> {
+        let delay = self.config.delay;
+        let host = self.host(url);
+        let crawl_delay = host
+            .robots
+            .as_ref()
+            .and_then(|robots| robots.crawl_delay)
+            .unwrap_or_default();
+        let wait = host.next_request.saturating_duration_since(Instant::now());
+        host.next_request = Instant::now().max(host.next_request) + delay.max(crawl_delay);
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+        self.client.get(url.clone()).send().await
+    }
+
+    fn host(&mut self, url: &Url) -> &mut Host {
+        self.hosts.entry(host_key(url)).or_insert_with(|| Host {
+            robots: None,
+            next_request: Instant::now(),
+        })
+    }
+}
+
+/// What [Crawler::fetch] got back.
+enum Fetched {
+    /// The HTML of the page.
+    Page(String),
+    /// A redirect to the URL, resolved against the URL that was fetched.
+    Redirect(Url),
+    /// Not an HTML page, or a redirect without a location.
+    Skipped,
+}
+
+fn host_key(url: &Url) -> String {
+    format!(
+        "{}://{}:{}",
+        url.scheme(),
+        url.host_str().unwrap_or_default(),
+        url.port_or_known_default().unwrap_or_default()
+    )
+}
+
+/// The links of a page, resolved against its URL.
+struct PageLinks {
+    links: Vec<Url>,
+    canonical: Option<Url>,
+}
+
+fn parse_links(html: &str, url: &Url) -> PageLinks {
+    let document = Html::parse_document(html);
+    let base = Selector::parse("base[href]").unwrap();
+    let base = document
+        .select(&base)
+        .next()
+        .and_then(|base| url.join(base.value().attr("href")?).ok())
+        .unwrap_or_else(|| url.clone());
+
+    let anchors = Selector::parse("a[href]").unwrap();
+    let links = document
+        .select(&anchors)
+        .filter_map(|anchor| base.join(anchor.value().attr("href")?).ok())
+        .collect();
+
+    let canonical = Selector::parse("link[rel=canonical][href]").unwrap();
+    let canonical = document
+        .select(&canonical)
+        .next()
+        .and_then(|link| url.join(link.value().attr("href")?).ok())
+        .map(|mut canonical| {
+            canonical.set_fragment(None);
+            canonical
+        });
+
+    PageLinks { links, canonical }
+}
+
+/// The rules of a `robots.txt` that apply to one user agent.
+#[derive(Debug, Default)]
+struct RobotsTxt {
+    /// Whether the rule allows, and the pattern of the paths it applies to.
+    rules: Vec<(bool, String, Regex)>,
+    crawl_delay: Option<Duration>,
+}
+
+impl RobotsTxt {
+    /// Parses the group of `content` for `user_agent`, or the `*` group if there is none.
+    fn parse(content: &str, user_agent: &str) -> Self {
+        #[derive(Default)]
+        struct Group {
+            agents: Vec<String>,
+            rules: Vec<(bool, String)>,
+            crawl_delay: Option<f64>,
+        }
+
+        let mut groups: Vec<Group> = Vec::new();
+        let mut reading_agents = false;
+        for line in content.lines() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let (key, value) = (key.trim().to_lowercase(), value.trim());
+            if key == "user-agent" {
+                if !reading_agents {
+                    groups.push(Group::default());
+                }
+                reading_agents = true;
+                if let Some(group) = groups.last_mut() {
+                    group.agents.push(value.to_lowercase());
+                }
+                continue;
+            }
+            reading_agents = false;
+            let Some(group) = groups.last_mut() else {
+                continue;
+            };
+            match key.as_str() {
+                "allow" | "disallow" if !value.is_empty() => {
+                    group.rules.push((key == "allow", value.to_string()))
+                }
+                "crawl-delay" => group.crawl_delay = value.parse().ok(),
+                _ => {}
+            }
+        }
+
+        let user_agent = user_agent.to_lowercase();
+        let group = groups
+            .iter()
+            .find(|group| {
+                group
+                    .agents
+                    .iter()
+                    .any(|agent| agent != "*" && user_agent.contains(agent.as_str()))
+            })
+            .or_else(|| groups.iter().find(|group| group.agents.iter().any(|agent| agent == "*")));
+        let Some(group) = group else {
+            return Self::default();
+        };
+        Self {
+            rules: group
+                .rules
+                .iter()
+                .filter_map(|(allow, pattern)| {
+                    Some((*allow, pattern.clone(), robots_pattern(pattern)?))
+                })
+                .collect(),
+            crawl_delay: group
+                .crawl_delay
+                .filter(|delay| delay.is_finite() && *delay >= 0.0)
+                .map(Duration::from_secs_f64),
+        }
+    }
+
+    /// The longest matching rule decides, and allowing wins a tie. Paths no rule matches are
+    /// allowed.
+    fn is_allowed(&self, path: &str) -> bool {
+        self.rules
+            .iter()
+            .filter(|(_, _, regex)| regex.is_match(path))
+            .max_by_key(|(allow, pattern, _)| (pattern.len(), *allow))
+            .is_none_or(|(allow, _, _)| *allow)
+    }
+}
+
+/// Turns a `robots.txt` path pattern, where `*` matches anything and a trailing `$` ends the
+/// path, into a regex.
+fn robots_pattern(pattern: &str) -> Option<Regex> {
+    let (pattern, anchored) = match pattern.strip_suffix('$') {
+        Some(pattern) => (pattern, true),
+        None => (pattern, false),
+    };
+    let regex = pattern
+        .split('*')
+        .map(regex::escape)
+        .collect::<Vec<_>>()
+        .join(".*");
+    Regex::new(&format!("^{}{}", regex, if anchored { "$" } else { "" })).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    #[test]
+    fn test_robots_txt() {
+        let robots = RobotsTxt::parse(
+            "User-agent: OtherBot\nDisallow: /\n\nUser-agent: *\nDisallow: /private\nAllow: /private/public\nDisallow: /*.pdf$\nCrawl-delay: 2\n",
+            "EmbedAnything",
+        );
+        assert!(robots.is_allowed("/docs"));
+        assert!(!robots.is_allowed("/private/notes"));
+        assert!(robots.is_allowed("/private/public/notes"));
+        assert!(!robots.is_allowed("/docs/manual.pdf"));
+        assert!(robots.is_allowed("/docs/manual.pdf.html"));
+        assert_eq!(robots.crawl_delay, Some(Duration::from_secs(2)));
+    }
+
+    /// Serves a small website on a random local port and returns its URL.
+    async fn serve_website() -> String {
+        let pages: HashMap<&str, &str> = HashMap::from([
+            ("/robots.txt", "User-agent: *\nDisallow: /private\n"),
+            (
+                "/",
+                r#"<a href="/a">A</a> <a href="b#intro">B</a> <a href="/private/secret">Secret</a>
+                <a href="http://other.example/">Elsewhere</a>"#,
+            ),
+            ("/a", r#"<link rel="canonical" href="/"><p>Same as the home page</p>"#),
+            ("/b", r#"<a href="/c">C</a>"#),
+            ("/c", "<p>Too deep</p>"),
+            ("/private/secret", "<p>Secret</p>"),
+            ("/guide/", r#"<a href="intro">Intro</a>"#),
+            ("/guide/intro", "<p>Intro</p>"),
+        ]);
+        let redirects: HashMap<&str, &str> = HashMap::from([
+            ("/docs/old", "/guide/"),
+            ("/away", "http://other.example/"),
+            ("/docs/secret", "/private/secret"),
+        ]);
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            loop {
+                let Ok((mut stream, _)) = listener.accept().await else {
+                    return;
+                };
+                let mut request = vec![0; 4096];
+                let read = stream.read(&mut request).await.unwrap_or(0);
+                let request = String::from_utf8_lossy(&request[..read]);
+                let path = request.split_whitespace().nth(1).unwrap_or("/");
+                let response = match (pages.get(path), redirects.get(path)) {
+                    (_, Some(location)) => format!(
+                        "HTTP/1.1 301 Moved Permanently\r\nLocation: {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                        location
+                    ),
+                    (Some(body), _) => format!(
+                        "HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        body.len(),
+                        body
+                    ),
+                    (None, None) => "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string(),
+                };
+                let _ = stream.write_all(response.as_bytes()).await;
+            }
+        });
+        format!("http://{}/", address)
+    }
+
+    #[tokio::test]
+    async fn test_crawl_local_website() {
+        let root = serve_website().await;
+        let config = CrawlConfig::default()
+            .with_max_depth(1)
+            .with_delay(Duration::ZERO);
+        let mut crawler = Crawler::new(&[root.clone()], config).unwrap();
+
+        let mut pages = Vec::new();
+        while let Some(page) = crawler.next_page().await {
+            pages.push((page.url, page.depth));
+        }
+        assert_eq!(
+            pages,
+            vec![(root.clone(), 0), (format!("{}b", root), 1)]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_follow_redirects() {
+        let root = serve_website().await;
+        let config = CrawlConfig::default()
+            .with_max_depth(1)
+            .with_delay(Duration::ZERO);
+        let seeds = [
+            format!("{}docs/old", root),
+            format!("{}away", root),
+            format!("{}docs/secret", root),
+        ];
+        let mut crawler = Crawler::new(&seeds, config).unwrap();
+
+        let mut pages = Vec::new();
+        while let Some(page) = crawler.next_page().await {
+            pages.push((page.url, page.depth));
+        }
+        // Links are resolved against the URL the page was redirected to. The redirects to another
+        // host and to a page robots.txt disallows are not followed.
+        assert_eq!(
+            pages,
+            vec![(format!("{}guide/", root), 0), (format!("{}guide/intro", root), 1)]
+        );
+    }
+}
@@ -0,0 +1,253 @@
+//! Deduplication of identical chunks across the files of a batch or directory run, so that
+//! boilerplate such as license headers or legal footers is only embedded once.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use anyhow::Result;
+use sha2::{Digest, Sha256};
+
+use crate::embeddings::embed::{EmbedData, Embedder, EmbeddingResult};
+
+/// Which chunks count as duplicates of each other.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Deduplication {
+    /// Chunks with exactly the same text.
+    Exact,
+    /// Chunks whose text only differs in whitespace, e.g. line breaks or indentation.
+    Whitespace,
+}
+
+impl Deduplication {
+    /// The key chunks are compared by: the hex encoded SHA-256 of the, possibly normalized, text.
+    pub fn key(&self, chunk: &str) -> String {
+        let hash = match self {
+            Deduplication::Exact => Sha256::digest(chunk.as_bytes()),
+            Deduplication::Whitespace => {
+                let normalized = chunk.split_whitespace().collect::<Vec<_>>().join(" ");
+                Sha256::digest(normalized.as_bytes())
+            }
+        };
+        format!("{:x}", hash)
+    }
+}
+
+/// The metadata keys that locate a chunk, copied to its duplicates with a `duplicate_of_` prefix.
+const SOURCE_KEYS: [&str; 2] = ["file_name", "chunk_index"];
+
+/// Remembers the embeddings of the `capacity` most recently seen distinct chunks of a run. Every
+/// occurrence of a chunk still gets its own [EmbedData], with the metadata of the file it came
+/// from, but the model only sees the first one. A chunk that was forgotten is embedded again when
+/// it repeats.
+pub(crate) struct Deduplicator {
+    mode: Deduplication,
+    capacity: usize,
+    entries: HashMap<String, Entry>,
+    /// The keys of `entries` by when they were last seen, oldest first.
+    recency: BTreeMap<u64, String>,
+    clock: u64,
+}
+
+struct Entry {
+    embedding: EmbeddingResult,
+    /// The location of the first occurrence, as pairs of [SOURCE_KEYS] and their values.
+    source: Vec<(String, String)>,
+    last_seen: u64,
+}
+
+impl Deduplicator {
+    pub(crate) fn new(mode: Deduplication, capacity: usize) -> Self {
+        Self {
+            mode,
+            capacity,
+            entries: HashMap::new(),
+            recency: BTreeMap::new(),
+            clock: 0,
+        }
+    }
+
+    /// Embeds the chunks that have not been seen before, and returns the [EmbedData] of all of them.
+    pub(crate) async fn embed(
+        &mut self,
+        chunks: &[String],
+        metadata: &[Option<HashMap<String, String>>],
+        embedder: &Embedder,
+        batch_size: Option<usize>,
+    ) -> Result<Vec<EmbedData>> {
+        let (keys, to_embed) = self.plan(chunks);
+        let texts: Vec<&str> = to_embed.iter().map(|&i| chunks[i].as_str()).collect();
+        let embeddings = if texts.is_empty() {
+            Vec::new()
+        } else {
+            embedder.embed(&texts, batch_size, None).await?
+        };
+        Ok(self.resolve(chunks, metadata, keys, &to_embed, embeddings))
+    }
+
+    /// Returns the keys of `chunks`, and the indices of the chunks that have to be embedded: the
+    /// first occurrence of every chunk that has not been embedded before.
+    pub(crate) fn plan(&self, chunks: &[String]) -> (Vec<String>, Vec<usize>) {
+        let keys: Vec<String> = chunks.iter().map(|chunk| self.mode.key(chunk)).collect();
+        let mut planned = HashSet::new();
+        let to_embed = keys
+            .iter()
+            .enumerate()
+            .filter(|(_, key)| !self.entries.contains_key(*key) && planned.insert(*key))
+            .map(|(i, _)| i)
+            .collect();
+        (keys, to_embed)
+    }
+
+    /// Records the embeddings of the planned chunks, and builds the [EmbedData] of every chunk.
+    /// The key is added to the metadata as `chunk_hash`, so duplicates can be grouped downstream,
+    /// and duplicates point to the first occurrence with `duplicate_of_file_name` and
+    /// `duplicate_of_chunk_index`.
+    pub(crate) fn resolve(
+        &mut self,
+        chunks: &[String],
+        metadata: &[Option<HashMap<String, String>>],
+        keys: Vec<String>,
+        to_embed: &[usize],
+        embeddings: Vec<EmbeddingResult>,
+    ) -> Vec<EmbedData> {
+        for (&i, embedding) in to_embed.iter().zip(embeddings) {
+            let source: Vec<(String, String)> = metadata[i]
+                .as_ref()
+                .map(|metadata| {
+                    SOURCE_KEYS
+                        .iter()
+                        .filter_map(|&name| Some((name.to_string(), metadata.get(name)?.clone())))
+                        .collect()
+                })
+                .unwrap_or_default();
+            self.insert(keys[i].clone(), embedding, source);
+        }
+
+        let first: HashSet<usize> = to_embed.iter().copied().collect();
+        let data = keys
+            .into_iter()
+            .zip(chunks)
+            .zip(metadata)
+            .enumerate()
+            .filter_map(|(i, ((key, chunk), metadata))| {
+                let entry = self.touch(&key)?;
+                let mut metadata = metadata.clone().unwrap_or_default();
+                if !first.contains(&i) {
+                    for (name, value) in &entry.source {
+                        metadata.insert(format!("duplicate_of_{}", name), value.clone());
+                    }
+                }
+                let embedding = entry.embedding.clone();
+                metadata.insert("chunk_hash".to_string(), key);
+                Some(EmbedData::new(embedding, Some(chunk.clone()), Some(metadata)))
+            })
+            .collect();
+        // Only evicted once the batch is resolved, so every chunk of it finds its embedding.
+        self.evict();
+        data
+    }
+
+    fn insert(&mut self, key: String, embedding: EmbeddingResult, source: Vec<(String, String)>) {
+        self.clock += 1;
+        let entry = Entry {
+            embedding,
+            source,
+            last_seen: self.clock,
+        };
+        if let Some(previous) = self.entries.insert(key.clone(), entry) {
+            self.recency.remove(&previous.last_seen);
+        }
+        self.recency.insert(self.clock, key);
+    }
+
+    /// Marks the chunk with `key` as just seen.
+    fn touch(&mut self, key: &str) -> Option<&Entry> {
+        let entry = self.entries.get_mut(key)?;
+        self.clock += 1;
+        self.recency.remove(&entry.last_seen);
+        self.recency.insert(self.clock, key.to_string());
+        entry.last_seen = self.clock;
+        Some(entry)
+    }
+
+    /// Forgets the least recently seen chunks until at most `capacity` are left.
+    fn evict(&mut self) {
+        while self.entries.len() > self.capacity {
+            let Some((_, key)) = self.recency.pop_first() else {
+                break;
+            };
+            self.entries.remove(&key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file_metadata(file: &str) -> Option<HashMap<String, String>> {
+        Some(HashMap::from([("file_name".to_string(), file.to_string())]))
+    }
+
+    #[test]
+    fn test_whitespace_key() {
+        let mode = Deduplication::Whitespace;
+        assert_eq!(mode.key("All rights\n  reserved. "), mode.key("All rights reserved."));
+        assert_ne!(
+            Deduplication::Exact.key("All rights\n  reserved. "),
+            Deduplication::Exact.key("All rights reserved.")
+        );
+    }
+
+    #[test]
+    fn test_embeds_each_chunk_once() {
+        let mut deduplicator = Deduplicator::new(Deduplication::Whitespace, 100);
+
+        let chunks = vec![
+            "Copyright ACME".to_string(),
+            "intro".to_string(),
+            "Copyright  ACME\n".to_string(),
+        ];
+        let metadata = vec![file_metadata("a.md"), file_metadata("a.md"), file_metadata("b.md")];
+        let (keys, to_embed) = deduplicator.plan(&chunks);
+        assert_eq!(to_embed, vec![0, 1]);
+        let embeddings = vec![vec![1.0].into(), vec![2.0].into()];
+        let data = deduplicator.resolve(&chunks, &metadata, keys, &to_embed, embeddings);
+
+        assert_eq!(data.len(), 3);
+        assert_eq!(data[2].embedding.to_dense().unwrap(), vec![1.0]);
+        assert_eq!(data[2].metadata.as_ref().unwrap()["file_name"], "b.md");
+        assert_eq!(data[2].metadata.as_ref().unwrap()["duplicate_of_file_name"], "a.md");
+        assert!(!data[0].metadata.as_ref().unwrap().contains_key("duplicate_of_file_name"));
+        assert_eq!(
+            data[0].metadata.as_ref().unwrap()["chunk_hash"],
+            data[2].metadata.as_ref().unwrap()["chunk_hash"]
+        );
+
+        // A later batch reuses the embeddings of the earlier ones.
+        let chunks = vec!["intro".to_string()];
+        let (_, to_embed) = deduplicator.plan(&chunks);
+        assert!(to_embed.is_empty());
+    }
+
+    #[test]
+    fn test_forgets_least_recently_seen_chunks() {
+        let mut deduplicator = Deduplicator::new(Deduplication::Exact, 2);
+        let mut embed = |chunks: &[&str]| {
+            let chunks: Vec<String> = chunks.iter().map(|chunk| chunk.to_string()).collect();
+            let metadata = vec![file_metadata("a.md"); chunks.len()];
+            let (keys, to_embed) = deduplicator.plan(&chunks);
+            let embeddings = to_embed
+                .iter()
+                .map(|_| EmbeddingResult::DenseVector(vec![1.0]))
+                .collect();
+            deduplicator.resolve(&chunks, &metadata, keys, &to_embed, embeddings);
+            to_embed
+        };
+
+        // Every chunk of a batch is resolved, even if the batch has more than `capacity`.
+        assert_eq!(embed(&["a", "b", "c"]), vec![0, 1, 2]);
+        // "b" and "c" were seen last, and "c" is seen again, so "b" is forgotten next.
+        assert_eq!(embed(&["c", "d"]), vec![1]);
+        assert_eq!(embed(&["c", "d", "b"]), vec![2]);
+    }
+}
@@ -0,0 +1,399 @@
+//! An on-disk cache of text embeddings, so that chunks that were already embedded by the same
+//! model are not sent through the model, or to a paid API, again.
+//!
+//! Every embedding is stored as its own file under a directory per model, named after the SHA-256
+//! of the chunk text. When the cache grows past its size limit, the least recently used entries
+//! are evicted.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+use anyhow::Result;
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use walkdir::WalkDir;
+
+use super::embed::{EmbeddingResult, TextEmbedder};
+
+/// Identifies the model an embedding was made with. Embeddings are only reused for the exact same
+/// key, so a new revision, pooling method or dtype starts from an empty cache.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct ModelKey {
+    pub architecture: String,
+    pub model_id: String,
+    pub revision: Option<String>,
+    pub pooling: Option<String>,
+    /// Left out of the key when unset, so the caches of models loaded without a dtype are kept.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dtype: Option<String>,
+}
+
+impl ModelKey {
+    pub fn new(architecture: &str, model_id: &str) -> Self {
+        Self {
+            architecture: architecture.to_string(),
+            model_id: model_id.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_revision(mut self, revision: Option<&str>) -> Self {
+        self.revision = revision.map(|s| s.to_string());
+        self
+    }
+
+    pub fn with_pooling(mut self, pooling: Option<&str>) -> Self {
+        self.pooling = pooling.map(|s| s.to_string());
+        self
+    }
+
+    pub fn with_dtype(mut self, dtype: Option<&str>) -> Self {
+        self.dtype = dtype.map(|s| s.to_string());
+        self
+    }
+
+    /// The name of the directory the embeddings of this model are stored in.
+    fn namespace(&self) -> String {
+        let key = serde_json::to_vec(self).unwrap_or_default();
+        hex_digest(&key)[..16].to_string()
+    }
+}
+
+/// A directory of cached embeddings, shared by any number of models.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use std::sync::Arc;
+/// use embed_anything::embeddings::cache::EmbeddingCache;
+/// use embed_anything::embeddings::embed::EmbedderBuilder;
+///
+/// let cache = Arc::new(EmbeddingCache::open(".embedding_cache").unwrap().with_max_bytes(1 << 30));
+/// let embedder = EmbedderBuilder::new()
+///     .model_architecture("openai")
+///     .model_id(Some("text-embedding-3-small"))
+///     .cache(Some(cache))
+///     .from_pretrained_cloud()
+///     .unwrap();
+/// ```
+pub struct EmbeddingCache {
+    root: PathBuf,
+    max_bytes: Option<u64>,
+    /// The total size of the entries on disk.
+    size: Mutex<u64>,
+}
+
+impl EmbeddingCache {
+    /// Opens the cache at `root`, creating the directory if it does not exist yet.
+    pub fn open(root: impl AsRef<Path>) -> Result<Self> {
+        let root = root.as_ref().to_path_buf();
+        fs::create_dir_all(&root)?;
+        let size = entries(&root).iter().map(|entry| entry.size).sum();
+        Ok(Self {
+            root,
+            max_bytes: None,
+            size: Mutex::new(size),
+        })
+    }
+
+    /// Limits the size of the cache on disk. Without a limit, the cache grows forever.
+    pub fn with_max_bytes(mut self, max_bytes: u64) -> Self {
+        self.max_bytes = Some(max_bytes);
+        self
+    }
+
+    /// The total size of the cached embeddings, in bytes.
+    pub fn size_bytes(&self) -> u64 {
+        *self.size.lock().unwrap()
+    }
+
+    /// Returns the embedding of `text` made by `model`, if it is cached.
+    pub fn get(&self, model: &ModelKey, text: &str) -> Option<EmbeddingResult> {
+        let path = self.entry_path(model, text);
+        let embedding = serde_json::from_slice(&fs::read(&path).ok()?).ok()?;
+        // The modification time is what eviction goes by, so a hit marks the entry as recently used.
+        if let Ok(file) = fs::File::options().write(true).open(&path) {
+            let _ = file.set_modified(SystemTime::now());
+        }
+        Some(embedding)
+    }
+
+    /// Stores the embedding of `text` made by `model`, evicting old entries if the cache is full.
+    pub fn insert(&self, model: &ModelKey, text: &str, embedding: &EmbeddingResult) -> Result<()> {
+        let path = self.entry_path(model, text);
+        let dir = path.parent().unwrap();
+        if !dir.exists() {
+            fs::create_dir_all(dir)?;
+            fs::write(dir.join(MODEL_FILE), serde_json::to_vec_pretty(model)?)?;
+        }
+
+        let data = serde_json::to_vec(embedding)?;
+        let replaced = file_size(&path);
+        let tmp_path = path.with_extension("tmp");
+        fs::write(&tmp_path, &data)?;
+        fs::rename(tmp_path, &path)?;
+
+        let size = {
+            let mut size = self.size.lock().unwrap();
+            *size = (*size + data.len() as u64).saturating_sub(replaced);
+            *size
+        };
+        if self.max_bytes.is_some_and(|max_bytes| size > max_bytes) {
+            self.evict()?;
+        }
+        Ok(())
+    }
+
+    /// Drops the embedding of `text` made by `model`. Returns whether it was cached.
+    pub fn remove(&self, model: &ModelKey, text: &str) -> Result<bool> {
+        let path = self.entry_path(model, text);
+        let size = file_size(&path);
+        match fs::remove_file(&path) {
+            Ok(()) => {
+                let mut total = self.size.lock().unwrap();
+                *total = total.saturating_sub(size);
+                Ok(true)
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Drops every embedding made by `model`, e.g. after it was fine-tuned without a new revision.
+    pub fn invalidate_model(&self, model: &ModelKey) -> Result<()> {
+        let dir = self.root.join(model.namespace());
+        if dir.exists() {
+            let removed: u64 = entries(&dir).iter().map(|entry| entry.size).sum();
+            fs::remove_dir_all(dir)?;
+            let mut size = self.size.lock().unwrap();
+            *size = size.saturating_sub(removed);
+        }
+        Ok(())
+    }
+
+    /// Drops every cached embedding.
+    pub fn clear(&self) -> Result<()> {
+        fs::remove_dir_all(&self.root)?;
+        fs::create_dir_all(&self.root)?;
+        *self.size.lock().unwrap() = 0;
+        Ok(())
+    }
+
+    /// Deletes the least recently used entries until the cache is back to 90% of its limit, so
+    /// that the next inserts do not evict again straight away.
+    fn evict(&self) -> Result<()> {
+        let Some(max_bytes) = self.max_bytes else {
+            return Ok(());
+        };
+        let target = max_bytes / 10 * 9;
+
+        let mut size = self.size.lock().unwrap();
+        let mut entries = entries(&self.root);
+        entries.sort_by_key(|entry| entry.modified);
+        for entry in entries {
+            if *size <= target {
+                break;
+            }
+            if fs::remove_file(&entry.path).is_ok() {
+                *size = size.saturating_sub(entry.size);
+            }
+        }
+        Ok(())
+    }
+
+    fn entry_path(&self, model: &ModelKey, text: &str) -> PathBuf {
+        let hash = hex_digest(text.as_bytes());
+        self.root
+            .join(model.namespace())
+            .join(&hash[..2])
+            .join(format!("{}.json", hash))
+    }
+}
+
+/// The file every model directory describes its [ModelKey] in. It is not an entry.
+const MODEL_FILE: &str = "model.json";
+
+struct Entry {
+    path: PathBuf,
+    size: u64,
+    modified: SystemTime,
+}
+
+fn entries(dir: &Path) -> Vec<Entry> {
+    WalkDir::new(dir)
+        .min_depth(2)
+        .into_iter()
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().is_file())
+        .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "json"))
+        .filter(|entry| entry.file_name() != MODEL_FILE)
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok()?;
+            Some(Entry {
+                path: entry.into_path(),
+                size: metadata.len(),
+                modified: metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+            })
+        })
+        .collect()
+}
+
+fn file_size(path: &Path) -> u64 {
+    fs::metadata(path).map(|metadata| metadata.len()).unwrap_or(0)
+}
+
+fn hex_digest(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+/// A [TextEmbedder] that looks up every chunk in an [EmbeddingCache] first, and only embeds the
+/// chunks that are not cached. Use it through [TextEmbedder::Cached].
+pub struct CachedEmbedder {
+    pub embedder: TextEmbedder,
+    pub cache: Arc<EmbeddingCache>,
+    pub model: ModelKey,
+}
+
+impl CachedEmbedder {
+    pub fn new(embedder: TextEmbedder, cache: Arc<EmbeddingCache>, model: ModelKey) -> Self {
+        Self {
+            embedder,
+            cache,
+            model,
+        }
+    }
+
+    pub async fn embed(
+        &self,
+        text_batch: &[&str],
+        batch_size: Option<usize>,
+        late_chunking: Option<bool>,
+    ) -> Result<Vec<EmbeddingResult>> {
+        // With late chunking, the embedding of a chunk depends on the chunks around it.
+        if late_chunking.unwrap_or(false) {
+            return self.embed_uncached(text_batch, batch_size, late_chunking).await;
+        }
+
+        let mut embeddings: Vec<Option<EmbeddingResult>> = text_batch
+            .iter()
+            .map(|text| self.cache.get(&self.model, text))
+            .collect();
+        let (miss_indices, misses): (Vec<usize>, Vec<&str>) = text_batch
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| embeddings[*i].is_none())
+            .map(|(i, text)| (i, *text))
+            .unzip();
+
+        if !misses.is_empty() {
+            let computed = self
+                .embed_uncached(&misses, batch_size, late_chunking)
+                .await?;
+            for ((i, text), embedding) in miss_indices.into_iter().zip(misses).zip(computed) {
+                if let Err(e) = self.cache.insert(&self.model, text, &embedding) {
+                    eprintln!("Error caching embedding: {:?}", e);
+                }
+                embeddings[i] = Some(embedding);
+            }
+        }
+
+        embeddings
+            .into_iter()
+            .map(|embedding| {
+                embedding.ok_or_else(|| anyhow::anyhow!("The embedder returned too few embeddings"))
+            })
+            .collect()
+    }
+
+    fn embed_uncached<'a>(
+        &'a self,
+        text_batch: &'a [&'a str],
+        batch_size: Option<usize>,
+        late_chunking: Option<bool>,
+    ) -> BoxFuture<'a, Result<Vec<EmbeddingResult>>> {
+        // Boxed because TextEmbedder::embed calls back into this embedder.
+        Box::pin(self.embedder.embed(text_batch, batch_size, late_chunking))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::embeddings::cloud::openai::OpenAIEmbedder;
+    use tempdir::TempDir;
+
+    fn embedding(value: f32) -> EmbeddingResult {
+        EmbeddingResult::DenseVector(vec![value; 8])
+    }
+
+    #[test]
+    fn test_insert_get_and_invalidate() {
+        let temp_dir = TempDir::new("cache").unwrap();
+        let cache = EmbeddingCache::open(temp_dir.path()).unwrap();
+        let model = ModelKey::new("bert", "model").with_revision(Some("main"));
+        let other_revision = ModelKey::new("bert", "model").with_revision(Some("v2"));
+        let other_dtype = model.clone().with_dtype(Some("F16"));
+
+        cache.insert(&model, "hello", &embedding(1.0)).unwrap();
+        assert_eq!(
+            cache.get(&model, "hello").unwrap().to_dense().unwrap(),
+            vec![1.0; 8]
+        );
+        assert!(cache.get(&model, "hello world").is_none());
+        assert!(cache.get(&other_revision, "hello").is_none());
+        assert!(cache.get(&other_dtype, "hello").is_none());
+        assert!(cache.size_bytes() > 0);
+
+        cache.invalidate_model(&model).unwrap();
+        assert!(cache.get(&model, "hello").is_none());
+        assert_eq!(cache.size_bytes(), 0);
+    }
+
+    #[test]
+    fn test_evicts_least_recently_used() {
+        let temp_dir = TempDir::new("cache").unwrap();
+        let model = ModelKey::new("bert", "model");
+        let entry_size = serde_json::to_vec(&embedding(0.0)).unwrap().len() as u64;
+        let cache = EmbeddingCache::open(temp_dir.path())
+            .unwrap()
+            .with_max_bytes(entry_size * 3);
+
+        for (i, text) in ["a", "b", "c"].iter().enumerate() {
+            cache.insert(&model, text, &embedding(i as f32)).unwrap();
+            std::thread::sleep(std::time::Duration::from_millis(20));
+        }
+        // Using "a" makes "b" the least recently used entry.
+        cache.get(&model, "a").unwrap();
+        cache.insert(&model, "d", &embedding(3.0)).unwrap();
+
+        assert!(cache.size_bytes() <= entry_size * 3);
+        assert!(cache.get(&model, "b").is_none());
+        assert!(cache.get(&model, "a").is_some());
+        assert!(cache.get(&model, "d").is_some());
+    }
+
+    #[tokio::test]
+    async fn test_cached_embedder_skips_model() {
+        let temp_dir = TempDir::new("cache").unwrap();
+        let cache = Arc::new(EmbeddingCache::open(temp_dir.path()).unwrap());
+        let model = ModelKey::new("openai", "text-embedding-3-small");
+        cache.insert(&model, "first", &embedding(1.0)).unwrap();
+        cache.insert(&model, "second", &embedding(2.0)).unwrap();
+
+        // The API key is invalid, so this only succeeds if no request is sent.
+        let embedder = TextEmbedder::OpenAI(OpenAIEmbedder::new(
+            "text-embedding-3-small".to_string(),
+            Some("invalid".to_string()),
+        ))
+        .cached(cache, model);
+        let embeddings = embedder.embed(&["second", "first"], None, None).await.unwrap();
+
+        assert_eq!(embeddings[0].to_dense().unwrap(), vec![2.0; 8]);
+        assert_eq!(embeddings[1].to_dense().unwrap(), vec![1.0; 8]);
+    }
+}
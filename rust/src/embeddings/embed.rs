@@ -1,7 +1,9 @@
+use crate::adapter::Adapter;
 use crate::config::{ImageEmbedConfig, TextEmbedConfig};
 use crate::file_processor::audio::audio_processor::Segment;
 use crate::Dtype;
 
+use super::cache::{CachedEmbedder, EmbeddingCache, ModelKey};
 use super::cloud::cohere::CohereEmbedder;
 use super::cloud::openai::OpenAIEmbedder;
 use super::local::bert::{BertEmbed, BertEmbedder, SparseBertEmbedder};
@@ -13,10 +15,11 @@ use super::local::modernbert::ModernBertEmbedder;
 use super::local::text_embedding::ONNXModel;
 use anyhow::anyhow;
 use anyhow::Result;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::Arc;
+use tokenizers::{PostProcessor, Tokenizer};
 
 #[cfg(feature = "ort")]
 use {
@@ -25,7 +28,7 @@ use {
     super::local::ort_jina::OrtJinaEmbedder,
 };
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub enum EmbeddingResult {
     DenseVector(Vec<f32>),
     MultiVector(Vec<Vec<f32>>),
@@ -105,6 +108,8 @@ pub enum TextEmbedder {
     Bert(Box<dyn BertEmbed + Send + Sync>),
     ColBert(Box<dyn BertEmbed + Send + Sync>),
     ModernBert(Box<dyn BertEmbed + Send + Sync>),
+    /// Another embedder whose embeddings are looked up in, and stored to, an [EmbeddingCache].
+    Cached(Box<CachedEmbedder>),
 }
 
 impl TextEmbedder {
@@ -125,9 +130,54 @@ impl TextEmbedder {
             TextEmbedder::ModernBert(embedder) => {
                 embedder.embed(text_batch, batch_size, late_chunking)
             }
+            TextEmbedder::Cached(embedder) => {
+                embedder.embed(text_batch, batch_size, late_chunking).await
+            }
+        }
+    }
+
+    /// The tokenizer of the model, or `None` for cloud models.
+    pub fn tokenizer(&self) -> Option<&Tokenizer> {
+        match self {
+            TextEmbedder::OpenAI(_) | TextEmbedder::Cohere(_) => None,
+            TextEmbedder::Jina(embedder) => embedder.tokenizer(),
+            TextEmbedder::Bert(embedder) => embedder.tokenizer(),
+            TextEmbedder::ColBert(embedder) => embedder.tokenizer(),
+            TextEmbedder::ModernBert(embedder) => embedder.tokenizer(),
+            TextEmbedder::Cached(embedder) => embedder.embedder.tokenizer(),
+        }
+    }
+
+    /// Whether the model returns one dense vector per text, rather than a sparse vector or one
+    /// vector per token.
+    pub fn is_dense(&self) -> bool {
+        match self {
+            TextEmbedder::OpenAI(_) | TextEmbedder::Cohere(_) | TextEmbedder::Jina(_) => true,
+            TextEmbedder::Bert(embedder) | TextEmbedder::ModernBert(embedder) => {
+                !embedder.is_sparse()
+            }
+            TextEmbedder::ColBert(_) => false,
+            TextEmbedder::Cached(embedder) => embedder.embedder.is_dense(),
         }
     }
 
+    /// The number of tokens of a text the model can take, excluding the special tokens the
+    /// tokenizer adds. Longer texts are truncated.
+    pub fn max_sequence_length(&self) -> Option<usize> {
+        let tokenizer = self.tokenizer()?;
+        let max_length = tokenizer.get_truncation()?.max_length;
+        let special_tokens = tokenizer
+            .get_post_processor()
+            .map_or(0, |processor| processor.added_tokens(false));
+        Some(max_length.saturating_sub(special_tokens))
+    }
+
+    /// Wraps the embedder so that it only embeds the chunks that `cache` does not know yet for
+    /// `model`.
+    pub fn cached(self, cache: Arc<EmbeddingCache>, model: ModelKey) -> Self {
+        Self::Cached(Box::new(CachedEmbedder::new(self, cache, model)))
+    }
+
     pub fn from_pretrained_hf(
         model: &str,
         model_id: &str,
@@ -373,6 +423,8 @@ pub struct EmbedderBuilder {
     // The ONNX Model ID that you want to use
     onnx_model_id: Option<ONNXModel>,
     dtype: Option<Dtype>,
+    // The cache that text embeddings are looked up in before running the model
+    cache: Option<Arc<EmbeddingCache>>,
 }
 
 impl EmbedderBuilder {
@@ -386,6 +438,7 @@ impl EmbedderBuilder {
             path_in_repo: None,
             onnx_model_id: None,
             dtype: None,
+            cache: None,
         }
     }
 
@@ -432,21 +485,34 @@ impl EmbedderBuilder {
         self
     }
 
-    pub fn from_pretrained_hf(self) -> Result<Embedder, anyhow::Error> {
-        match self.model_id {
+    /// Look up text embeddings in `cache` before running the model, and store the new ones in it.
+    /// Entries are keyed by the architecture, model ID, revision and dtype given to this builder.
+    pub fn cache(mut self, cache: Option<Arc<EmbeddingCache>>) -> Self {
+        self.cache = cache;
+        self
+    }
+
+    pub fn from_pretrained_hf(mut self) -> Result<Embedder, anyhow::Error> {
+        // Dtype is not Copy, and `self` is still needed to wrap the embedder in the cache.
+        let dtype = self.dtype.take();
+        let dtype_key = dtype.as_ref().map(|dtype| format!("{:?}", dtype));
+        let embedder = match &self.model_id {
             Some(model_id) => Embedder::from_pretrained_hf(
                 &self.model_architecture,
-                &model_id,
+                model_id,
                 self.revision.as_deref(),
                 self.token.as_deref(),
-                self.dtype,
+                dtype,
             ),
             None => Err(anyhow::anyhow!("Model ID is required")),
-        }
+        }?;
+        Ok(self.with_cache(embedder, dtype_key.as_deref()))
     }
 
-    pub fn from_pretrained_onnx(self) -> Result<Embedder, anyhow::Error> {
-        match (self.onnx_model_id, self.model_id) {
+    pub fn from_pretrained_onnx(mut self) -> Result<Embedder, anyhow::Error> {
+        let dtype = self.dtype.take();
+        let dtype_key = dtype.as_ref().map(|dtype| format!("{:?}", dtype));
+        let embedder = match (self.onnx_model_id, &self.model_id) {
             (None, None) => Err(anyhow::anyhow!(
                 "Either model_id or onnx_model_id is required"
             )),
@@ -458,7 +524,7 @@ impl EmbedderBuilder {
                 Some(onnx_model_id),
                 self.revision.as_deref(),
                 None,
-                self.dtype,
+                dtype,
                 self.path_in_repo.as_deref(),
             ),
             (None, Some(model_id)) => Embedder::from_pretrained_onnx(
@@ -466,18 +532,44 @@ impl EmbedderBuilder {
                 None,
                 self.revision.as_deref(),
                 Some(model_id.as_str()),
-                self.dtype,
+                dtype,
                 self.path_in_repo.as_deref(),
             ),
-        }
+        }?;
+        Ok(self.with_cache(embedder, dtype_key.as_deref()))
     }
 
     pub fn from_pretrained_cloud(self) -> Result<Embedder, anyhow::Error> {
-        Embedder::from_pretrained_cloud(
+        let embedder = Embedder::from_pretrained_cloud(
             &self.model_architecture,
-            &self.model_id.unwrap(),
-            self.api_key,
-        )
+            self.model_id.as_deref().unwrap(),
+            self.api_key.clone(),
+        )?;
+        Ok(self.with_cache(embedder, None))
+    }
+
+    /// Wraps text embedders in [TextEmbedder::Cached] if a cache was given. `dtype` is the dtype
+    /// the model was loaded with, which build methods take out of the builder.
+    fn with_cache(self, embedder: Embedder, dtype: Option<&str>) -> Embedder {
+        match (self.cache, embedder) {
+            (Some(cache), Embedder::Text(embedder)) => {
+                let model_id = match (&self.model_id, self.onnx_model_id) {
+                    (Some(model_id), _) => model_id.clone(),
+                    (None, Some(onnx_model_id)) => onnx_model_id.to_string(),
+                    (None, None) => String::new(),
+                };
+                let pooling = self
+                    .onnx_model_id
+                    .and_then(|onnx_model_id| onnx_model_id.get_default_pooling_method())
+                    .map(|pooling| format!("{:?}", pooling));
+                let model = ModelKey::new(&self.model_architecture.to_lowercase(), &model_id)
+                    .with_revision(self.revision.as_deref())
+                    .with_pooling(pooling.as_deref())
+                    .with_dtype(dtype);
+                Embedder::Text(embedder.cached(cache, model))
+            }
+            (_, embedder) => embedder,
+        }
     }
 }
 
@@ -606,7 +698,7 @@ impl Embedder {
         directory: PathBuf,
         extensions: Option<Vec<String>>,
         config: Option<&TextEmbedConfig>,
-        adapter: Option<Box<dyn FnMut(Vec<EmbedData>) + Send + Sync>>,
+        adapter: Option<Arc<dyn Adapter>>,
     ) -> Result<Option<Vec<EmbedData>>> {
         crate::embed_directory_stream(directory, self, extensions, config, adapter).await
     }
@@ -615,7 +707,7 @@ impl Embedder {
         self: &Arc<Self>,
         directory: PathBuf,
         config: Option<&ImageEmbedConfig>,
-        adapter: Option<Box<dyn FnMut(Vec<EmbedData>) + Send + Sync>>,
+        adapter: Option<Arc<dyn Adapter>>,
     ) -> Result<Option<Vec<EmbedData>>> {
         crate::embed_image_directory(directory, self, config, adapter).await
     }
@@ -624,7 +716,7 @@ impl Embedder {
         &self,
         file_path: T,
         config: Option<&TextEmbedConfig>,
-        adapter: Option<Box<dyn FnOnce(Vec<EmbedData>) + Send + Sync>>,
+        adapter: Option<Arc<dyn Adapter>>,
     ) -> Result<Option<Vec<EmbedData>>> {
         crate::embed_file(file_path, self, config, adapter).await
     }
@@ -633,7 +725,7 @@ impl Embedder {
         &self,
         url: String,
         config: Option<&TextEmbedConfig>,
-        adapter: Option<Box<dyn FnOnce(Vec<EmbedData>) + Send + Sync>>,
+        adapter: Option<Arc<dyn Adapter>>,
     ) -> Result<Option<Vec<EmbedData>>> {
         crate::embed_webpage(url, self, config, adapter).await
     }
@@ -645,7 +737,7 @@ impl Embedder {
     /// * `files` - A vector of `PathBuf` objects representing the files to embed.
     /// * `embedder` - A reference to the embedding model to use.
     /// * `config` - An optional `TextEmbedConfig` object specifying the configuration for the embedding model.
-    /// * `adapter` - An optional callback function to handle the embeddings.
+    /// * `adapter` - An optional [Adapter] that the embeddings are upserted into.
     ///
     /// # Returns
     /// An `Option` containing a vector of `EmbedData` objects representing the embeddings of the files, or `None` if an adapter is used.
@@ -679,7 +771,7 @@ impl Embedder {
         self: &Arc<Self>,
         file_paths: impl IntoIterator<Item = impl AsRef<std::path::Path>>,
         config: Option<&TextEmbedConfig>,
-        adapter: Option<Box<dyn FnMut(Vec<EmbedData>) + Send + Sync>>,
+        adapter: Option<Arc<dyn Adapter>>,
     ) -> Result<Option<Vec<EmbedData>>> {
         crate::embed_files_batch(file_paths, self, config, adapter).await
     }
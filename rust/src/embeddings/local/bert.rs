@@ -29,6 +29,16 @@ pub trait BertEmbed {
         batch_size: Option<usize>,
         late_chunking: Option<bool>,
     ) -> Result<Vec<EmbeddingResult>, anyhow::Error>;
+
+    /// The tokenizer of the model, used to measure chunks in tokens.
+    fn tokenizer(&self) -> Option<&Tokenizer> {
+        None
+    }
+
+    /// Whether the embeddings are sparse, with one dimension per token of the vocabulary.
+    fn is_sparse(&self) -> bool {
+        false
+    }
 }
 #[derive(Debug, Deserialize, Clone)]
 pub struct TokenizerConfig {
@@ -274,6 +284,10 @@ impl BertEmbedder {
 }
 
 impl BertEmbed for BertEmbedder {
+    fn tokenizer(&self) -> Option<&Tokenizer> {
+        Some(&self.tokenizer)
+    }
+
     fn embed(
         &self,
         text_batch: &[&str],
@@ -364,6 +378,14 @@ impl SparseBertEmbedder {
 }
 
 impl BertEmbed for SparseBertEmbedder {
+    fn tokenizer(&self) -> Option<&Tokenizer> {
+        Some(&self.tokenizer)
+    }
+
+    fn is_sparse(&self) -> bool {
+        true
+    }
+
     fn embed(
         &self,
         text_batch: &[&str],
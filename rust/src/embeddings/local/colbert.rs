@@ -253,6 +253,10 @@ impl ColbertEmbed for OrtColbertEmbedder {
 }
 
 impl BertEmbed for OrtColbertEmbedder {
+    fn tokenizer(&self) -> Option<&Tokenizer> {
+        Some(&self.tokenizer)
+    }
+
     fn embed(
         &self,
         text_batch: &[&str],
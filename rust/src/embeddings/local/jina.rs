@@ -24,6 +24,11 @@ pub trait JinaEmbed {
         batch_size: Option<usize>,
         late_chunking: Option<bool>,
     ) -> Result<Vec<EmbeddingResult>, anyhow::Error>;
+
+    /// The tokenizer of the model, used to measure chunks in tokens.
+    fn tokenizer(&self) -> Option<&Tokenizer> {
+        None
+    }
 }
 
 ///jina-embeddings-v2-base-en is an English, monolingual embedding model supporting 8192 sequence length. It is based on a BERT architecture (JinaBERT) that supports the symmetric bidirectional variant of ALiBi to allow longer sequence length. The backbone jina-bert-v2-base-en is pretrained on the C4 dataset. The model is further trained on Jina AI's collection of more than 400 millions of sentence pairs and hard negatives. These pairs were obtained from various domains and were carefully selected through a thorough cleaning process.
@@ -238,6 +243,10 @@ impl JinaEmbedder {
 }
 
 impl JinaEmbed for JinaEmbedder {
+    fn tokenizer(&self) -> Option<&Tokenizer> {
+        Some(&self.tokenizer)
+    }
+
     fn embed(
         &self,
         text_batch: &[&str],
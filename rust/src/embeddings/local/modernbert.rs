@@ -240,6 +240,10 @@ impl ModernBertEmbedder {
 }
 
 impl BertEmbed for ModernBertEmbedder {
+    fn tokenizer(&self) -> Option<&Tokenizer> {
+        Some(&self.tokenizer)
+    }
+
     fn embed(
         &self,
         text_batch: &[&str],
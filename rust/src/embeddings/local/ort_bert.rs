@@ -359,6 +359,10 @@ impl OrtBertEmbedder {
 }
 
 impl BertEmbed for OrtBertEmbedder {
+    fn tokenizer(&self) -> Option<&Tokenizer> {
+        Some(&self.tokenizer)
+    }
+
     fn embed(
         &self,
         text_batch: &[&str],
@@ -481,6 +485,14 @@ impl OrtSparseBertEmbedder {
 }
 
 impl BertEmbed for OrtSparseBertEmbedder {
+    fn tokenizer(&self) -> Option<&Tokenizer> {
+        Some(&self.tokenizer)
+    }
+
+    fn is_sparse(&self) -> bool {
+        true
+    }
+
     fn embed(
         &self,
         text_batch: &[&str],
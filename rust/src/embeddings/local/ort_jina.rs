@@ -291,6 +291,10 @@ impl OrtJinaEmbedder {
 }
 
 impl JinaEmbed for OrtJinaEmbedder {
+    fn tokenizer(&self) -> Option<&Tokenizer> {
+        Some(&self.tokenizer)
+    }
+
     fn embed(
         &self,
         text_batch: &[&str],
@@ -6,7 +6,9 @@ use candle_core::{Device, Tensor};
 use embed::{EmbedData, Embedder, EmbeddingResult};
 
 use crate::file_processor::audio::audio_processor::Segment;
+use crate::file_processor::processor::Document;
 
+pub mod cache;
 pub mod cloud;
 pub mod embed;
 pub mod local;
@@ -26,6 +28,27 @@ pub fn get_text_metadata(
     Ok(final_embeddings)
 }
 
+/// Like [get_text_metadata] for the chunks of `document`, with the metadata of the document and the
+/// provenance of every chunk added to the metadata.
+pub fn get_document_metadata(
+    encodings: &Rc<Vec<EmbeddingResult>>,
+    document: &Document,
+    metadata: &Option<HashMap<String, String>>,
+) -> anyhow::Result<Vec<EmbedData>> {
+    let final_embeddings = encodings
+        .par_iter()
+        .zip(&document.chunks)
+        .zip(&document.provenance)
+        .map(|((data, text), provenance)| {
+            let mut chunk_metadata = document.metadata.clone();
+            chunk_metadata.extend(metadata.clone().unwrap_or_default());
+            chunk_metadata.extend(provenance.metadata());
+            EmbedData::new(data.clone(), Some(text.to_string()), Some(chunk_metadata))
+        })
+        .collect::<Vec<_>>();
+    Ok(final_embeddings)
+}
+
 pub fn get_audio_metadata<T: AsRef<std::path::Path>>(
     encodings: Vec<EmbeddingResult>,
     segments: Vec<Segment>,
@@ -0,0 +1,488 @@
+//! Reading the pages listed in a `sitemap.xml`, a sitemap index, or an RSS or Atom feed, so a
+//! site can be embedded from the list of pages it publishes.
+
+use std::{
+    collections::{HashSet, VecDeque},
+    io::Read,
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Result};
+use chrono::{DateTime, FixedOffset, NaiveDate, Utc};
+use flate2::read::GzDecoder;
+use roxmltree::{Document, Node, ParsingOptions};
+
+/// Which pages of a feed a [FeedSource] fetches, and how fast.
+///
+/// # Example
+///
+/// ```rust
+/// use chrono::{Duration, Utc};
+/// use embed_anything::feeds::FeedConfig;
+/// // Only the pages that changed in the last week.
+/// let config = FeedConfig::default().with_modified_since(Utc::now() - Duration::days(7));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeedConfig {
+    /// Skips the pages, and the sitemaps of a sitemap index, last modified or published before
+    /// this date. Entries without a date are always fetched. Defaults to None.
+    pub modified_since: Option<DateTime<Utc>>,
+    /// Stops after this many pages. Defaults to 1000.
+    pub max_pages: Option<usize>,
+    /// The maximum number of sitemaps read when following sitemap indexes. Defaults to 100.
+    pub max_sitemaps: usize,
+    /// The minimum time between two requests. Defaults to 500ms.
+    pub delay: Duration,
+    pub user_agent: String,
+    /// The timeout of every request. Defaults to 30s.
+    pub timeout: Duration,
+}
+
+impl Default for FeedConfig {
+    fn default() -> Self {
+        Self {
+            modified_since: None,
+            max_pages: Some(1000),
+            max_sitemaps: 100,
+            delay: Duration::from_millis(500),
+            user_agent: "EmbedAnything".to_string(),
+            timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+impl FeedConfig {
+    pub fn with_modified_since(mut self, since: DateTime<Utc>) -> Self {
+        self.modified_since = Some(since);
+        self
+    }
+
+    pub fn with_max_pages(mut self, pages: usize) -> Self {
+        self.max_pages = Some(pages);
+        self
+    }
+
+    pub fn with_max_sitemaps(mut self, sitemaps: usize) -> Self {
+        self.max_sitemaps = sitemaps;
+        self
+    }
+
+    pub fn with_delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    pub fn with_user_agent(mut self, user_agent: &str) -> Self {
+        self.user_agent = user_agent.to_string();
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Returns true if an entry with these dates has to be fetched.
+    fn is_recent(&self, entry: &FeedEntry) -> bool {
+        match (self.modified_since, entry.lastmod.or(entry.published)) {
+            (Some(since), Some(date)) => date >= since,
+            _ => true,
+        }
+    }
+}
+
+/// A page listed in a sitemap or a feed, or a sitemap listed in a sitemap index.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeedEntry {
+    pub url: String,
+    /// The `lastmod` of a sitemap, or the `updated` date of an Atom entry.
+    pub lastmod: Option<DateTime<FixedOffset>>,
+    /// The `pubDate` of an RSS item, or the `published` date of an Atom entry.
+    pub published: Option<DateTime<FixedOffset>>,
+    pub title: Option<String>,
+}
+
+impl FeedEntry {
+    fn new(url: &str) -> Self {
+        Self {
+            url: url.trim().to_string(),
+            lastmod: None,
+            published: None,
+            title: None,
+        }
+    }
+
+    /// The dates and title of the entry, as stored with the embeddings of its page.
+    pub fn metadata(&self) -> Vec<(String, String)> {
+        let mut metadata = vec![("url".to_string(), self.url.clone())];
+        if let Some(lastmod) = self.lastmod {
+            metadata.push(("lastmod".to_string(), lastmod.to_rfc3339()));
+        }
+        if let Some(published) = self.published {
+            metadata.push(("published".to_string(), published.to_rfc3339()));
+        }
+        if let Some(title) = &self.title {
+            metadata.push(("title".to_string(), title.clone()));
+        }
+        metadata
+    }
+}
+
+/// What a sitemap or feed lists.
+#[derive(Debug, PartialEq)]
+pub enum Feed {
+    /// The pages of a sitemap, or the entries of an RSS or Atom feed.
+    Pages(Vec<FeedEntry>),
+    /// The sitemaps of a sitemap index.
+    Sitemaps(Vec<FeedEntry>),
+}
+
+/// Parses a sitemap, a sitemap index, an RSS 1.0 or 2.0 feed, or an Atom feed.
+pub fn parse_feed(xml: &str) -> Result<Feed> {
+    let options = ParsingOptions {
+        allow_dtd: true,
+        ..Default::default()
+    };
+    let document = Document::parse_with_options(xml, options)?;
+    let root = document.root_element();
+    let feed = match root.tag_name().name() {
+        "urlset" => Feed::Pages(elements(root, "url").filter_map(sitemap_entry).collect()),
+        "sitemapindex" => {
+            Feed::Sitemaps(elements(root, "sitemap").filter_map(sitemap_entry).collect())
+        }
+        // RSS 2.0 nests the items in the channel, RSS 1.0 (RDF) puts them next to it.
+        "rss" | "RDF" => Feed::Pages(
+            root.descendants()
+                .filter(|node| node.is_element() && node.tag_name().name() == "item")
+                .filter_map(rss_entry)
+                .collect(),
+        ),
+        "feed" => Feed::Pages(elements(root, "entry").filter_map(atom_entry).collect()),
+        other => bail!("Not a sitemap or feed: <{}>", other),
+    };
+    Ok(feed)
+}
+
+fn elements<'a, 'input: 'a>(
+    node: Node<'a, 'input>,
+    name: &'a str,
+) -> impl Iterator<Item = Node<'a, 'input>> + 'a {
+    node.children()
+        .filter(move |child| child.is_element() && child.tag_name().name() == name)
+}
+
+/// The text of the first child element of `node` called `name`, ignoring namespaces.
+fn child_text<'a>(node: Node<'a, '_>, name: &str) -> Option<&'a str> {
+    node.children()
+        .find(|child| child.is_element() && child.tag_name().name() == name)
+        .and_then(|child| child.text())
+        .map(str::trim)
+        .filter(|text| !text.is_empty())
+}
+
+fn sitemap_entry(node: Node) -> Option<FeedEntry> {
+    let mut entry = FeedEntry::new(child_text(node, "loc")?);
+    entry.lastmod = child_text(node, "lastmod").and_then(parse_date);
+    Some(entry)
+}
+
+fn rss_entry(node: Node) -> Option<FeedEntry> {
+    let mut entry = FeedEntry::new(child_text(node, "link")?);
+    entry.published = child_text(node, "pubDate")
+        .or_else(|| child_text(node, "date"))
+        .and_then(parse_date);
+    entry.title = child_text(node, "title").map(str::to_string);
+    Some(entry)
+}
+
+fn atom_entry(node: Node) -> Option<FeedEntry> {
+    let link = elements(node, "link")
+        .find(|link| link.attribute("rel").is_none_or(|rel| rel == "alternate"))?
+        .attribute("href")?;
+    let mut entry = FeedEntry::new(link);
+    entry.lastmod = child_text(node, "updated").and_then(parse_date);
+    entry.published = child_text(node, "published").and_then(parse_date);
+    entry.title = child_text(node, "title").map(str::to_string);
+    Some(entry)
+}
+
+/// Parses the date formats of sitemaps (W3C datetime), RSS (RFC 2822) and Atom (RFC 3339).
+/// Dates without a time are taken as midnight UTC.
+fn parse_date(date: &str) -> Option<DateTime<FixedOffset>> {
+    DateTime::parse_from_rfc3339(date)
+        .or_else(|_| DateTime::parse_from_rfc2822(date))
+        .or_else(|_| DateTime::parse_from_str(date, "%Y-%m-%dT%H:%M%:z"))
+        .ok()
+        .or_else(|| {
+            let day = NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()?;
+            Some(day.and_hms_opt(0, 0, 0)?.and_utc().fixed_offset())
+        })
+}
+
+/// The maximum uncompressed size of a gzipped sitemap, which the sitemap protocol caps at 50MB.
+const MAX_DECOMPRESSED_SIZE: u64 = 50 * 1024 * 1024;
+
+/// Decodes a sitemap or feed, which may be gzipped. Fails if it is larger than `max_size` bytes
+/// uncompressed.
+fn decode(bytes: &[u8], max_size: u64) -> Result<String> {
+    if bytes.starts_with(&[0x1f, 0x8b]) {
+        let mut xml = String::new();
+        GzDecoder::new(bytes).take(max_size + 1).read_to_string(&mut xml)?;
+        if xml.len() as u64 > max_size {
+            bail!("Gzipped sitemap is larger than {} bytes uncompressed", max_size);
+        }
+        Ok(xml)
+    } else {
+        Ok(String::from_utf8_lossy(bytes).to_string())
+    }
+}
+
+/// A page returned by [FeedSource::next_page].
+#[derive(Debug, Clone)]
+pub struct FeedPage {
+    pub entry: FeedEntry,
+    pub html: String,
+}
+
+/// Fetches the pages listed in a sitemap or feed, one by one. Sitemap indexes are followed, and
+/// the pages are fetched in the order they are listed. Pages that fail to load, or are not HTML,
+/// are logged and skipped.
+pub struct FeedSource {
+    config: FeedConfig,
+    client: reqwest::Client,
+    entries: VecDeque<FeedEntry>,
+    pages: usize,
+    next_request: Instant,
+}
+
+impl FeedSource {
+    /// Fetches the sitemap or feed at `url`, and the sitemaps it lists if it is a sitemap index.
+    pub async fn new(url: &str, config: FeedConfig) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .user_agent(config.user_agent.clone())
+            .timeout(config.timeout)
+            .build()?;
+        let mut source = Self {
+            config,
+            client,
+            entries: VecDeque::new(),
+            pages: 0,
+            next_request: Instant::now(),
+        };
+
+        let mut sitemaps = VecDeque::from([url.to_string()]);
+        let mut visited = HashSet::new();
+        while let Some(sitemap) = sitemaps.pop_front() {
+            if visited.len() >= source.config.max_sitemaps || !visited.insert(sitemap.clone()) {
+                continue;
+            }
+            let xml = match source
+                .get_bytes(&sitemap)
+                .await
+                .and_then(|bytes| decode(&bytes, MAX_DECOMPRESSED_SIZE))
+            {
+                Ok(xml) => xml,
+                // Only the top level sitemap or feed has to load.
+                Err(e) if sitemap != url => {
+                    eprintln!("Skipping {}: {:?}", sitemap, e);
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+            match parse_feed(&xml)? {
+                Feed::Pages(entries) => source
+                    .entries
+                    .extend(entries.into_iter().filter(|entry| source.config.is_recent(entry))),
+                Feed::Sitemaps(entries) => sitemaps.extend(
+                    entries
+                        .into_iter()
+                        .filter(|entry| source.config.is_recent(entry))
+                        .map(|entry| entry.url),
+                ),
+            }
+        }
+        Ok(source)
+    }
+
+    /// The entries whose pages have not been fetched yet.
+    pub fn entries(&self) -> impl Iterator<Item = &FeedEntry> {
+        self.entries.iter()
+    }
+
+    /// Fetches the next page. Returns `None` once every page has been fetched, or `max_pages`
+    /// pages have been returned.
+    pub async fn next_page(&mut self) -> Option<FeedPage> {
+        while self
+            .config
+            .max_pages
+            .is_none_or(|max_pages| self.pages < max_pages)
+        {
+            let entry = self.entries.pop_front()?;
+            match self.fetch_html(&entry.url).await {
+                Ok(html) => {
+                    self.pages += 1;
+                    return Some(FeedPage { entry, html });
+                }
+                Err(e) => eprintln!("Skipping {}: {:?}", entry.url, e),
+            }
+        }
+        None
+    }
+
+    /// Fetches the page at `url`, which has to be an HTML page.
+    async fn fetch_html(&mut self, url: &str) -> Result<String> {
+        let response = self.get(url).await?;
+        let is_html = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|content_type| content_type.to_str().ok())
+            .is_none_or(|content_type| content_type.contains("html"));
+        if !is_html {
+            bail!("Not an HTML page");
+        }
+        Ok(response.text().await?)
+    }
+
+    async fn get_bytes(&mut self, url: &str) -> Result<Vec<u8>> {
+        Ok(self.get(url).await?.bytes().await?.to_vec())
+    }
+
+    /// Sends a GET request once the delay since the last request has passed.
+    async fn get(&mut self, url: &str) -> Result<reqwest::Response> {
+        let wait = self.next_request.saturating_duration_since(Instant::now());
+        self.next_request = Instant::now().max(self.next_request) + self.config.delay;
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+        Ok(self.client.get(url).send().await?.error_for_status()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    #[test]
+    fn test_parse_sitemap() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+            <urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+                <url><loc>https://example.com/docs/</loc><lastmod>2024-03-01</lastmod></url>
+                <url><loc> https://example.com/blog/ </loc></url>
+            </urlset>"#;
+        let Feed::Pages(entries) = parse_feed(xml).unwrap() else {
+            panic!("Expected pages");
+        };
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].url, "https://example.com/docs/");
+        assert_eq!(
+            entries[0].lastmod.unwrap().to_rfc3339(),
+            "2024-03-01T00:00:00+00:00"
+        );
+        assert_eq!(entries[1].url, "https://example.com/blog/");
+        assert_eq!(entries[1].lastmod, None);
+
+        let config = FeedConfig::default()
+            .with_modified_since("2024-02-01T00:00:00Z".parse().unwrap());
+        assert!(config.is_recent(&entries[0]));
+        assert!(config.is_recent(&entries[1]));
+        let config = FeedConfig::default()
+            .with_modified_since("2024-04-01T00:00:00Z".parse().unwrap());
+        assert!(!config.is_recent(&entries[0]));
+    }
+
+    #[test]
+    fn test_parse_gzipped_sitemap_index() {
+        let xml = r#"<sitemapindex xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+                <sitemap><loc>https://example.com/sitemap-docs.xml.gz</loc><lastmod>2024-03-01T10:00:00+01:00</lastmod></sitemap>
+            </sitemapindex>"#;
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(xml.as_bytes()).unwrap();
+        let gzipped = encoder.finish().unwrap();
+
+        let xml = decode(&gzipped, MAX_DECOMPRESSED_SIZE).unwrap();
+        let Feed::Sitemaps(sitemaps) = parse_feed(&xml).unwrap() else {
+            panic!("Expected sitemaps");
+        };
+        assert_eq!(sitemaps[0].url, "https://example.com/sitemap-docs.xml.gz");
+        assert_eq!(
+            sitemaps[0].lastmod.unwrap().to_rfc3339(),
+            "2024-03-01T10:00:00+01:00"
+        );
+        // A sitemap that decompresses to more than the limit is rejected.
+        assert!(decode(&gzipped, xml.len() as u64 - 1).is_err());
+    }
+
+    #[test]
+    fn test_parse_rss_and_atom() {
+        let rss = r#"<rss version="2.0"><channel><title>Blog</title>
+                <item><title>Release 1.0</title><link>https://example.com/1.0</link>
+                <pubDate>Tue, 05 Mar 2024 09:30:00 GMT</pubDate></item>
+            </channel></rss>"#;
+        let Feed::Pages(items) = parse_feed(rss).unwrap() else {
+            panic!("Expected pages");
+        };
+        assert_eq!(items[0].url, "https://example.com/1.0");
+        assert_eq!(items[0].title.as_deref(), Some("Release 1.0"));
+        assert_eq!(
+            items[0].published.unwrap().to_rfc3339(),
+            "2024-03-05T09:30:00+00:00"
+        );
+
+        let atom = r#"<feed xmlns="http://www.w3.org/2005/Atom"><title>Blog</title>
+                <entry><title>Release 2.0</title>
+                <link rel="alternate" href="https://example.com/2.0"/>
+                <link rel="edit" href="https://example.com/edit/2.0"/>
+                <updated>2024-04-02T12:00:00Z</updated><published>2024-04-01T12:00:00Z</published></entry>
+            </feed>"#;
+        let Feed::Pages(entries) = parse_feed(atom).unwrap() else {
+            panic!("Expected pages");
+        };
+        assert_eq!(entries[0].url, "https://example.com/2.0");
+        let metadata = entries[0].metadata();
+        assert!(metadata.contains(&("lastmod".to_string(), "2024-04-02T12:00:00+00:00".to_string())));
+        assert!(metadata.contains(&("published".to_string(), "2024-04-01T12:00:00+00:00".to_string())));
+    }
+
+    #[tokio::test]
+    async fn test_skips_pages_that_are_not_html() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let root = format!("http://{}", listener.local_addr().unwrap());
+        let sitemap = format!(
+            r#"<urlset><url><loc>{root}/report.pdf</loc></url><url><loc>{root}/docs</loc></url></urlset>"#
+        );
+        tokio::spawn(async move {
+            loop {
+                let Ok((mut stream, _)) = listener.accept().await else {
+                    return;
+                };
+                let mut request = vec![0; 4096];
+                let read = stream.read(&mut request).await.unwrap_or(0);
+                let request = String::from_utf8_lossy(&request[..read]);
+                let (content_type, body) = match request.split_whitespace().nth(1) {
+                    Some("/sitemap.xml") => ("application/xml", sitemap.as_str()),
+                    Some("/report.pdf") => ("application/pdf", "%PDF-1.4"),
+                    _ => ("text/html", "<p>Docs</p>"),
+                };
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    content_type,
+                    body.len(),
+                    body
+                );
+                let _ = stream.write_all(response.as_bytes()).await;
+            }
+        });
+
+        let config = FeedConfig::default().with_delay(Duration::ZERO);
+        let mut source = FeedSource::new(&format!("{}/sitemap.xml", root), config)
+            .await
+            .unwrap();
+        let page = source.next_page().await.unwrap();
+        assert_eq!(page.entry.url, format!("{}/docs", root));
+        assert_eq!(page.html, "<p>Docs</p>");
+        assert!(source.next_page().await.is_none());
+    }
+}
@@ -1,11 +1,148 @@
-use std::{collections::HashSet, io::Error, path::PathBuf};
+use std::{
+    collections::HashSet,
+    io::{Error, ErrorKind},
+    path::{Path, PathBuf},
+};
 
+use ignore::{overrides::OverrideBuilder, WalkBuilder};
 use regex::Regex;
-use walkdir::WalkDir;
+
+use crate::archive::is_archive;
 // use tokio::fs;
 
+/// Which files under a directory [FileParser] picks up, on top of the extensions it looks for.
+///
+/// # Example
+///
+/// ```rust
+/// use embed_anything::file_loader::FileFilter;
+/// let filter = FileFilter::default()
+///     .with_include(&["docs/**"])
+///     .with_exclude(&["**/drafts/**", "*.min.md"])
+///     .with_max_depth(4)
+///     .with_max_file_size(10 * 1024 * 1024);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileFilter {
+    /// Glob patterns, relative to the directory, that a file has to match to be picked up. Uses
+    /// the `.gitignore` syntax, so `*.md` matches at any depth. Defaults to every file.
+    pub include: Vec<String>,
+    /// Glob patterns, relative to the directory, of files and directories to skip, e.g.
+    /// `node_modules`. Takes precedence over `include`.
+    pub exclude: Vec<String>,
+    /// Skips the files listed in `.gitignore`, `.ignore` and `.git/info/exclude` files, and the
+    /// `.git` directory itself. Files matching `include` are picked up regardless. Defaults to true.
+    pub respect_ignore_files: bool,
+    /// How many directories deep to descend, where 1 only looks at the files of the directory
+    /// itself. Defaults to no limit.
+    pub max_depth: Option<usize>,
+    /// Follows symbolic links to files and directories. Defaults to false, which skips them.
+    pub follow_symlinks: bool,
+    /// Skips files larger than this many bytes. Defaults to no limit.
+    pub max_file_size: Option<u64>,
+    /// Also picks up `.zip`, `.tar`, `.tar.gz` and `.tgz` archives when looking for text files,
+    /// so the documents in them get embedded. Defaults to false.
+    pub archives: bool,
+}
+
+impl Default for FileFilter {
+    fn default() -> Self {
+        Self {
+            include: Vec::new(),
+            exclude: Vec::new(),
+            respect_ignore_files: true,
+            max_depth: None,
+            follow_symlinks: false,
+            max_file_size: None,
+            archives: false,
+        }
+    }
+}
+
+impl FileFilter {
+    pub fn with_include(mut self, patterns: &[&str]) -> Self {
+        self.include = patterns.iter().map(|p| p.to_string()).collect();
+        self
+    }
+
+    pub fn with_exclude(mut self, patterns: &[&str]) -> Self {
+        self.exclude = patterns.iter().map(|p| p.to_string()).collect();
+        self
+    }
+
+    pub fn with_respect_ignore_files(mut self, respect_ignore_files: bool) -> Self {
+        self.respect_ignore_files = respect_ignore_files;
+        self
+    }
+
+    pub fn with_max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = Some(depth);
+        self
+    }
+
+    pub fn with_follow_symlinks(mut self, follow_symlinks: bool) -> Self {
+        self.follow_symlinks = follow_symlinks;
+        self
+    }
+
+    pub fn with_max_file_size(mut self, bytes: u64) -> Self {
+        self.max_file_size = Some(bytes);
+        self
+    }
+
+    pub fn with_archives(mut self, archives: bool) -> Self {
+        self.archives = archives;
+        self
+    }
+
+    /// Walks `directory_path`, or only the part of it on the way to `within` and under it, with
+    /// the same rules either way.
+    fn walk_builder(
+        &self,
+        directory_path: &PathBuf,
+        within: Option<&Path>,
+    ) -> Result<WalkBuilder, Error> {
+        let mut overrides = OverrideBuilder::new(directory_path);
+        for pattern in &self.include {
+            overrides.add(pattern).map_err(invalid_pattern)?;
+        }
+        for pattern in &self.exclude {
+            overrides
+                .add(&format!("!{}", pattern))
+                .map_err(invalid_pattern)?;
+        }
+
+        let mut builder = WalkBuilder::new(directory_path);
+        builder
+            .standard_filters(false)
+            .git_ignore(self.respect_ignore_files)
+            .git_exclude(self.respect_ignore_files)
+            .ignore(self.respect_ignore_files)
+            .parents(self.respect_ignore_files)
+            .require_git(false)
+            .max_depth(self.max_depth)
+            .follow_links(self.follow_symlinks)
+            .max_filesize(self.max_file_size)
+            .overrides(overrides.build().map_err(invalid_pattern)?);
+        let skip_git = self.respect_ignore_files;
+        let within = within.map(Path::to_path_buf);
+        builder.filter_entry(move |entry| {
+            let on_the_way = within.as_ref().is_none_or(|within| {
+                within.starts_with(entry.path()) || entry.path().starts_with(within)
+            });
+            on_the_way && !(skip_git && entry.file_name() == ".git")
+        });
+        Ok(builder)
+    }
+}
+
+fn invalid_pattern(e: ignore::Error) -> Error {
+    Error::new(ErrorKind::InvalidInput, e)
+}
+
 pub struct FileParser {
     pub files: Vec<String>,
+    pub filter: FileFilter,
 }
 
 impl Default for FileParser {
@@ -16,7 +153,14 @@ impl Default for FileParser {
 
 impl FileParser {
     pub fn new() -> Self {
-        Self { files: Vec::new() }
+        Self::with_filter(FileFilter::default())
+    }
+
+    pub fn with_filter(filter: FileFilter) -> Self {
+        Self {
+            files: Vec::new(),
+            filter,
+        }
     }
 
     pub fn get_text_files(
@@ -24,58 +168,70 @@ impl FileParser {
         directory_path: &PathBuf,
         extensions: Option<Vec<String>>,
     ) -> Result<Vec<String>, Error> {
-        let extension_regex = match extensions {
-            Some(exts) => Regex::new(&format!(r"\.({})$", exts.join("|"))).unwrap(),
-            None => Regex::new(r"\.(pdf|md|txt|docx)$").unwrap(),
-        };
+        self.files = self.text_files(directory_path, None, extensions)?;
+        Ok(self.files.clone())
+    }
 
-        let files: Vec<String> = WalkDir::new(directory_path)
-            .into_iter()
-            .filter_map(|entry| entry.ok())
-            .filter(|entry| entry.file_type().is_file())
-            .filter(|entry| extension_regex.is_match(entry.file_name().to_str().unwrap_or("")))
-            .map(|entry| {
-                let absolute_path = entry
-                    .path()
-                    .canonicalize()
-                    .unwrap_or_else(|_| entry.path().to_path_buf());
-                absolute_path.to_string_lossy().to_string()
-            })
-            .collect();
+    /// Returns the text files [FileParser::get_text_files] picks up in `directory_path` that are
+    /// `path` or under it, e.g. to check a file or directory that changed against the filter,
+    /// which is relative to `directory_path`.
+    pub fn get_text_files_under(
+        &self,
+        directory_path: &PathBuf,
+        path: &Path,
+        extensions: Option<Vec<String>>,
+    ) -> Result<Vec<String>, Error> {
+        self.text_files(directory_path, Some(path), extensions)
+    }
 
-        self.files = files;
-        Ok(self.files.clone())
+    fn text_files(
+        &self,
+        directory_path: &PathBuf,
+        within: Option<&Path>,
+        extensions: Option<Vec<String>>,
+    ) -> Result<Vec<String>, Error> {
+        let extension_regex = Self::text_file_regex(extensions);
+        let archives = self.filter.archives;
+        self.walk(directory_path, within, |name| {
+            extension_regex.is_match(name) || (archives && is_archive(name))
+        })
+    }
+
+    /// Returns the regex matching the file names [FileParser::get_text_files] picks up.
+    pub fn text_file_regex(extensions: Option<Vec<String>>) -> Regex {
+        match extensions {
+            Some(exts) => Regex::new(&format!(r"\.({})$", exts.join("|"))).unwrap(),
+            None => Regex::new(r"\.(pdf|md|txt|docx)$").unwrap(),
+        }
     }
 
     pub fn get_image_paths(&mut self, directory_path: &PathBuf) -> Result<Vec<String>, Error> {
         let image_regex = Regex::new(r".*\.(png|jpg|jpeg|gif|bmp|tiff|webp)$").unwrap();
-
-        let image_paths: Vec<String> = WalkDir::new(directory_path)
-            .into_iter()
-            .filter_map(|entry| entry.ok())
-            .filter(|entry| entry.file_type().is_file())
-            .filter(|entry| image_regex.is_match(entry.file_name().to_str().unwrap_or("")))
-            .map(|entry| {
-                let absolute_path = entry
-                    .path()
-                    .canonicalize()
-                    .unwrap_or_else(|_| entry.path().to_path_buf());
-                absolute_path.to_string_lossy().to_string()
-            })
-            .collect();
-
-        self.files = image_paths;
+        self.files = self.walk(directory_path, None, |name| image_regex.is_match(name))?;
         Ok(self.files.clone())
     }
 
     pub fn get_audio_files(&mut self, directory_path: &PathBuf) -> Result<Vec<String>, Error> {
         let audio_regex = Regex::new(r".*\.(wav)$").unwrap();
+        self.files = self.walk(directory_path, None, |name| audio_regex.is_match(name))?;
+        Ok(self.files.clone())
+    }
 
-        let audio_paths: Vec<String> = WalkDir::new(directory_path)
-            .into_iter()
+    /// Returns the absolute paths of the files under `directory_path`, or only under `within` if
+    /// given, that pass the filter and whose name is accepted by `keep`.
+    fn walk(
+        &self,
+        directory_path: &PathBuf,
+        within: Option<&Path>,
+        keep: impl Fn(&str) -> bool,
+    ) -> Result<Vec<String>, Error> {
+        let files = self
+            .filter
+            .walk_builder(directory_path, within)?
+            .build()
             .filter_map(|entry| entry.ok())
-            .filter(|entry| entry.file_type().is_file())
-            .filter(|entry| audio_regex.is_match(entry.file_name().to_str().unwrap_or("")))
+            .filter(|entry| entry.file_type().is_some_and(|file_type| file_type.is_file()))
+            .filter(|entry| keep(entry.file_name().to_str().unwrap_or("")))
             .map(|entry| {
                 let absolute_path = entry
                     .path()
@@ -84,9 +240,7 @@ impl FileParser {
                 absolute_path.to_string_lossy().to_string()
             })
             .collect();
-
-        self.files = audio_paths;
-        Ok(self.files.clone())
+        Ok(files)
     }
 
     pub fn get_files_to_index(&self, indexed_files: &HashSet<String>) -> Vec<String> {
@@ -232,4 +386,67 @@ mod tests {
                 .to_string()
         );
     }
+
+    #[test]
+    fn test_file_filter() {
+        let temp_dir = TempDir::new("example").unwrap();
+        let root = temp_dir.path();
+        for dir in ["docs/drafts", "node_modules/pkg", "build"] {
+            std::fs::create_dir_all(root.join(dir)).unwrap();
+        }
+        for file in [
+            "readme.md",
+            "docs/guide.md",
+            "docs/drafts/todo.md",
+            "node_modules/pkg/readme.md",
+            "build/out.md",
+        ] {
+            File::create(root.join(file)).unwrap();
+        }
+        std::fs::write(root.join("docs/large.md"), "x".repeat(100)).unwrap();
+        std::fs::write(root.join(".gitignore"), "build/\n").unwrap();
+
+        let file_names = |filter: FileFilter| {
+            let mut files = FileParser::with_filter(filter)
+                .get_text_files(&PathBuf::from(root), Some(vec!["md".to_string()]))
+                .unwrap()
+                .iter()
+                .map(|f| {
+                    PathBuf::from(f)
+                        .strip_prefix(root.canonicalize().unwrap())
+                        .unwrap()
+                        .to_string_lossy()
+                        .to_string()
+                })
+                .collect::<Vec<_>>();
+            files.sort();
+            files
+        };
+
+        let filter = FileFilter::default().with_exclude(&["node_modules", "drafts"]);
+        assert_eq!(
+            file_names(filter.clone()),
+            vec!["docs/guide.md", "docs/large.md", "readme.md"]
+        );
+        assert_eq!(
+            file_names(filter.clone().with_include(&["docs/**"]).with_max_file_size(10)),
+            vec!["docs/guide.md"]
+        );
+        assert_eq!(file_names(filter.clone().with_max_depth(1)), vec!["readme.md"]);
+
+        // A single file or directory is checked against the rules of the whole directory.
+        let root = root.canonicalize().unwrap();
+        let parser = FileParser::with_filter(filter.clone());
+        let under = |path: &str| {
+            parser
+                .get_text_files_under(&root, &root.join(path), Some(vec!["md".to_string()]))
+                .unwrap()
+                .len()
+        };
+        assert_eq!(under("docs"), 2);
+        assert_eq!(under("docs/guide.md"), 1);
+        assert_eq!(under("docs/drafts/todo.md"), 0);
+        assert_eq!(under("build/out.md"), 0);
+        assert!(file_names(filter.with_respect_ignore_files(false)).contains(&"build/out.md".to_string()));
+    }
 }
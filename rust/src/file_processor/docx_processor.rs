@@ -1,8 +1,10 @@
+use std::io::Cursor;
 use std::path::Path;
-use docx_parser::MarkdownDocument;
+use docx_rust::document::{BodyContent, Paragraph, Table, TableCellContent, TableRowContent};
+use docx_rust::{Docx, DocxFile};
 use text_splitter::ChunkConfigError;
-use crate::file_processor::markdown_processor::MarkdownProcessor;
-use crate::file_processor::processor::{Document, DocumentProcessor, FileProcessor};
+use crate::file_processor::markdown_processor::{ChunkSizing, MarkdownProcessor};
+use crate::file_processor::processor::{BytesProcessor, Document, DocumentProcessor, FileProcessor};
 
 /// A struct for processing PDF files.
 pub struct DocxProcessor {
@@ -11,7 +13,11 @@ pub struct DocxProcessor {
 
 impl DocxProcessor {
     pub fn new(chunk_size: usize, overlap: usize) -> Result<DocxProcessor, ChunkConfigError> {
-        let markdown_processor = MarkdownProcessor::new(chunk_size, overlap)?;
+        Self::with_sizing(chunk_size, overlap, ChunkSizing::Characters)
+    }
+
+    pub fn with_sizing(chunk_size: usize, overlap: usize, sizing: ChunkSizing) -> Result<DocxProcessor, ChunkConfigError> {
+        let markdown_processor = MarkdownProcessor::with_sizing(chunk_size, overlap, sizing)?;
         Ok(DocxProcessor {
             markdown_processor,
         })
@@ -20,12 +26,93 @@ impl DocxProcessor {
 
 impl FileProcessor for DocxProcessor {
     fn process_file(&self, path: impl AsRef<Path>) -> anyhow::Result<Document> {
-        let docs = MarkdownDocument::from_file(path);
-        let markdown = docs.to_markdown(false);
-        self.markdown_processor.process_document(&markdown)
+        self.process_bytes(&std::fs::read(path)?)
+    }
+}
+
+impl BytesProcessor for DocxProcessor {
+    fn process_bytes(&self, bytes: &[u8]) -> anyhow::Result<Document> {
+        let file = DocxFile::from_reader(Cursor::new(bytes))
+            .map_err(|e| anyhow::anyhow!("Error processing docx: {:?}", e))?;
+        let docx = file
+            .parse()
+            .map_err(|e| anyhow::anyhow!("Error parsing docx: {:?}", e))?;
+        self.markdown_processor.process_document(&to_markdown(&docx))
     }
 }
 
+/// Converts the paragraphs and tables of the document body to markdown.
+fn to_markdown(docx: &Docx) -> String {
+    let blocks: Vec<String> = docx
+        .document
+        .body
+        .content
+        .iter()
+        .filter_map(|content| match content {
+            BodyContent::Paragraph(paragraph) => paragraph_to_markdown(paragraph),
+            BodyContent::Table(table) => table_to_markdown(table),
+            _ => None,
+        })
+        .collect();
+    blocks.join("\n\n")
+}
+
+fn paragraph_to_markdown(paragraph: &Paragraph) -> Option<String> {
+    let text = paragraph.text();
+    let text = text.trim();
+    if text.is_empty() {
+        return None;
+    }
+    let level = paragraph
+        .property
+        .as_ref()
+        .and_then(|property| property.style_id.as_ref())
+        .and_then(|style| style.value.strip_prefix("Heading")?.parse::<usize>().ok())
+        .filter(|level| (1..=6).contains(level));
+    match level {
+        Some(level) => Some(format!("{} {}", "#".repeat(level), text)),
+        None => Some(text.to_string()),
+    }
+}
+
+fn table_to_markdown(table: &Table) -> Option<String> {
+    let rows: Vec<Vec<String>> = table
+        .rows
+        .iter()
+        .map(|row| {
+            row.cells
+                .iter()
+                .filter_map(|cell| match cell {
+                    TableRowContent::TableCell(cell) => Some(
+                        cell.content
+                            .iter()
+                            .map(|content| match content {
+                                TableCellContent::Paragraph(paragraph) => paragraph.text(),
+                            })
+                            .collect::<Vec<_>>()
+                            .join(" ")
+                            .trim()
+                            .replace('|', "\\|"),
+                    ),
+                    _ => None,
+                })
+                .collect()
+        })
+        .filter(|cells: &Vec<String>| !cells.is_empty())
+        .collect();
+    let columns = rows.iter().map(Vec::len).max()?;
+    let mut lines: Vec<String> = rows
+        .iter()
+        .map(|cells| {
+            let mut cells = cells.clone();
+            cells.resize(columns, String::new());
+            format!("| {} |", cells.join(" | "))
+        })
+        .collect();
+    lines.insert(1, format!("|{}", " --- |".repeat(columns)));
+    Some(lines.join("\n"))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -40,10 +127,21 @@ mod tests {
 
     // Returns an error if the file path is invalid.
     #[test]
-    #[should_panic(expected = "Error processing file: IO(Os { code: 2, kind: NotFound, message: \"No such file or directory\" })")]
     fn test_extract_text_invalid_file_path() {
         let invalid_file_path = "this_file_definitely_does_not_exist.docx";
         let processor = DocxProcessor::new(128, 0).unwrap();
-        processor.process_file(&invalid_file_path).unwrap();
+        let error = processor.process_file(&invalid_file_path).unwrap_err();
+        let error = error.downcast_ref::<std::io::Error>().unwrap();
+        assert_eq!(error.kind(), std::io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn test_process_bytes() {
+        let bytes = std::fs::read("../test_files/test.docx").unwrap();
+        let processor = DocxProcessor::new(128, 0).unwrap();
+
+        let document = processor.process_bytes(&bytes).unwrap();
+        assert!(document.chunks.iter().any(|chunk| chunk.contains("This is a docx file test")));
+        assert!(processor.process_bytes(b"not a docx").is_err());
     }
 }
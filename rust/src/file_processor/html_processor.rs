@@ -1,6 +1,9 @@
-use crate::file_processor::markdown_processor::MarkdownProcessor;
-use anyhow::Result;
+use std::collections::{BTreeMap, HashMap};
+
+use crate::file_processor::markdown_processor::{ChunkSizing, MarkdownProcessor};
+use anyhow::{anyhow, Result};
 use htmd::{HtmlToMarkdown, HtmlToMarkdownBuilder};
+use scraper::{ElementRef, Html, Selector};
 use text_splitter::ChunkConfigError;
 use crate::file_processor::processor::{Document, DocumentProcessor};
 
@@ -9,28 +12,399 @@ pub struct HtmlDocument {
     pub origin: Option<String>,
 }
 
+/// What the `<head>` of an HTML page says about it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HtmlMetadata {
+    /// The `<title>`, or the `og:title` if there is none.
+    pub title: Option<String>,
+    /// The `description` meta tag, or the `og:description` if there is none.
+    pub description: Option<String>,
+    /// The `href` of `<link rel="canonical">`, as written in the page.
+    pub canonical_url: Option<String>,
+    /// The `lang` of `<html>`, or the `content-language` meta tag.
+    pub language: Option<String>,
+    /// The OpenGraph meta tags, e.g. `og:type` or `og:site_name`, by property.
+    pub open_graph: BTreeMap<String, String>,
+    /// The `article:published_time`, or a date meta tag such as `date` or `datePublished`.
+    pub published: Option<String>,
+}
+
+impl HtmlMetadata {
+    /// Reads the metadata of an HTML page. Missing or empty tags are left out.
+    pub fn from_html(html: &str) -> Self {
+        let html = Html::parse_document(html);
+        let select = |selector: &str| {
+            let selector = Selector::parse(selector).unwrap();
+            html.select(&selector).next()
+        };
+        let attr = |selector: &str, name: &str| {
+            select(selector)
+                .and_then(|element| element.attr(name))
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+                .map(str::to_string)
+        };
+
+        let meta = Selector::parse("meta[property][content]").unwrap();
+        let open_graph: BTreeMap<String, String> = html
+            .select(&meta)
+            .filter_map(|element| {
+                let property = element.attr("property")?.trim();
+                let content = element.attr("content")?.trim();
+                (property.starts_with("og:") && !content.is_empty())
+                    .then(|| (property.to_string(), content.to_string()))
+            })
+            .collect();
+
+        let title = select("title")
+            .map(|title| title.text().collect::<String>().trim().to_string())
+            .filter(|title| !title.is_empty())
+            .or_else(|| open_graph.get("og:title").cloned());
+        let description = attr(r#"meta[name="description" i]"#, "content")
+            .or_else(|| open_graph.get("og:description").cloned());
+        let canonical_url = attr(r#"link[rel="canonical" i]"#, "href");
+        let language = attr("html", "lang")
+            .or_else(|| attr(r#"meta[http-equiv="content-language" i]"#, "content"));
+        let published = attr(r#"meta[property="article:published_time"]"#, "content")
+            .or_else(|| {
+                attr(
+                    r#"meta[name="date" i], meta[name="dc.date" i], meta[name="pubdate" i], meta[name="publish_date" i]"#,
+                    "content",
+                )
+            })
+            .or_else(|| attr(r#"[itemprop="datePublished"]"#, "content"))
+            .or_else(|| attr(r#"[itemprop="datePublished"]"#, "datetime"));
+
+        Self {
+            title,
+            description,
+            canonical_url,
+            language,
+            open_graph,
+            published,
+        }
+    }
+
+    /// The metadata as `EmbedData` metadata, with the OpenGraph tags under their property.
+    pub fn metadata(&self) -> HashMap<String, String> {
+        let mut metadata: HashMap<String, String> = self.open_graph.clone().into_iter().collect();
+        let fields = [
+            ("title", &self.title),
+            ("description", &self.description),
+            ("canonical_url", &self.canonical_url),
+            ("language", &self.language),
+            ("published", &self.published),
+        ];
+        for (key, value) in fields {
+            if let Some(value) = value {
+                metadata.insert(key.to_string(), value.clone());
+            }
+        }
+        metadata
+    }
+}
+
+/// Which part of an HTML page is converted to markdown and embedded. By default, the whole page
+/// is.
+///
+/// # Example
+///
+/// ```rust
+/// use embed_anything::file_processor::html_processor::ContentExtraction;
+/// let extraction = ContentExtraction::default()
+///     .with_main_content(true)
+///     .with_drop_selectors(&[".cookie-banner", "#comments"]);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ContentExtraction {
+    /// Keeps only the main content of the page, found by scoring its blocks of text like
+    /// readability does, so that navigation, banners, sidebars and footers are not embedded.
+    /// Defaults to false.
+    pub main_content: bool,
+    /// CSS selectors of the elements that hold the content, e.g. `article`. When one of them
+    /// matches, only the matching elements are kept and the page is not scored. Defaults to none.
+    pub keep_selectors: Vec<String>,
+    /// CSS selectors of the elements that are removed before anything else, e.g.
+    /// `.cookie-banner`. Defaults to none.
+    pub drop_selectors: Vec<String>,
+}
+
+impl ContentExtraction {
+    pub fn with_main_content(mut self, main_content: bool) -> Self {
+        self.main_content = main_content;
+        self
+    }
+
+    pub fn with_keep_selectors(mut self, selectors: &[&str]) -> Self {
+        self.keep_selectors = selectors.iter().map(|s| s.to_string()).collect();
+        self
+    }
+
+    pub fn with_drop_selectors(mut self, selectors: &[&str]) -> Self {
+        self.drop_selectors = selectors.iter().map(|s| s.to_string()).collect();
+        self
+    }
+}
+
+/// A [ContentExtraction] with its selectors parsed.
+#[derive(Default)]
+struct ContentFilter {
+    main_content: bool,
+    keep: Vec<Selector>,
+    drop: Vec<Selector>,
+}
+
+/// Elements that are never part of the main content.
+const BOILERPLATE_TAGS: [&str; 9] = [
+    "nav", "aside", "footer", "form", "iframe", "script", "style", "noscript", "template",
+];
+
+/// Words of `class` and `id` attributes that mark an element as boilerplate, or as content.
+const UNLIKELY_WORDS: [&str; 27] = [
+    "ad", "ads", "advert", "banner", "breadcrumb", "breadcrumbs", "comment", "comments", "consent",
+    "cookie", "cookies", "footer", "gdpr", "header", "menu", "modal", "nav", "navbar",
+    "newsletter", "pagination", "popup", "promo", "related", "share", "sidebar", "social",
+    "subscribe",
+];
+const LIKELY_WORDS: [&str; 9] = [
+    "article", "blog", "body", "content", "entry", "main", "page", "post", "story",
+];
+
+impl ContentFilter {
+    fn new(extraction: &ContentExtraction) -> Result<Self> {
+        let parse = |selectors: &[String]| {
+            selectors
+                .iter()
+                .map(|selector| {
+                    Selector::parse(selector)
+                        .map_err(|e| anyhow!("Invalid CSS selector {:?}: {}", selector, e))
+                })
+                .collect::<Result<Vec<_>>>()
+        };
+        Ok(Self {
+            main_content: extraction.main_content,
+            keep: parse(&extraction.keep_selectors)?,
+            drop: parse(&extraction.drop_selectors)?,
+        })
+    }
+
+    /// Returns the HTML of the part of the page to embed.
+    fn apply(&self, content: &str) -> String {
+        if !self.main_content && self.keep.is_empty() && self.drop.is_empty() {
+            return content.to_string();
+        }
+        let mut html = Html::parse_document(content);
+        let dropped: Vec<_> = self
+            .drop
+            .iter()
+            .flat_map(|selector| html.select(selector).map(|element| element.id()))
+            .collect();
+        detach(&mut html, dropped);
+
+        let kept: Vec<_> = self
+            .keep
+            .iter()
+            .flat_map(|selector| html.select(selector))
+            .collect();
+        // Elements nested in another kept element are already part of it.
+        let kept: Vec<_> = kept
+            .iter()
+            .filter(|element| {
+                !element
+                    .ancestors()
+                    .any(|ancestor| kept.iter().any(|other| other.id() == ancestor.id()))
+            })
+            .collect();
+        if !kept.is_empty() {
+            return join_html(kept.into_iter().copied());
+        }
+
+        if self.main_content {
+            let boilerplate: Vec<_> = html
+                .root_element()
+                .descendants()
+                .filter_map(ElementRef::wrap)
+                .filter(|element| is_boilerplate(*element))
+                .map(|element| element.id())
+                .collect();
+            detach(&mut html, boilerplate);
+            let main_content = main_content(&html);
+            if !main_content.is_empty() {
+                return join_html(main_content.into_iter());
+            }
+        }
+        html.html()
+    }
+}
+
+fn detach(html: &mut Html, ids: Vec<ego_tree::NodeId>) {
+    for id in ids {
+        if let Some(mut node) = html.tree.get_mut(id) {
+            node.detach();
+        }
+    }
+}
+
+fn join_html<'a>(elements: impl Iterator<Item = ElementRef<'a>>) -> String {
+    elements
+        .map(|element| element.html())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Returns whether the `class` and `id` of `element` contain words of content, and of boilerplate.
+fn class_words(element: ElementRef) -> (bool, bool) {
+    let names = format!(
+        "{} {}",
+        element.attr("class").unwrap_or_default(),
+        element.attr("id").unwrap_or_default()
+    )
+    .to_lowercase();
+    let words: Vec<&str> = names
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect();
+    (
+        words.iter().any(|word| LIKELY_WORDS.contains(word)),
+        words.iter().any(|word| UNLIKELY_WORDS.contains(word)),
+    )
+}
+
+fn is_boilerplate(element: ElementRef) -> bool {
+    let tag = element.value().name();
+    if BOILERPLATE_TAGS.contains(&tag) {
+        return true;
+    }
+    let (likely, unlikely) = class_words(element);
+    unlikely && !likely && !["html", "body", "article", "main"].contains(&tag)
+}
+
+/// The score an element starts with when it becomes a candidate for the main content.
+fn initial_score(element: ElementRef) -> f64 {
+    let tag_score = match element.value().name() {
+        "div" => 5.0,
+        "pre" | "td" | "blockquote" => 3.0,
+        "address" | "ol" | "ul" | "dl" | "dd" | "dt" | "li" | "form" => -3.0,
+        "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "th" => -5.0,
+        _ => 0.0,
+    };
+    let (likely, unlikely) = class_words(element);
+    tag_score + if likely { 25.0 } else { 0.0 } - if unlikely { 25.0 } else { 0.0 }
+}
+
+fn text_length(element: ElementRef) -> usize {
+    element.text().map(|text| text.trim().chars().count()).sum()
+}
+
+/// The share of the text of `element` that is in links.
+fn link_density(element: ElementRef, links: &Selector) -> f64 {
+    let length = text_length(element);
+    if length == 0 {
+        return 0.0;
+    }
+    let link_length: usize = element.select(links).map(text_length).sum();
+    link_length as f64 / length as f64
+}
+
+/// Finds the main content of a page like readability: every paragraph of text adds to the score
+/// of its parent, and less to the ancestors above, the longer it is and the more commas it has.
+/// The score of a candidate then drops with the share of its text in links. The best candidate
+/// is returned, with its siblings that score well enough, as content is often split over
+/// several sections. Returns nothing when the page has no paragraph.
+fn main_content(html: &Html) -> Vec<ElementRef<'_>> {
+    let blocks = Selector::parse("p, pre, td, blockquote").unwrap();
+    let links = Selector::parse("a").unwrap();
+
+    let mut scores = HashMap::new();
+    for block in html.select(&blocks) {
+        let length = text_length(block);
+        if length < 25 {
+            continue;
+        }
+        let commas: usize = block.text().map(|text| text.matches(',').count()).sum();
+        let score = 1.0 + commas as f64 + (length / 100).min(3) as f64;
+        let ancestors = block.ancestors().filter_map(ElementRef::wrap).take(5);
+        for (level, ancestor) in ancestors.enumerate() {
+            let divider = match level {
+                0 => 1.0,
+                1 => 2.0,
+                level => level as f64 * 3.0,
+            };
+            *scores
+                .entry(ancestor.id())
+                .or_insert_with(|| initial_score(ancestor)) += score / divider;
+        }
+    }
+
+    let score = |element: ElementRef| {
+        let score = scores.get(&element.id())?;
+        Some(score * (1.0 - link_density(element, &links)))
+    };
+    let best = scores
+        .keys()
+        .filter_map(|id| html.tree.get(*id).and_then(ElementRef::wrap))
+        .filter_map(|element| Some((element, score(element)?)))
+        // Ties go to the element that comes first.
+        .max_by(|(a, a_score), (b, b_score)| a_score.total_cmp(b_score).then(b.id().cmp(&a.id())));
+    let Some((best, best_score)) = best else {
+        return Vec::new();
+    };
+
+    let threshold = (best_score * 0.2).max(10.0);
+    match best.parent().and_then(ElementRef::wrap) {
+        Some(parent) => parent
+            .children()
+            .filter_map(ElementRef::wrap)
+            .filter(|sibling| {
+                sibling.id() == best.id() || score(*sibling).is_some_and(|s| s >= threshold)
+            })
+            .collect(),
+        None => vec![best],
+    }
+}
+
 /// A Struct for processing HTML files.
 pub struct HtmlProcessor {
     markdown_processor: MarkdownProcessor,
     html_to_markdown: HtmlToMarkdown,
+    content_filter: ContentFilter,
 }
 
 impl HtmlProcessor {
     pub fn new(chunk_size: usize, overlap: usize) -> Result<HtmlProcessor, ChunkConfigError> {
-        let markdown_processor = MarkdownProcessor::new(chunk_size, overlap)?;
+        Self::with_sizing(chunk_size, overlap, ChunkSizing::Characters)
+    }
+
+    pub fn with_sizing(chunk_size: usize, overlap: usize, sizing: ChunkSizing) -> Result<HtmlProcessor, ChunkConfigError> {
+        let markdown_processor = MarkdownProcessor::with_sizing(chunk_size, overlap, sizing)?;
         let html_to_markdown = HtmlToMarkdownBuilder::new()
             .build();
         Ok(HtmlProcessor {
             markdown_processor,
             html_to_markdown,
+            content_filter: ContentFilter::default(),
         })
     }
+
+    /// Embeds only the part of every page selected by `extraction`. Fails if one of its
+    /// selectors is not valid CSS.
+    pub fn with_content_extraction(mut self, extraction: &ContentExtraction) -> Result<Self> {
+        self.content_filter = ContentFilter::new(extraction)?;
+        Ok(self)
+    }
 }
 
 impl DocumentProcessor for HtmlProcessor {
+    /// Converts the content of the page to markdown and splits it. The [HtmlMetadata] of the
+    /// page is the metadata of the document.
     fn process_document(&self, content: &str) -> Result<Document> {
-        let content = self.html_to_markdown.convert(content)?;
-        self.markdown_processor.process_document(&content)
+        let metadata = HtmlMetadata::from_html(content).metadata();
+        let content = self.content_filter.apply(content);
+        let content = self.html_to_markdown.convert(&content)?;
+        Ok(self
+            .markdown_processor
+            .process_document(&content)?
+            .with_metadata(metadata))
     }
 }
 
@@ -54,4 +428,83 @@ mod tests {
         let result = html_processor.process_file(html_file);
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_html_metadata() {
+        let html = r#"<!DOCTYPE html>
+            <html lang="en-GB">
+            <head>
+                <title> Release notes </title>
+                <meta name="Description" content="What changed in 1.0">
+                <link rel="canonical" href="https://example.com/releases/1.0">
+                <meta property="og:title" content="Release 1.0">
+                <meta property="og:type" content="article">
+                <meta property="article:published_time" content="2024-03-05T09:30:00Z">
+            </head>
+            <body><h1>Release 1.0</h1><p>Faster and smaller.</p></body>
+            </html>"#;
+        let document = HtmlProcessor::new(128, 0).unwrap().process_document(html).unwrap();
+
+        let metadata = &document.metadata;
+        assert_eq!(metadata["title"], "Release notes");
+        assert_eq!(metadata["description"], "What changed in 1.0");
+        assert_eq!(metadata["canonical_url"], "https://example.com/releases/1.0");
+        assert_eq!(metadata["language"], "en-GB");
+        assert_eq!(metadata["og:title"], "Release 1.0");
+        assert_eq!(metadata["og:type"], "article");
+        assert_eq!(metadata["published"], "2024-03-05T09:30:00Z");
+
+        let metadata = HtmlMetadata::from_html("<p>No head</p>");
+        assert_eq!(metadata, HtmlMetadata::default());
+    }
+
+    const PAGE: &str = r#"<html><body>
+        <nav><a href="/">Home</a> <a href="/docs">Docs</a> <a href="/blog">Blog</a></nav>
+        <div class="cookie-banner">We use cookies to improve your experience, accept them all.</div>
+        <div class="layout">
+            <div class="sidebar"><ul><li><a href="/a">Related post with a long title one</a></li>
+            <li><a href="/b">Related post with a long title two</a></li></ul></div>
+            <div class="article-body">
+                <h1>Fast embeddings</h1>
+                <p>Embedding models turn text into vectors, which makes search, clustering and recommendations possible.</p>
+                <p>Batching the chunks, caching the tokenizer, and running the model on a GPU make it fast.</p>
+                <div class="ad">Buy our premium plan, now with 20% off, for a limited time only!</div>
+            </div>
+        </div>
+        <footer><p>Copyright 2024, Example Inc. All rights reserved, worldwide.</p></footer>
+    </body></html>"#;
+
+    #[test]
+    fn test_main_content() {
+        let processor = HtmlProcessor::new(1000, 0)
+            .unwrap()
+            .with_content_extraction(&ContentExtraction::default().with_main_content(true))
+            .unwrap();
+        let text = processor.process_document(PAGE).unwrap().chunks.join("\n");
+
+        assert!(text.contains("Fast embeddings"));
+        assert!(text.contains("Batching the chunks"));
+        for boilerplate in ["Docs", "cookies", "Related post", "premium plan", "Copyright"] {
+            assert!(!text.contains(boilerplate), "{} was kept", boilerplate);
+        }
+    }
+
+    #[test]
+    fn test_keep_and_drop_selectors() {
+        let extraction = ContentExtraction::default()
+            .with_keep_selectors(&[".article-body", "h1"])
+            .with_drop_selectors(&[".ad"]);
+        let processor = HtmlProcessor::new(1000, 0)
+            .unwrap()
+            .with_content_extraction(&extraction)
+            .unwrap();
+        let text = processor.process_document(PAGE).unwrap().chunks.join("\n");
+        assert!(text.contains("Embedding models turn text into vectors"));
+        assert_eq!(text.matches("Fast embeddings").count(), 1);
+        assert!(!text.contains("premium plan"));
+        assert!(!text.contains("cookies"));
+
+        let extraction = ContentExtraction::default().with_keep_selectors(&["div["]);
+        assert!(HtmlProcessor::new(1000, 0).unwrap().with_content_extraction(&extraction).is_err());
+    }
 }
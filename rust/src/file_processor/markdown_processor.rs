@@ -1,14 +1,42 @@
-use text_splitter::{Characters, ChunkConfig, ChunkConfigError, MarkdownSplitter};
+use std::sync::Arc;
+use text_splitter::{Characters, ChunkConfig, ChunkConfigError, ChunkSizer, MarkdownSplitter};
+use tokenizers::Tokenizer;
 use crate::file_processor::processor::{Document, DocumentProcessor};
 
+/// The unit `chunk_size` and `overlap` are measured in.
+#[derive(Clone, Default)]
+pub enum ChunkSizing {
+    #[default]
+    Characters,
+    /// Tokens of the tokenizer, which should not truncate, so that long chunks are measured fully.
+    Tokens(Arc<Tokenizer>),
+}
+
+impl ChunkSizer for ChunkSizing {
+    fn size(&self, chunk: &str) -> usize {
+        match self {
+            ChunkSizing::Characters => Characters.size(chunk),
+            ChunkSizing::Tokens(tokenizer) => tokenizer
+                .encode(chunk, false)
+                .map_or_else(|_| Characters.size(chunk), |encoding| encoding.len()),
+        }
+    }
+}
+
 /// A struct that provides functionality to process Markdown files.
 pub struct MarkdownProcessor {
-    splitter: MarkdownSplitter<Characters>
+    splitter: MarkdownSplitter<ChunkSizing>
 }
 
 impl MarkdownProcessor {
     pub fn new(chunk_size: usize, overlap: usize) -> Result<MarkdownProcessor, ChunkConfigError> {
+        Self::with_sizing(chunk_size, overlap, ChunkSizing::Characters)
+    }
+
+    /// Like [MarkdownProcessor::new], with `chunk_size` and `overlap` measured in `sizing` units.
+    pub fn with_sizing(chunk_size: usize, overlap: usize, sizing: ChunkSizing) -> Result<MarkdownProcessor, ChunkConfigError> {
         let splitter_config = ChunkConfig::new(chunk_size)
+            .with_sizer(sizing)
             .with_overlap(overlap)?;
         let splitter = MarkdownSplitter::new(splitter_config);
         Ok(MarkdownProcessor {
@@ -20,11 +48,97 @@ impl MarkdownProcessor {
 impl DocumentProcessor for MarkdownProcessor {
 
     fn process_document(&self, content: &str) -> anyhow::Result<Document> {
-        let chunks = self.splitter.chunks(content).into_iter()
-            .map(|x| x.to_string())
-            .collect();
-        Ok(Document {
-            chunks
-        })
+        let mut document = Document::from_offsets(content, self.splitter.chunk_indices(content));
+        let headings = heading_paths(content);
+        for provenance in document.provenance.iter_mut() {
+            let start = provenance.bytes.as_ref().map_or(0, |bytes| bytes.start);
+            let current = headings.partition_point(|(offset, _)| *offset <= start);
+            if current > 0 {
+                provenance.heading_path = headings[current - 1].1.clone();
+            }
+        }
+        Ok(document)
+    }
+}
+
+/// Returns the byte offset of every ATX heading (`# Title`) outside of code blocks, together with
+/// the path of headings it is nested in, itself included.
+fn heading_paths(content: &str) -> Vec<(usize, Vec<String>)> {
+    let mut headings = Vec::new();
+    let mut path: Vec<(usize, String)> = Vec::new();
+    let mut in_code_block = false;
+    let mut offset = 0;
+
+    for line in content.split_inclusive('\n') {
+        let line_offset = offset;
+        offset += line.len();
+
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_code_block = !in_code_block;
+            continue;
+        }
+        if in_code_block {
+            continue;
+        }
+
+        let level = trimmed.chars().take_while(|&c| c == '#').count();
+        let title = &trimmed[level..];
+        if !(1..=6).contains(&level) || !(title.starts_with(' ') || title.trim().is_empty()) {
+            continue;
+        }
+        let title = title.trim().trim_end_matches('#').trim().to_string();
+
+        path.retain(|(parent_level, _)| *parent_level < level);
+        path.push((level, title));
+        headings.push((
+            line_offset,
+            path.iter().map(|(_, title)| title.clone()).collect(),
+        ));
+    }
+    headings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use tokenizers::models::wordlevel::WordLevel;
+    use tokenizers::pre_tokenizers::whitespace::Whitespace;
+
+    #[test]
+    fn test_token_sizing() {
+        let model = WordLevel::builder()
+            .vocab(HashMap::from([("[UNK]".to_string(), 0)]))
+            .unk_token("[UNK]".to_string())
+            .build()
+            .unwrap();
+        let mut tokenizer = Tokenizer::new(model);
+        tokenizer.with_pre_tokenizer(Some(Whitespace));
+
+        let processor = MarkdownProcessor::with_sizing(3, 0, ChunkSizing::Tokens(Arc::new(tokenizer))).unwrap();
+        let document = processor.process_document("one two three four five six seven").unwrap();
+
+        assert!(document.chunks.len() >= 3);
+        assert!(document.chunks.iter().all(|chunk| chunk.split_whitespace().count() <= 3));
+    }
+
+    #[test]
+    fn test_heading_path() {
+        let content = "# Guide\n\nIntro text.\n\n## Install\n\n```sh\n# not a heading\n```\n\nRun the installer.\n\n# Reference\n\nAll options.";
+        let processor = MarkdownProcessor::new(30, 0).unwrap();
+        let document = processor.process_document(content).unwrap();
+
+        let install = document
+            .chunks
+            .iter()
+            .position(|chunk| chunk.contains("Run the installer"))
+            .unwrap();
+        assert_eq!(document.provenance[install].heading_path, vec!["Guide", "Install"]);
+        let reference = document.chunks.len() - 1;
+        assert_eq!(document.provenance[reference].heading_path, vec!["Reference"]);
+
+        let bytes = document.provenance[install].bytes.clone().unwrap();
+        assert_eq!(&content[bytes], document.chunks[install]);
     }
 }
@@ -4,6 +4,12 @@ pub mod processor;
 /// This module contains the file processor for different file types.
 pub mod pdf_processor;
 
+/// This module contains the reconstruction of the tables of PDF pages as markdown tables.
+pub mod pdf_table;
+
+/// This module contains the reading of the info dictionary and the outline of PDFs.
+pub mod pdf_metadata;
+
 /// This module contains the file processor for markdown files.
 pub mod markdown_processor;
 
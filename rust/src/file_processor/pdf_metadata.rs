@@ -0,0 +1,452 @@
+//! Reading the info dictionary and the outline, or bookmarks, of a PDF.
+
+use std::collections::{HashMap, HashSet};
+
+use pdf_extract::{Dictionary, Document, Object, ObjectId};
+
+/// What the info dictionary of a PDF says about it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PdfMetadata {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub subject: Option<String>,
+    pub keywords: Option<String>,
+    /// The `CreationDate`, as an ISO 8601 date when it can be read as one.
+    pub created: Option<String>,
+    /// The `ModDate`, as an ISO 8601 date when it can be read as one.
+    pub modified: Option<String>,
+    pub page_count: usize,
+}
+
+impl PdfMetadata {
+    /// Reads the info dictionary of a PDF. Missing or empty entries are left out.
+    pub fn from_document(document: &Document) -> Self {
+        let info = document
+            .trailer
+            .get(b"Info")
+            .and_then(|info| document.dereference(info))
+            .and_then(|(_, info)| info.as_dict())
+            .ok();
+        let entry = |key: &[u8]| {
+            let value = info?.get(key).and_then(|value| document.dereference(value)).ok()?.1;
+            let text = decode_text_string(value.as_str().ok()?);
+            let text = text.trim();
+            (!text.is_empty()).then(|| text.to_string())
+        };
+        let date = |key: &[u8]| entry(key).map(|date| parse_date(&date).unwrap_or(date));
+
+        Self {
+            title: entry(b"Title"),
+            author: entry(b"Author"),
+            subject: entry(b"Subject"),
+            keywords: entry(b"Keywords"),
+            created: date(b"CreationDate"),
+            modified: date(b"ModDate"),
+            page_count: document.get_pages().len(),
+        }
+    }
+
+    /// The metadata as `EmbedData` metadata. Keys start with `pdf_`, so that the `created` and
+    /// `modified` times of the file do not overwrite the dates of the PDF.
+    pub fn metadata(&self) -> HashMap<String, String> {
+        let fields = [
+            ("pdf_title", &self.title),
+            ("pdf_author", &self.author),
+            ("pdf_subject", &self.subject),
+            ("pdf_keywords", &self.keywords),
+            ("pdf_created", &self.created),
+            ("pdf_modified", &self.modified),
+        ];
+        let mut metadata: HashMap<String, String> = fields
+            .into_iter()
+            .filter_map(|(key, value)| Some((key.to_string(), value.clone()?)))
+            .collect();
+        metadata.insert("pdf_page_count".to_string(), self.page_count.to_string());
+        metadata
+    }
+}
+
+/// An entry of the outline of a PDF, also known as a bookmark.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutlineEntry {
+    /// The titles of the entry and of the entries it is nested in, outermost first.
+    pub path: Vec<String>,
+    /// The page the entry points to, numbered from 1.
+    pub page: usize,
+}
+
+/// The outline of a PDF, with the entries that point to a page sorted by page.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PdfOutline {
+    pub entries: Vec<OutlineEntry>,
+}
+
+impl PdfOutline {
+    /// Reads the outline of a PDF. Entries whose destination cannot be resolved to a page are
+    /// left out, but the entries nested in them are not.
+    pub fn from_document(document: &Document) -> Self {
+        let page_numbers: HashMap<ObjectId, usize> = document
+            .get_pages()
+            .into_iter()
+            .map(|(number, id)| (id, number as usize))
+            .collect();
+        let first = document
+            .catalog()
+            .and_then(|catalog| catalog.get(b"Outlines"))
+            .and_then(|outlines| document.dereference(outlines))
+            .and_then(|(_, outlines)| outlines.as_dict())
+            .and_then(|outlines| outlines.get(b"First"))
+            .ok();
+
+        let mut outline = OutlineReader {
+            document,
+            page_numbers,
+            visited: HashSet::new(),
+            entries: Vec::new(),
+        };
+        if let Some(first) = first {
+            outline.read(first, &[]);
+        }
+        let mut entries = outline.entries;
+        entries.sort_by_key(|entry| entry.page);
+        Self { entries }
+    }
+
+    /// The path of the entry the text at byte `offset` of a page is in, given the `text` of the
+    /// page. Entries only point to a page, so where an entry starts on its page is found by
+    /// looking for its title in the text; when it is not found, the entry starts where the entry
+    /// before it on the page does.
+    pub fn section(&self, page: usize, text: &str, offset: usize) -> Option<&[String]> {
+        let mut section = None;
+        let mut position = 0;
+        for entry in self.entries.iter().take_while(|entry| entry.page <= page) {
+            if entry.page == page {
+                let title = entry.path.last().map_or("", |title| title.as_str());
+                if let Some(start) = text.get(position..).and_then(|rest| rest.find(title)) {
+                    if position + start > offset {
+                        break;
+                    }
+                    position += start + title.len();
+                }
+            }
+            section = Some(entry.path.as_slice());
+        }
+        section
+    }
+}
+
+struct OutlineReader<'a> {
+    document: &'a Document,
+    page_numbers: HashMap<ObjectId, usize>,
+    /// The entries read so far, so that a malformed outline with a cycle is read only once.
+    visited: HashSet<ObjectId>,
+    entries: Vec<OutlineEntry>,
+}
+
+impl<'a> OutlineReader<'a> {
+    /// Reads the entry `item` and the entries after it, and the entries nested in them.
+    fn read(&mut self, item: &'a Object, parent: &[String]) {
+        let mut item = Some(item);
+        while let Some(reference) = item {
+            let Ok(id) = reference.as_reference() else {
+                return;
+            };
+            if !self.visited.insert(id) {
+                return;
+            }
+            let Ok(dictionary) = self.document.get_dictionary(id) else {
+                return;
+            };
+
+            let title = dictionary
+                .get(b"Title")
+                .and_then(|title| self.document.dereference(title))
+                .and_then(|(_, title)| title.as_str())
+                .map(|title| decode_text_string(title).trim().to_string())
+                .unwrap_or_default();
+            let mut path = parent.to_vec();
+            path.push(title);
+            if let Some(page) = self.page(dictionary) {
+                self.entries.push(OutlineEntry {
+                    path: path.clone(),
+                    page,
+                });
+            }
+            if let Ok(first) = dictionary.get(b"First") {
+                self.read(first, &path);
+            }
+            item = dictionary.get(b"Next").ok();
+        }
+    }
+
+    /// The page the destination of an entry points to, from its `Dest` or its `GoTo` action.
+    fn page(&self, entry: &'a Dictionary) -> Option<usize> {
+        let destination = match entry.get(b"Dest") {
+            Ok(destination) => destination,
+            Err(_) => {
+                let action = self.dereference(entry.get(b"A").ok()?)?.as_dict().ok()?;
+                if action.get(b"S").and_then(Object::as_name).ok()? != b"GoTo" {
+                    return None;
+                }
+                action.get(b"D").ok()?
+            }
+        };
+        let mut destination = self.dereference(destination)?;
+        if let Ok(name) = destination.as_name().or_else(|_| destination.as_str()) {
+            destination = self.named_destination(name)?;
+        }
+        // A named destination may be a dictionary with the destination under `D`.
+        if let Ok(dictionary) = destination.as_dict() {
+            destination = self.dereference(dictionary.get(b"D").ok()?)?;
+        }
+        let page = destination.as_array().ok()?.first()?.as_reference().ok()?;
+        self.page_numbers.get(&page).copied()
+    }
+
+    /// Looks a named destination up in the `Dests` of the catalog, or in its `Names` tree.
+    fn named_destination(&self, name: &[u8]) -> Option<&'a Object> {
+        let catalog = self.document.catalog().ok()?;
+        if let Some(dests) = catalog.get(b"Dests").ok().and_then(|dests| self.dereference(dests)) {
+            if let Some(destination) = dests.as_dict().ok().and_then(|dests| dests.get(name).ok()) {
+                return self.dereference(destination);
+            }
+        }
+        let names = self.dereference(catalog.get(b"Names").ok()?)?.as_dict().ok()?;
+        let tree = self.dereference(names.get(b"Dests").ok()?)?;
+        self.find_in_name_tree(tree, name, 0)
+    }
+
+    fn find_in_name_tree(&self, node: &'a Object, name: &[u8], depth: usize) -> Option<&'a Object> {
+        // Name trees are shallow, a deeper one is malformed.
+        if depth > 32 {
+            return None;
+        }
+        let node = node.as_dict().ok()?;
+        if let Ok(names) = node.get(b"Names").and_then(Object::as_array) {
+            let found = names
+                .chunks(2)
+                .find(|pair| pair[0].as_str().is_ok_and(|key| key == name))
+                .and_then(|pair| pair.get(1));
+            if let Some(destination) = found {
+                return self.dereference(destination);
+            }
+        }
+        let kids = node.get(b"Kids").and_then(Object::as_array).ok()?;
+        kids.iter()
+            .filter_map(|kid| self.dereference(kid))
+            .find_map(|kid| self.find_in_name_tree(kid, name, depth + 1))
+    }
+
+    fn dereference(&self, object: &'a Object) -> Option<&'a Object> {
+        self.document.dereference(object).ok().map(|(_, object)| object)
+    }
+}
+
+/// Decodes a PDF text string, which is UTF-16 or UTF-8 with a byte order mark, or else in
+/// PDFDocEncoding, read here as Latin-1 which it mostly agrees with.
+fn decode_text_string(bytes: &[u8]) -> String {
+    if let Some(utf16) = bytes.strip_prefix(&[0xfe, 0xff]) {
+        let units: Vec<u16> = utf16
+            .chunks_exact(2)
+            .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+            .collect();
+        String::from_utf16_lossy(&units)
+    } else if let Some(utf8) = bytes.strip_prefix(&[0xef, 0xbb, 0xbf]) {
+        String::from_utf8_lossy(utf8).to_string()
+    } else {
+        bytes.iter().map(|&byte| byte as char).collect()
+    }
+}
+
+/// Converts a PDF date, such as `D:20240131120000+01'00'`, to ISO 8601, such as
+/// `2024-01-31T12:00:00+01:00`. Every part after the year is optional.
+fn parse_date(date: &str) -> Option<String> {
+    let date = date.strip_prefix("D:").unwrap_or(date);
+    let digits = date.find(|c: char| !c.is_ascii_digit()).unwrap_or(date.len());
+    let (digits, offset) = date.split_at(digits);
+    if digits.len() < 4 || digits.len() > 14 || digits.len() % 2 != 0 {
+        return None;
+    }
+    let part = |range: std::ops::Range<usize>, default: &'static str| digits.get(range).unwrap_or(default);
+    let mut iso = format!(
+        "{}-{}-{}T{}:{}:{}",
+        &digits[0..4],
+        part(4..6, "01"),
+        part(6..8, "01"),
+        part(8..10, "00"),
+        part(10..12, "00"),
+        part(12..14, "00"),
+    );
+
+    let offset: String = offset.chars().filter(|c| *c != '\'').collect();
+    match offset.as_str() {
+        "" => {}
+        "Z" | "Z0000" => iso.push('Z'),
+        _ => {
+            let time = offset.strip_prefix(['+', '-'])?;
+            if !matches!(time.len(), 2 | 4) || !time.bytes().all(|b| b.is_ascii_digit()) {
+                return None;
+            }
+            iso.push_str(&format!("{}{}:{}", &offset[..1], &time[0..2], time.get(2..4).unwrap_or("00")));
+        }
+    }
+    Some(iso)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    use crate::embeddings::embed::{Embedder, EmbeddingResult, TextEmbedder};
+    use crate::embeddings::local::bert::BertEmbed;
+    use pdf_extract::content::{Content, Operation};
+    use pdf_extract::{dictionary, Stream};
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_parse_date() {
+        assert_eq!(parse_date("D:20240131120005+01'00'"), Some("2024-01-31T12:00:05+01:00".to_string()));
+        assert_eq!(parse_date("D:20240131120005Z"), Some("2024-01-31T12:00:05Z".to_string()));
+        assert_eq!(parse_date("D:2024"), Some("2024-01-01T00:00:00".to_string()));
+        assert_eq!(parse_date("last tuesday"), None);
+    }
+
+    #[test]
+    fn test_decode_text_string() {
+        assert_eq!(decode_text_string(b"Caf\xe9"), "Café");
+        assert_eq!(decode_text_string(&[0xfe, 0xff, 0x00, 0x43, 0x00, 0xe9]), "Cé");
+    }
+
+    #[test]
+    fn test_section() {
+        let entry = |path: &[&str], page| OutlineEntry {
+            path: path.iter().map(|title| title.to_string()).collect(),
+            page,
+        };
+        let outline = PdfOutline {
+            entries: vec![
+                entry(&["Introduction"], 1),
+                entry(&["Methods"], 2),
+                entry(&["Methods", "Data"], 2),
+                entry(&["Results"], 4),
+            ],
+        };
+        let text = "the end of the introduction.\nMethods\nWe did things.\nData\nWe used data.";
+
+        assert_eq!(outline.section(1, "", 0), Some(&["Introduction".to_string()][..]));
+        assert_eq!(outline.section(2, text, 0), Some(&["Introduction".to_string()][..]));
+        assert_eq!(outline.section(2, text, text.find("We did").unwrap()), Some(&["Methods".to_string()][..]));
+        assert_eq!(
+            outline.section(2, text, text.find("We used").unwrap()),
+            Some(&["Methods".to_string(), "Data".to_string()][..])
+        );
+        assert_eq!(outline.section(3, "", 0), Some(&["Methods".to_string(), "Data".to_string()][..]));
+        assert_eq!(PdfOutline::default().section(1, text, 0), None);
+    }
+
+    #[test]
+    fn test_pdf_metadata() {
+        let document = Document::load("../test_files/test.pdf").unwrap();
+        let metadata = PdfMetadata::from_document(&document);
+        assert_eq!(metadata.page_count, document.get_pages().len());
+        assert_eq!(metadata.metadata()["pdf_page_count"], metadata.page_count.to_string());
+    }
+
+    struct ConstantEmbedder;
+
+    impl BertEmbed for ConstantEmbedder {
+        fn embed(
+            &self,
+            text_batch: &[&str],
+            _batch_size: Option<usize>,
+            _late_chunking: Option<bool>,
+        ) -> anyhow::Result<Vec<EmbeddingResult>> {
+            Ok(text_batch.iter().map(|_| EmbeddingResult::DenseVector(vec![1.0])).collect())
+        }
+    }
+
+    /// Writes a one page PDF with an info dictionary and an outline entry pointing to the page.
+    fn write_pdf(path: &Path) {
+        let mut document = Document::with_version("1.5");
+        let pages_id = document.new_object_id();
+        let font_id = document.add_object(dictionary! {
+            "Type" => "Font",
+            "Subtype" => "Type1",
+            "BaseFont" => "Courier",
+        });
+        let content = Content {
+            operations: vec![
+                Operation::new("BT", vec![]),
+                Operation::new("Tf", vec!["F1".into(), 12.into()]),
+                Operation::new("Td", vec![72.into(), 720.into()]),
+                Operation::new("Tj", vec![Object::string_literal("Attention is all you need.")]),
+                Operation::new("ET", vec![]),
+            ],
+        };
+        let content_id = document.add_object(Stream::new(dictionary! {}, content.encode().unwrap()));
+        let page_id = document.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => pages_id,
+            "Contents" => content_id,
+        });
+        document.objects.insert(
+            pages_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Kids" => vec![page_id.into()],
+                "Count" => 1,
+                "Resources" => dictionary! { "Font" => dictionary! { "F1" => font_id } },
+                "MediaBox" => vec![0.into(), 0.into(), 612.into(), 792.into()],
+            }),
+        );
+
+        let outlines_id = document.new_object_id();
+        let entry_id = document.add_object(dictionary! {
+            "Title" => Object::string_literal("Introduction"),
+            "Parent" => outlines_id,
+            "Dest" => vec![page_id.into(), "Fit".into()],
+        });
+        document.objects.insert(
+            outlines_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Outlines",
+                "First" => entry_id,
+                "Last" => entry_id,
+                "Count" => 1,
+            }),
+        );
+        let catalog_id = document.add_object(dictionary! {
+            "Type" => "Catalog",
+            "Pages" => pages_id,
+            "Outlines" => outlines_id,
+        });
+        let info_id = document.add_object(dictionary! {
+            "Title" => Object::string_literal("Attention"),
+            "Author" => Object::string_literal("Vaswani"),
+            "CreationDate" => Object::string_literal("D:20170612000000Z"),
+        });
+        document.trailer.set("Root", catalog_id);
+        document.trailer.set("Info", info_id);
+        document.save(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_embed_file_keeps_pdf_metadata() {
+        let temp_dir = TempDir::new("pdf_metadata").unwrap();
+        let path = temp_dir.path().join("attention.pdf");
+        write_pdf(&path);
+
+        let embedder = Embedder::Text(TextEmbedder::Bert(Box::new(ConstantEmbedder)));
+        let embeddings = crate::embed_file(&path, &embedder, None, None).await.unwrap().unwrap();
+
+        let metadata = embeddings[0].metadata.as_ref().unwrap();
+        assert_eq!(metadata["pdf_title"], "Attention");
+        assert_eq!(metadata["pdf_author"], "Vaswani");
+        assert_eq!(metadata["pdf_created"], "2017-06-12T00:00:00Z");
+        assert_eq!(metadata["pdf_page_count"], "1");
+        assert_eq!(metadata["section_path"], "Introduction");
+        // The times of the file are kept next to the dates of the PDF.
+        assert!(metadata.contains_key("modified"));
+    }
+}
@@ -1,16 +1,22 @@
+use std::ops::{Range, RangeInclusive};
 use std::path::Path;
 use crate::tesseract::input::{Args, Image};
 use anyhow::Error;
 use image::DynamicImage;
 use pdf2image::{Pages, RenderOptionsBuilder, PDF};
 use text_splitter::ChunkConfigError;
-use crate::file_processor::markdown_processor::MarkdownProcessor;
-use crate::file_processor::processor::{Document, DocumentProcessor, FileProcessor};
+use crate::file_processor::markdown_processor::{ChunkSizing, MarkdownProcessor};
+use crate::file_processor::pdf_metadata::{PdfMetadata, PdfOutline};
+use crate::file_processor::pdf_table::{extract_page_with_tables, split_table};
+use crate::file_processor::processor::{BytesProcessor, ChunkProvenance, Document, DocumentProcessor, FileProcessor};
 
 /// A struct for processing PDF files.
 pub struct PdfProcessor {
     markdown_processor: MarkdownProcessor,
     ocr_config: OcrConfig,
+    pdf_config: PdfConfig,
+    chunk_size: usize,
+    sizing: ChunkSizing,
 }
 
 pub struct OcrConfig {
@@ -18,44 +24,444 @@ pub struct OcrConfig {
     pub tesseract_path: Option<String>
 }
 
+/// How the pages of a PDF are extracted and split into chunks.
+///
+/// # Example
+///
+/// ```rust
+/// use embed_anything::file_processor::pdf_processor::PdfConfig;
+/// // Only the first ten pages.
+/// let config = PdfConfig::default().with_page_range(1..=10);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PdfConfig {
+    /// Lets chunks run over page breaks, e.g. to keep a paragraph cut by a page break in one
+    /// chunk. The text of the whole PDF is then held in memory. Defaults to false, which splits
+    /// every page on its own.
+    pub cross_page_chunks: bool,
+    /// Only extracts the pages in this range, numbered from 1. Defaults to None, or every page.
+    pub page_range: Option<RangeInclusive<usize>>,
+    /// Finds the tables of every page from the position of its text, and rebuilds them as
+    /// markdown tables. A table is kept in one chunk, or split by rows with the header repeated
+    /// when it is larger than a chunk, and its chunks get a `chunk_type` of `table`. Not used with
+    /// OCR. Defaults to false.
+    pub extract_tables: bool,
+    /// Only uses OCR on the pages without a text layer, such as scanned pages, which have little
+    /// or no text or text garbled by a broken font encoding, and extracts the text of the other
+    /// pages. Needs the same tools as OCR, which are only used when a page needs it. Ignored when
+    /// OCR is used on every page. Defaults to false.
+    pub auto_ocr: bool,
+}
+
+impl PdfConfig {
+    pub fn with_cross_page_chunks(mut self, cross_page_chunks: bool) -> Self {
+        self.cross_page_chunks = cross_page_chunks;
+        self
+    }
+
+    pub fn with_page_range(mut self, range: RangeInclusive<usize>) -> Self {
+        self.page_range = Some(range);
+        self
+    }
+
+    pub fn with_extract_tables(mut self, extract_tables: bool) -> Self {
+        self.extract_tables = extract_tables;
+        self
+    }
+
+    pub fn with_auto_ocr(mut self, auto_ocr: bool) -> Self {
+        self.auto_ocr = auto_ocr;
+        self
+    }
+
+    /// The pages to extract from a PDF with `page_count` pages.
+    fn pages(&self, page_count: usize) -> RangeInclusive<usize> {
+        let (first, last) = self
+            .page_range
+            .as_ref()
+            .map_or((1, page_count), |range| (*range.start(), *range.end()));
+        first.max(1)..=last.min(page_count)
+    }
+}
+
 impl PdfProcessor {
     pub fn new(chunk_size: usize, overlap: usize, ocr_config: OcrConfig) -> Result<PdfProcessor, ChunkConfigError> {
-        let markdown_processor = MarkdownProcessor::new(chunk_size, overlap)?;
+        Self::with_sizing(chunk_size, overlap, ChunkSizing::Characters, ocr_config)
+    }
+
+    pub fn with_sizing(chunk_size: usize, overlap: usize, sizing: ChunkSizing, ocr_config: OcrConfig) -> Result<PdfProcessor, ChunkConfigError> {
+        let markdown_processor = MarkdownProcessor::with_sizing(chunk_size, overlap, sizing.clone())?;
         Ok(PdfProcessor {
             markdown_processor,
             ocr_config,
+            pdf_config: PdfConfig::default(),
+            chunk_size,
+            sizing,
         })
     }
+
+    pub fn with_pdf_config(mut self, pdf_config: PdfConfig) -> Self {
+        self.pdf_config = pdf_config;
+        self
+    }
+
+    /// Extracts and splits the pages of the PDF at `path` one at a time, as they are iterated.
+    /// Every document holds the chunks of one page, with offsets relative to the text of the page,
+    /// and the metadata of the PDF. Pages whose text cannot be extracted are skipped. OCR is only
+    /// used with [PdfConfig::auto_ocr].
+    pub fn process_file_by_page(
+        &self,
+        path: impl AsRef<Path>,
+    ) -> anyhow::Result<impl Iterator<Item = Document> + '_> {
+        let path = path.as_ref().to_path_buf();
+        let pages = PdfPages::from_file(&path)?;
+        let metadata = pages.metadata().metadata();
+        let outline = pages.outline();
+        let pages = pages.with_config(&self.pdf_config);
+        let load_pdf = move || -> anyhow::Result<PDF> { Ok(PDF::from_file(&path)?) };
+        let mut pdf = None;
+        Ok(pages.filter_map(move |(number, page)| {
+            let page = self.ocr_if_needed(number, page, &mut pdf, &load_pdf);
+            let document = page.and_then(|page| self.process_page(number, &page, &outline));
+            skip_failed_page(number, document).map(|document| document.with_metadata(metadata.clone()))
+        }))
+    }
+}
+
+impl PdfProcessor {
+    fn process_page(&self, number: usize, page: &PdfPage, outline: &PdfOutline) -> anyhow::Result<Document> {
+        let mut document = self.split(&page.text, &page.tables)?;
+        for provenance in document.provenance.iter_mut() {
+            provenance.pages = Some(number..=number);
+            let offset = provenance.bytes.as_ref().map_or(0, |bytes| bytes.start);
+            provenance.section_path = section_path(outline, number, &page.text, offset);
+        }
+        Ok(document)
+    }
+
+    /// Splits the pages, whose text is joined with `separator`, into one document. Chunk offsets
+    /// are relative to the joined text.
+    fn process_pages(
+        &self,
+        pages: impl IntoIterator<Item = (usize, anyhow::Result<PdfPage>)>,
+        separator: &str,
+        outline: &PdfOutline,
+    ) -> anyhow::Result<Document> {
+        if self.pdf_config.cross_page_chunks {
+            // Pages that fail are kept empty, so that every page keeps its place in the text.
+            let (numbers, pages): (Vec<usize>, Vec<PdfPage>) = pages
+                .into_iter()
+                .map(|(number, page)| (number, skip_failed_page(number, page).unwrap_or_default()))
+                .unzip();
+            let texts: Vec<String> = pages.iter().map(|page| page.text.clone()).collect();
+            let (content, mut page_starts) = join_pages(&texts, separator);
+            let tables: Vec<Range<usize>> = pages
+                .iter()
+                .zip(&page_starts)
+                .flat_map(|(page, start)| {
+                    page.tables.iter().map(move |table| table.start + start..table.end + start)
+                })
+                .collect();
+            // Pages before the first one start at 0, so that chunks get the right page numbers.
+            let first = numbers.first().copied().unwrap_or(1);
+            page_starts.splice(0..0, std::iter::repeat_n(0, first - 1));
+            let mut document = self.split(&content, &tables)?.with_pages(&page_starts);
+            for provenance in document.provenance.iter_mut() {
+                let (Some(bytes), Some(pages_of_chunk)) = (&provenance.bytes, &provenance.pages) else {
+                    continue;
+                };
+                let number = *pages_of_chunk.start();
+                let text = &pages[number - first].text;
+                let offset = bytes.start - page_starts[number - 1];
+                provenance.section_path = section_path(outline, number, text, offset);
+            }
+            return Ok(document);
+        }
+
+        let pages = pages
+            .into_iter()
+            .filter_map(|(number, page)| Some((number, skip_failed_page(number, page)?)));
+        let mut document = Document::from_chunks(Vec::new());
+        let (mut text, mut chars) = (String::new(), 0);
+        for (number, page) in pages {
+            if !page.text.is_empty() && !text.is_empty() {
+                text.push_str(separator);
+                chars += separator.chars().count();
+            }
+            document.append(self.process_page(number, &page, outline)?, text.len(), chars);
+            text.push_str(&page.text);
+            chars += page.text.chars().count();
+        }
+        document.text = text;
+        Ok(document)
+    }
+
+    /// Splits `content` into chunks, except for the `tables` in it, which are kept whole or split
+    /// by rows.
+    fn split(&self, content: &str, tables: &[Range<usize>]) -> anyhow::Result<Document> {
+        let mut document = Document::from_chunks(Vec::new());
+        let (mut position, mut chars) = (0, 0);
+        for table in tables {
+            let text = &content[position..table.start];
+            document.append(self.markdown_processor.process_document(text)?, position, chars);
+            chars += text.chars().count();
+
+            let table_text = &content[table.clone()];
+            for (chunk, rows) in split_table(table_text, self.chunk_size, &self.sizing) {
+                let start = chars + table_text[..rows.start].chars().count();
+                let end = start + table_text[rows.clone()].chars().count();
+                document.provenance.push(ChunkProvenance {
+                    index: document.chunks.len(),
+                    bytes: Some(table.start + rows.start..table.start + rows.end),
+                    chars: Some(start..end),
+                    table: true,
+                    ..Default::default()
+                });
+                document.chunks.push(chunk);
+            }
+            chars += table_text.chars().count();
+            position = table.end;
+        }
+        document.append(self.markdown_processor.process_document(&content[position..])?, position, chars);
+        document.text = content.to_string();
+        Ok(document)
+    }
+
+    /// Splits the text of the pages, with OCR on the pages that need it if
+    /// [PdfConfig::auto_ocr] is set. `load_pdf` loads the PDF for OCR.
+    fn text_pages(
+        &self,
+        pages: PdfPages,
+        load_pdf: impl Fn() -> anyhow::Result<PDF>,
+    ) -> anyhow::Result<Document> {
+        let metadata = pages.metadata().metadata();
+        let outline = pages.outline();
+        let mut pdf = None;
+        let pages = pages
+            .with_config(&self.pdf_config)
+            .map(|(number, page)| (number, self.ocr_if_needed(number, page, &mut pdf, &load_pdf)));
+        Ok(self.process_pages(pages, "", &outline)?.with_metadata(metadata))
+    }
+
+    /// Returns the OCR text of the page instead of its text if [PdfConfig::auto_ocr] is set and the
+    /// page has no text layer, or its text could not be extracted. The PDF is loaded into `pdf`
+    /// with `load_pdf` the first time a page needs OCR. When OCR fails, the page is returned as it
+    /// was extracted.
+    fn ocr_if_needed(
+        &self,
+        number: usize,
+        page: anyhow::Result<PdfPage>,
+        pdf: &mut Option<PDF>,
+        load_pdf: &impl Fn() -> anyhow::Result<PDF>,
+    ) -> anyhow::Result<PdfPage> {
+        if !self.pdf_config.auto_ocr || page.as_ref().is_ok_and(|page| has_text_layer(&page.text)) {
+            return page;
+        }
+        if pdf.is_none() {
+            match load_pdf() {
+                Ok(loaded) => *pdf = Some(loaded),
+                Err(e) => {
+                    eprintln!("Could not load the PDF to OCR page {}: {:?}", number, e);
+                    return page;
+                }
+            }
+        }
+        let tesseract_path = self.ocr_config.tesseract_path.as_deref();
+        match extract_pages_with_ocr(pdf.as_ref().unwrap(), number..=number, tesseract_path) {
+            Ok(texts) => {
+                // Ends the page with a line break like the text of the other pages.
+                let text = texts.concat() + "\n";
+                Ok(PdfPage { text, tables: Vec::new() })
+            }
+            Err(e) => {
+                eprintln!("Could not OCR page {} of the PDF: {:?}", number, e);
+                page
+            }
+        }
+    }
+
+    /// With OCR, `pages` is only read for the metadata and outline of the PDF, which are left out
+    /// when the PDF could not be read without OCR.
+    fn ocr_pages(&self, pdf: PDF, pages: Option<PdfPages>) -> anyhow::Result<Document> {
+        let metadata = pages.as_ref().map(|pages| pages.metadata().metadata()).unwrap_or_default();
+        let outline = pages.map(|pages| pages.outline()).unwrap_or_default();
+        let tesseract_path = self.ocr_config.tesseract_path.as_deref();
+        let pages = self.pdf_config.pages(pdf.page_count() as usize);
+        let texts = extract_pages_with_ocr(&pdf, pages.clone(), tesseract_path)?;
+        let texts = texts.into_iter().map(|text| Ok(PdfPage { text, tables: Vec::new() }));
+        Ok(self.process_pages(pages.zip(texts), "\n", &outline)?.with_metadata(metadata))
+    }
 }
 
 impl FileProcessor for PdfProcessor {
     fn process_file(&self, path: impl AsRef<Path>) -> anyhow::Result<Document> {
-        let content = if self.ocr_config.use_ocr {
-            let tesseract_path = self.ocr_config.tesseract_path.as_deref();
-            extract_text_with_ocr(&path, tesseract_path)?
+        let pages = PdfPages::from_file(&path);
+        if self.ocr_config.use_ocr {
+            self.ocr_pages(PDF::from_file(path)?, pages.ok())
         } else {
-            pdf_extract::extract_text(path).map_err(|e| anyhow::anyhow!(e))?
+            self.text_pages(pages?, || Ok(PDF::from_file(&path)?))
+        }
+    }
+}
+
+impl BytesProcessor for PdfProcessor {
+    fn process_bytes(&self, bytes: &[u8]) -> anyhow::Result<Document> {
+        let pages = PdfPages::from_bytes(bytes);
+        if self.ocr_config.use_ocr {
+            self.ocr_pages(PDF::from_bytes(bytes.to_vec())?, pages.ok())
+        } else {
+            self.text_pages(pages?, || Ok(PDF::from_bytes(bytes.to_vec())?))
+        }
+    }
+}
+
+fn section_path(outline: &PdfOutline, page: usize, text: &str, offset: usize) -> Vec<String> {
+    outline.section(page, text, offset).map(<[String]>::to_vec).unwrap_or_default()
+}
+
+/// Pages with fewer characters than this, whitespace aside, are taken to have no text layer.
+const MIN_TEXT_LAYER_CHARS: usize = 16;
+
+/// Pages with a larger share of characters that are not text, which is what the text of a font
+/// with a broken encoding is extracted as, are taken to have no text layer.
+const MAX_GARBLED_SHARE: f64 = 0.3;
+
+/// Whether the extracted `text` of a page looks like the page has a text layer.
+fn has_text_layer(text: &str) -> bool {
+    let chars: Vec<char> = text.chars().filter(|c| !c.is_whitespace()).collect();
+    if chars.len() < MIN_TEXT_LAYER_CHARS {
+        return false;
+    }
+    let garbled = chars
+        .iter()
+        .filter(|&&c| {
+            c == char::REPLACEMENT_CHARACTER || c.is_control() || ('\u{e000}'..='\u{f8ff}').contains(&c)
+        })
+        .count();
+    (garbled as f64) <= MAX_GARBLED_SHARE * chars.len() as f64
+}
+
+fn skip_failed_page<T>(number: usize, result: anyhow::Result<T>) -> Option<T> {
+    result
+        .map_err(|e| eprintln!("Skipping page {} of the PDF: {:?}", number, e))
+        .ok()
+}
+
+/// The text of a page of a PDF.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PdfPage {
+    pub text: String,
+    /// The byte ranges of the markdown tables in `text`, when tables are extracted.
+    pub tables: Vec<Range<usize>>,
+}
+
+/// The text of the pages of a PDF, extracted one page at a time as the pages are iterated, so
+/// that the text of a large PDF is never held in memory as a whole. Yields the number of every
+/// page, starting at 1, with its text.
+pub struct PdfPages {
+    document: pdf_extract::Document,
+    numbers: std::vec::IntoIter<u32>,
+    extract_tables: bool,
+}
+
+impl PdfPages {
+    pub fn from_file(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        Self::new(pdf_extract::Document::load(path)?)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
+        Self::new(pdf_extract::Document::load_mem(bytes)?)
+    }
+
+    fn new(mut document: pdf_extract::Document) -> anyhow::Result<Self> {
+        // Like pdf_extract, PDFs encrypted without a password are decrypted.
+        if document.is_encrypted() {
+            document.decrypt("")?;
+        }
+        let numbers: Vec<u32> = document.get_pages().into_keys().collect();
+        Ok(Self {
+            document,
+            numbers: numbers.into_iter(),
+            extract_tables: false,
+        })
+    }
+
+    pub fn page_count(&self) -> usize {
+        self.document.get_pages().len()
+    }
+
+    pub fn metadata(&self) -> PdfMetadata {
+        PdfMetadata::from_document(&self.document)
+    }
+
+    pub fn outline(&self) -> PdfOutline {
+        PdfOutline::from_document(&self.document)
+    }
+
+    /// Only yields the pages selected by `config`, with their tables if it extracts them.
+    pub fn with_config(self, config: &PdfConfig) -> Self {
+        let pages = config.pages(self.page_count());
+        let numbers: Vec<u32> = self
+            .numbers
+            .filter(|number| pages.contains(&(*number as usize)))
+            .collect();
+        Self {
+            document: self.document,
+            numbers: numbers.into_iter(),
+            extract_tables: config.extract_tables,
+        }
+    }
+}
+
+impl Iterator for PdfPages {
+    type Item = (usize, anyhow::Result<PdfPage>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let number = self.numbers.next()?;
+        let page = if self.extract_tables {
+            extract_page_with_tables(&self.document, number)
+                .map(|(text, tables)| PdfPage { text, tables })
+        } else {
+            let mut text = String::new();
+            let result = {
+                let mut output = pdf_extract::PlainTextOutput::new(&mut text);
+                pdf_extract::output_doc_page(&self.document, &mut output, number)
+            };
+            result.map(|_| PdfPage { text, tables: Vec::new() })
         };
-        
-        self.markdown_processor.process_document(&content)
+        Some((number as usize, page.map_err(|e| anyhow::anyhow!(e))))
     }
 }
 
-fn get_images_from_pdf<T: AsRef<Path>>(
-    file_path: &T,
-) -> Result<Vec<DynamicImage>, Error> {
-    let pdf = PDF::from_file(file_path)?;
-    let page_count = pdf.page_count();
+/// Joins the text of the pages, skipping empty ones, and returns the byte offset every page starts
+/// at in the joined text.
+fn join_pages(pages: &[String], separator: &str) -> (String, Vec<usize>) {
+    let mut content = String::new();
+    let mut page_starts = Vec::with_capacity(pages.len());
+    for page in pages {
+        if !page.is_empty() && !content.is_empty() {
+            content.push_str(separator);
+        }
+        page_starts.push(content.len());
+        content.push_str(page);
+    }
+    (content, page_starts)
+}
+
+fn get_images_from_pdf(pdf: &PDF, pages: RangeInclusive<usize>) -> Result<Vec<DynamicImage>, Error> {
+    if pages.is_empty() {
+        return Ok(Vec::new());
+    }
     let pages = pdf.render(
-        Pages::Range(1..=page_count),
+        Pages::Range(*pages.start() as u32..=*pages.end() as u32),
         RenderOptionsBuilder::default().build()?,
     )?;
     Ok(pages)
 }
 
 fn extract_text_from_image(image: &DynamicImage, args: &Args) -> Result<String, Error> {
-    let image = Image::from_dynamic_image(image).unwrap();
-    let text = crate::tesseract::command::image_to_string(&image, args).unwrap();
+    let image = Image::from_dynamic_image(image)?;
+    let text = crate::tesseract::command::image_to_string(&image, args)?;
     Ok(text)
 }
 
@@ -63,21 +469,30 @@ fn extract_text_with_ocr<T: AsRef<Path>>(
     file_path: &T,
     tesseract_path: Option<&str>,
 ) -> Result<String, Error> {
-    let images = get_images_from_pdf(file_path)?;
-    let texts: Result<Vec<String>, Error> = images
+    let pdf = PDF::from_file(file_path)?;
+    let pages = 1..=pdf.page_count() as usize;
+    let (text, _) = join_pages(&extract_pages_with_ocr(&pdf, pages, tesseract_path)?, "\n");
+    Ok(text)
+}
+
+/// Returns the OCR text of the `pages`, with empty lines removed.
+fn extract_pages_with_ocr(
+    pdf: &PDF,
+    pages: RangeInclusive<usize>,
+    tesseract_path: Option<&str>,
+) -> Result<Vec<String>, Error> {
+    let images = get_images_from_pdf(pdf, pages)?;
+    images
         .iter()
-        .map(|image| extract_text_from_image(image, &Args::default().with_path(tesseract_path)))
-        .collect();
-    
-    // Join the texts and clean up empty lines
-    let text = texts?.join("\n");
-    let cleaned_text = text
-        .lines()
-        .filter(|line| !line.trim().is_empty())
-        .collect::<Vec<&str>>()
-        .join("\n");
-    
-    Ok(cleaned_text)
+        .map(|image| {
+            let text = extract_text_from_image(image, &Args::default().with_path(tesseract_path))?;
+            Ok(text
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .collect::<Vec<&str>>()
+                .join("\n"))
+        })
+        .collect()
 }
 
 #[cfg(test)]
@@ -95,10 +510,120 @@ mod tests {
         File::create(pdf_file).unwrap();
 
         let pdf_file = "../test_files/test.pdf";
+        let processor = processor.with_pdf_config(PdfConfig::default().with_cross_page_chunks(true));
         let text = processor.process_file(pdf_file).unwrap();
         assert_eq!(text.chunks.len(), 4271);
     }
 
+    #[test]
+    fn test_chunks_stay_on_their_page() {
+        let processor = PdfProcessor::new(128, 0, OcrConfig { use_ocr: false, tesseract_path: None }).unwrap();
+        let document = processor.process_file("../test_files/test.pdf").unwrap();
+
+        assert!(document.provenance.iter().all(|provenance| {
+            let pages = provenance.pages.as_ref().unwrap();
+            pages.start() == pages.end()
+        }));
+        assert!(document.provenance.windows(2).all(|pair| {
+            pair[0].index + 1 == pair[1].index
+                && pair[0].bytes.as_ref().unwrap().end <= pair[1].bytes.as_ref().unwrap().start
+        }));
+
+        let by_page: Vec<Document> = processor.process_file_by_page("../test_files/test.pdf").unwrap().collect();
+        let (mut chunks, mut provenance) = (Vec::new(), Vec::new());
+        let (mut bytes, mut chars) = (0, 0);
+        for page in by_page {
+            let (page_bytes, page_chars) = (page.text.len(), page.text.chars().count());
+            let page = page.with_offsets(chunks.len(), bytes, chars);
+            chunks.extend(page.chunks);
+            provenance.extend(page.provenance);
+            bytes += page_bytes;
+            chars += page_chars;
+        }
+        assert_eq!(chunks, document.chunks);
+        assert_eq!(provenance, document.provenance);
+    }
+
+    #[test]
+    fn test_page_range() {
+        let pdf_config = PdfConfig::default().with_page_range(2..=2);
+        let processor = PdfProcessor::new(128, 0, OcrConfig { use_ocr: false, tesseract_path: None })
+            .unwrap()
+            .with_pdf_config(pdf_config.clone());
+        let document = processor.process_file("../test_files/test.pdf").unwrap();
+        assert!(!document.chunks.is_empty());
+        assert!(document.provenance.iter().all(|provenance| provenance.pages == Some(2..=2)));
+
+        let processor = processor.with_pdf_config(pdf_config.with_cross_page_chunks(true));
+        let document = processor.process_file("../test_files/test.pdf").unwrap();
+        assert!(document.provenance.iter().all(|provenance| provenance.pages == Some(2..=2)));
+    }
+
+    #[test]
+    fn test_split_with_tables() {
+        let processor = PdfProcessor::new(64, 0, OcrConfig { use_ocr: false, tesseract_path: None }).unwrap();
+        let table = "| Name | Size |\n| --- | --- |\n| café | 1 |\n| tea | 2 |";
+        let content = format!("Before the table.\n{table}\nAfter the table.");
+        let start = content.find(table).unwrap();
+        let document = processor.split(&content, &[start..start + table.len()]).unwrap();
+
+        assert_eq!(document.chunks.len(), 3);
+        let provenance = &document.provenance[1];
+        assert!(provenance.table);
+        assert_eq!(document.chunks[1], table);
+        assert_eq!(&content[provenance.bytes.clone().unwrap()], document.chunks[1]);
+        let chars: String = content.chars().skip(provenance.chars.as_ref().unwrap().start).take(document.chunks[1].chars().count()).collect();
+        assert_eq!(chars, document.chunks[1]);
+        assert!(!document.provenance[2].table);
+        assert_eq!(&content[document.provenance[2].bytes.clone().unwrap()], "After the table.");
+    }
+
+    #[test]
+    fn test_has_text_layer() {
+        assert!(has_text_layer("A page of text, long enough to be one."));
+        assert!(!has_text_layer("  \n 12 \n"));
+        assert!(!has_text_layer("\u{fffd}\u{fffd}\u{e001}\u{e002}\u{e003}\u{fffd} page 1 of 2 \u{e004}\u{fffd}\u{e005}"));
+    }
+
+    #[test]
+    fn test_auto_ocr_keeps_text_pages() {
+        let processor = PdfProcessor::new(128, 0, OcrConfig { use_ocr: false, tesseract_path: None }).unwrap();
+        let document = processor.process_file("../test_files/test.pdf").unwrap();
+        // Every page of the test PDF has a text layer, so nothing is OCRed.
+        let processor = processor.with_pdf_config(PdfConfig::default().with_auto_ocr(true));
+        assert_eq!(processor.process_file("../test_files/test.pdf").unwrap().chunks, document.chunks);
+    }
+
+    #[test]
+    fn test_document_metadata() {
+        let processor = PdfProcessor::new(128, 0, OcrConfig { use_ocr: false, tesseract_path: None }).unwrap();
+        let document = processor.process_file("../test_files/test.pdf").unwrap();
+        let page_count = PdfPages::from_file("../test_files/test.pdf").unwrap().page_count();
+        assert_eq!(document.metadata.get("pdf_page_count"), Some(&page_count.to_string()));
+
+        let first_page = processor.process_file_by_page("../test_files/test.pdf").unwrap().next().unwrap();
+        assert_eq!(first_page.metadata, document.metadata);
+    }
+
+    #[test]
+    fn test_chunk_pages() {
+        let processor = PdfProcessor::new(128, 0, OcrConfig { use_ocr: false, tesseract_path: None }).unwrap();
+        let document = processor.process_file("../test_files/test.pdf").unwrap();
+
+        let first = document.provenance.first().unwrap();
+        let last = document.provenance.last().unwrap();
+        assert_eq!(first.pages.as_ref().map(|pages| *pages.start()), Some(1));
+        assert!(last.pages.as_ref().unwrap().end() > &1);
+    }
+
+    #[test]
+    fn test_process_bytes() {
+        let processor = PdfProcessor::new(128, 0, OcrConfig { use_ocr: false, tesseract_path: None }).unwrap();
+        let bytes = std::fs::read("../test_files/test.pdf").unwrap();
+        let document = processor.process_bytes(&bytes).unwrap();
+        assert_eq!(document.chunks, processor.process_file("../test_files/test.pdf").unwrap().chunks);
+    }
+
     #[test]
     fn test_extract_text_with_ocr() {
         let pdf_file = "../test_files/test.pdf";
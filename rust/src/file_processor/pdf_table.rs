@@ -0,0 +1,449 @@
+//! Rebuilding the tables of a PDF page from the positions of its glyphs, as markdown tables.
+
+use std::cell::RefCell;
+use std::io::Write;
+use std::ops::Range;
+use std::rc::Rc;
+
+use pdf_extract::{MediaBox, OutputDev, OutputError, PlainTextOutput, Transform};
+use text_splitter::ChunkSizer;
+
+/// A character drawn on a page, in page coordinates with the origin at the top left.
+#[derive(Debug, Clone, PartialEq)]
+struct Glyph {
+    text: String,
+    x: f64,
+    y: f64,
+    /// The horizontal advance of the glyph.
+    width: f64,
+    size: f64,
+    /// The bytes the glyph was written to in the text of the page, including the whitespace
+    /// written before it.
+    span: Range<usize>,
+}
+
+/// A run of text on a line, separated from its neighbours by a gap wider than a space.
+#[derive(Debug, Clone, PartialEq)]
+struct Cell {
+    text: String,
+    x0: f64,
+    x1: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Line {
+    y: f64,
+    size: f64,
+    cells: Vec<Cell>,
+    glyphs: Vec<usize>,
+}
+
+/// The text of the page, as written by [PlainTextOutput].
+#[derive(Clone, Default)]
+struct SharedText(Rc<RefCell<Vec<u8>>>);
+
+impl Write for SharedText {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Writes the text of a page like [PlainTextOutput], and records where every glyph is.
+struct GlyphOutput<'a> {
+    plain_text: PlainTextOutput<&'a mut dyn Write>,
+    text: SharedText,
+    page_height: f64,
+    glyphs: Vec<Glyph>,
+}
+
+impl OutputDev for GlyphOutput<'_> {
+    fn begin_page(
+        &mut self,
+        page_num: u32,
+        media_box: &MediaBox,
+        art_box: Option<(f64, f64, f64, f64)>,
+    ) -> Result<(), OutputError> {
+        self.page_height = media_box.ury - media_box.lly;
+        self.plain_text.begin_page(page_num, media_box, art_box)
+    }
+
+    fn end_page(&mut self) -> Result<(), OutputError> {
+        self.plain_text.end_page()
+    }
+
+    fn output_character(
+        &mut self,
+        trm: &Transform,
+        width: f64,
+        spacing: f64,
+        font_size: f64,
+        char: &str,
+    ) -> Result<(), OutputError> {
+        let start = self.text.0.borrow().len();
+        self.plain_text.output_character(trm, width, spacing, font_size, char)?;
+        let end = self.text.0.borrow().len();
+        // The same font size and position as PlainTextOutput, with the y axis pointing down.
+        let size = ((font_size * trm.m11 + font_size * trm.m21)
+            * (font_size * trm.m12 + font_size * trm.m22))
+            .abs()
+            .sqrt();
+        self.glyphs.push(Glyph {
+            text: char.to_string(),
+            x: trm.m31,
+            y: self.page_height - trm.m32,
+            width: width * size,
+            size,
+            span: start..end,
+        });
+        Ok(())
+    }
+
+    fn begin_word(&mut self) -> Result<(), OutputError> {
+        self.plain_text.begin_word()
+    }
+
+    fn end_word(&mut self) -> Result<(), OutputError> {
+        self.plain_text.end_word()
+    }
+
+    fn end_line(&mut self) -> Result<(), OutputError> {
+        self.plain_text.end_line()
+    }
+}
+
+/// Extracts the text of a page like [pdf_extract::extract_text], with the tables on it replaced by
+/// markdown tables. Returns the text, and the byte range of every table in it.
+pub(crate) fn extract_page_with_tables(
+    document: &pdf_extract::Document,
+    page: u32,
+) -> Result<(String, Vec<Range<usize>>), OutputError> {
+    let text = SharedText::default();
+    let mut writer = text.clone();
+    let mut output = GlyphOutput {
+        plain_text: PlainTextOutput::new(&mut writer as &mut dyn Write),
+        text: text.clone(),
+        page_height: 0.0,
+        glyphs: Vec::new(),
+    };
+    pdf_extract::output_doc_page(document, &mut output, page)?;
+    let glyphs = output.glyphs;
+    let content = String::from_utf8_lossy(&text.0.borrow()).to_string();
+    Ok(replace_tables(&content, &glyphs))
+}
+
+/// Replaces the text of the tables found among `glyphs` with markdown tables. Tables whose text
+/// is interleaved with text outside of them are left as they are.
+fn replace_tables(content: &str, glyphs: &[Glyph]) -> (String, Vec<Range<usize>>) {
+    let mut tables: Vec<(Range<usize>, String)> = find_tables(glyphs)
+        .into_iter()
+        .filter_map(|(rows, table_glyphs)| {
+            let start = table_glyphs.iter().map(|&i| glyphs[i].span.start).min()?;
+            let end = table_glyphs.iter().map(|&i| glyphs[i].span.end).max()?;
+            let interleaved = glyphs.iter().enumerate().any(|(i, glyph)| {
+                !glyph.text.trim().is_empty()
+                    && glyph.span.start < end
+                    && glyph.span.end > start
+                    && !table_glyphs.contains(&i)
+            });
+            (!interleaved).then(|| (start..end, to_markdown(&rows)))
+        })
+        .collect();
+    tables.sort_by_key(|(range, _)| range.start);
+
+    let mut text = String::with_capacity(content.len());
+    let mut ranges = Vec::with_capacity(tables.len());
+    let mut position = 0;
+    for (range, markdown) in tables {
+        if range.start < position {
+            continue;
+        }
+        text.push_str(&content[position..range.start]);
+        text.push('\n');
+        ranges.push(text.len()..text.len() + markdown.len());
+        text.push_str(&markdown);
+        text.push('\n');
+        position = range.end;
+    }
+    text.push_str(&content[position..]);
+    (text, ranges)
+}
+
+/// Groups the glyphs into lines, and the glyphs of a line into cells.
+fn lines(glyphs: &[Glyph]) -> Vec<Line> {
+    let mut order: Vec<usize> = (0..glyphs.len())
+        .filter(|&i| !glyphs[i].text.trim().is_empty())
+        .collect();
+    order.sort_by(|&a, &b| glyphs[a].y.total_cmp(&glyphs[b].y));
+
+    let mut rows: Vec<Vec<usize>> = Vec::new();
+    for i in order {
+        match rows.last_mut() {
+            Some(row)
+                if (glyphs[i].y - glyphs[row[0]].y).abs()
+                    <= glyphs[row[0]].size.max(glyphs[i].size) * 0.5 =>
+            {
+                row.push(i)
+            }
+            _ => rows.push(vec![i]),
+        }
+    }
+
+    rows.into_iter()
+        .map(|mut row| {
+            row.sort_by(|&a, &b| glyphs[a].x.total_cmp(&glyphs[b].x));
+            let mut cells: Vec<Cell> = Vec::new();
+            let mut previous: Option<&Glyph> = None;
+            for glyph in row.iter().map(|&i| &glyphs[i]) {
+                let gap = previous.map(|previous| glyph.x - (previous.x + previous.width));
+                let size = glyph.size.max(previous.map_or(0.0, |previous| previous.size));
+                match cells.last_mut() {
+                    // Cells are further apart than words.
+                    Some(cell) if gap.is_some_and(|gap| gap <= size) => {
+                        if gap.is_some_and(|gap| gap > size * 0.15) {
+                            cell.text.push(' ');
+                        }
+                        cell.text.push_str(&glyph.text);
+                        cell.x1 = glyph.x + glyph.width;
+                    }
+                    _ => cells.push(Cell {
+                        text: glyph.text.clone(),
+                        x0: glyph.x,
+                        x1: glyph.x + glyph.width,
+                    }),
+                }
+                previous = Some(glyph);
+            }
+            Line {
+                y: glyphs[row[0]].y,
+                size: row.iter().map(|&i| glyphs[i].size).fold(0.0, f64::max),
+                cells,
+                glyphs: row,
+            }
+        })
+        .collect()
+}
+
+/// Finds the tables among the glyphs of a page: runs of at least three consecutive lines split
+/// into cells that line up in two or more columns. Lines of prose in a multi-column layout are
+/// told apart by the length of their cells. Returns the rows of every table, and its glyphs.
+fn find_tables(glyphs: &[Glyph]) -> Vec<(Vec<Vec<String>>, Vec<usize>)> {
+    let lines = lines(glyphs);
+    let mut tables = Vec::new();
+    let mut run: Vec<&Line> = Vec::new();
+    for line in lines.iter().chain(std::iter::once(&Line {
+        y: f64::INFINITY,
+        size: 0.0,
+        cells: Vec::new(),
+        glyphs: Vec::new(),
+    })) {
+        let continues = run
+            .last()
+            .is_none_or(|last| line.y - last.y <= last.size.max(line.size) * 3.0);
+        if line.cells.len() >= 2 && continues {
+            run.push(line);
+            continue;
+        }
+        if let Some(table) = table_from_lines(&run) {
+            tables.push(table);
+        }
+        run.clear();
+        if line.cells.len() >= 2 {
+            run.push(line);
+        }
+    }
+    tables
+}
+
+fn table_from_lines(lines: &[&Line]) -> Option<(Vec<Vec<String>>, Vec<usize>)> {
+    if lines.len() < 3 {
+        return None;
+    }
+    let mut cell_lengths: Vec<usize> = lines
+        .iter()
+        .flat_map(|line| line.cells.iter().map(|cell| cell.text.chars().count()))
+        .collect();
+    cell_lengths.sort_unstable();
+    if cell_lengths[cell_lengths.len() / 2] > 30 {
+        return None;
+    }
+
+    // The columns are the spans that the cells of all the lines cover.
+    let mut spans: Vec<(f64, f64)> = lines
+        .iter()
+        .flat_map(|line| line.cells.iter().map(|cell| (cell.x0, cell.x1)))
+        .collect();
+    spans.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let mut columns: Vec<(f64, f64)> = Vec::new();
+    for (x0, x1) in spans {
+        match columns.last_mut() {
+            Some(column) if x0 <= column.1 => column.1 = column.1.max(x1),
+            _ => columns.push((x0, x1)),
+        }
+    }
+    if columns.len() < 2 {
+        return None;
+    }
+
+    let rows = lines
+        .iter()
+        .map(|line| {
+            let mut row = vec![String::new(); columns.len()];
+            for cell in &line.cells {
+                let column = columns
+                    .iter()
+                    .position(|column| cell.x0 >= column.0 && cell.x0 <= column.1)
+                    .unwrap_or(0);
+                if !row[column].is_empty() {
+                    row[column].push(' ');
+                }
+                row[column].push_str(&cell.text);
+            }
+            row
+        })
+        .collect();
+    let glyphs = lines.iter().flat_map(|line| line.glyphs.iter().copied()).collect();
+    Some((rows, glyphs))
+}
+
+/// Writes the rows as a markdown table, with the first row as the header.
+fn to_markdown(rows: &[Vec<String>]) -> String {
+    let row = |cells: &[String]| {
+        let cells: Vec<String> = cells.iter().map(|cell| cell.replace('|', "\\|")).collect();
+        format!("| {} |", cells.join(" | "))
+    };
+    let mut lines = vec![row(&rows[0]), row(&vec!["---".to_string(); rows[0].len()])];
+    for cells in &rows[1..] {
+        lines.push(row(cells));
+    }
+    lines.join("\n")
+}
+
+/// Splits a markdown table into chunks of whole rows no larger than `chunk_size`, with the header
+/// repeated at the top of every chunk. Returns every chunk with the byte range of its rows in
+/// `table`. A row larger than `chunk_size` gets a chunk of its own.
+pub(crate) fn split_table(
+    table: &str,
+    chunk_size: usize,
+    sizer: &impl ChunkSizer,
+) -> Vec<(String, Range<usize>)> {
+    if sizer.size(table) <= chunk_size {
+        return vec![(table.to_string(), 0..table.len())];
+    }
+    let mut offset = 0;
+    let lines: Vec<(usize, &str)> = table
+        .split('\n')
+        .map(|line| {
+            let start = offset;
+            offset += line.len() + 1;
+            (start, line)
+        })
+        .collect();
+    if lines.len() < 3 {
+        return vec![(table.to_string(), 0..table.len())];
+    }
+    let header = format!("{}\n{}", lines[0].1, lines[1].1);
+
+    let mut chunks = Vec::new();
+    let mut rows: Vec<(usize, &str)> = Vec::new();
+    for &line in &lines[2..] {
+        let candidate: Vec<&str> = rows.iter().chain([&line]).map(|(_, row)| *row).collect();
+        if !rows.is_empty() && sizer.size(&format!("{}\n{}", header, candidate.join("\n"))) > chunk_size {
+            chunks.push(table_chunk(&header, &rows, chunks.is_empty()));
+            rows.clear();
+        }
+        rows.push(line);
+    }
+    if !rows.is_empty() {
+        chunks.push(table_chunk(&header, &rows, chunks.is_empty()));
+    }
+    chunks
+}
+
+fn table_chunk(header: &str, rows: &[(usize, &str)], first: bool) -> (String, Range<usize>) {
+    let text: Vec<&str> = rows.iter().map(|(_, row)| *row).collect();
+    let (last_start, last_row) = rows[rows.len() - 1];
+    // The first chunk starts with the header of the table itself.
+    let start = if first { 0 } else { rows[0].0 };
+    (format!("{}\n{}", header, text.join("\n")), start..last_start + last_row.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use text_splitter::Characters;
+
+    fn glyphs(lines: &[(f64, &[(f64, &str)])]) -> (String, Vec<Glyph>) {
+        let mut content = String::new();
+        let mut glyphs = Vec::new();
+        for (y, cells) in lines {
+            for (x, text) in cells.iter() {
+                for (i, char) in text.chars().enumerate() {
+                    let start = content.len();
+                    content.push(char);
+                    glyphs.push(Glyph {
+                        text: char.to_string(),
+                        x: x + i as f64 * 5.0,
+                        y: *y,
+                        width: 5.0,
+                        size: 10.0,
+                        span: start..content.len(),
+                    });
+                }
+                content.push(' ');
+            }
+            content.push('\n');
+        }
+        (content, glyphs)
+    }
+
+    #[test]
+    fn test_replace_tables() {
+        let (content, glyphs) = glyphs(&[
+            (10.0, &[(0.0, "Quarterly results")]),
+            (40.0, &[(0.0, "Quarter"), (100.0, "Revenue"), (200.0, "Profit")]),
+            (52.0, &[(0.0, "Q1"), (100.0, "1,200"), (200.0, "300")]),
+            (64.0, &[(0.0, "Q2"), (100.0, "1,450"), (200.0, "410")]),
+            (120.0, &[(0.0, "Revenue grew in every quarter.")]),
+        ]);
+        let (text, tables) = replace_tables(&content, &glyphs);
+
+        assert_eq!(tables.len(), 1);
+        assert_eq!(
+            &text[tables[0].clone()],
+            "| Quarter | Revenue | Profit |\n| --- | --- | --- |\n| Q1 | 1,200 | 300 |\n| Q2 | 1,450 | 410 |"
+        );
+        assert!(text.starts_with("Quarterly results"));
+        assert!(text.ends_with("Revenue grew in every quarter. \n"));
+    }
+
+    #[test]
+    fn test_prose_is_not_a_table() {
+        let left = "The quick brown fox jumps over the";
+        let right = "lazy dog, again and again and again";
+        let (content, glyphs) = glyphs(&[
+            (10.0, &[(0.0, left), (300.0, right)]),
+            (22.0, &[(0.0, left), (300.0, right)]),
+            (34.0, &[(0.0, left), (300.0, right)]),
+        ]);
+        let (text, tables) = replace_tables(&content, &glyphs);
+        assert!(tables.is_empty());
+        assert_eq!(text, content);
+    }
+
+    #[test]
+    fn test_split_table() {
+        let table = "| a | b |\n| --- | --- |\n| 1 | 2 |\n| 3 | 4 |\n| 5 | 6 |";
+        let chunks = split_table(table, 40, &Characters);
+
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(chunks[1].0, "| a | b |\n| --- | --- |\n| 3 | 4 |");
+        assert_eq!(&table[chunks[1].1.clone()], "| 3 | 4 |");
+        assert!(table[chunks[0].1.clone()].starts_with("| a | b |"));
+        assert_eq!(split_table(table, 1000, &Characters), vec![(table.to_string(), 0..table.len())]);
+    }
+}
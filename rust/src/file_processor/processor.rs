@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+use std::ops::{Range, RangeInclusive};
 use std::path::Path;
 
 pub trait DocumentProcessor {
@@ -10,6 +12,11 @@ pub trait FileProcessor {
     fn process_file(&self, path: impl AsRef<Path>) -> anyhow::Result<Document>;
 }
 
+/// Processes a document that is already in memory, e.g. an upload, instead of reading a file.
+pub trait BytesProcessor {
+    fn process_bytes(&self, bytes: &[u8]) -> anyhow::Result<Document>;
+}
+
 pub trait UrlProcessor {
     fn process_url(&self, url: &str) -> anyhow::Result<Document>;
 }
@@ -22,6 +29,12 @@ impl <T: DocumentProcessor> FileProcessor for T {
     }
 }
 
+impl <T: DocumentProcessor> BytesProcessor for T {
+    fn process_bytes(&self, bytes: &[u8]) -> anyhow::Result<Document> {
+        self.process_document(&String::from_utf8_lossy(bytes))
+    }
+}
+
 impl <T: DocumentProcessor> UrlProcessor for T {
     fn process_url(&self, url: &str) -> anyhow::Result<Document> {
         let content = reqwest::blocking::get(url)?.text()?;
@@ -30,5 +43,251 @@ impl <T: DocumentProcessor> UrlProcessor for T {
 }
 
 pub struct Document {
-    pub chunks: Vec<String>
+    /// The text the chunks were split from, which their offsets refer to.
+    pub text: String,
+    pub chunks: Vec<String>,
+    /// Where each chunk comes from, in the same order as `chunks`.
+    pub provenance: Vec<ChunkProvenance>,
+    /// Metadata of the whole document, e.g. the title of a web page. It is added to the metadata
+    /// of every chunk.
+    pub metadata: HashMap<String, String>,
+}
+
+impl Document {
+    /// A document whose chunks cannot be traced back to the source text, e.g. after semantic
+    /// re-chunking. Only the chunk index is known.
+    pub fn from_chunks(chunks: Vec<String>) -> Self {
+        let provenance = (0..chunks.len())
+            .map(|index| ChunkProvenance {
+                index,
+                ..Default::default()
+            })
+            .collect();
+        Self {
+            text: chunks.join("\n"),
+            chunks,
+            provenance,
+            metadata: HashMap::new(),
+        }
+    }
+
+    /// Builds a document from chunks found at the given byte offsets of `content`.
+    pub fn from_offsets<'a>(content: &str, chunks: impl IntoIterator<Item = (usize, &'a str)>) -> Self {
+        let mut char_offsets = CharOffsets::new(content);
+        let (chunks, provenance) = chunks
+            .into_iter()
+            .enumerate()
+            .map(|(index, (offset, chunk))| {
+                let bytes = offset..offset + chunk.len();
+                let chars = char_offsets.get(bytes.start)..char_offsets.get(bytes.end);
+                let provenance = ChunkProvenance {
+                    index,
+                    bytes: Some(bytes),
+                    chars: Some(chars),
+                    ..Default::default()
+                };
+                (chunk.to_string(), provenance)
+            })
+            .unzip();
+        Self {
+            text: content.to_string(),
+            chunks,
+            provenance,
+            metadata: HashMap::new(),
+        }
+    }
+
+    /// Builds a document from other chunks of the text of this one, found at the given byte
+    /// offsets, e.g. semantic chunks. Every chunk gets the pages, heading path and section path of
+    /// the chunks of this document it overlaps.
+    pub fn rechunk<'a>(&self, chunks: impl IntoIterator<Item = (usize, &'a str)>) -> Self {
+        let mut document = Document::from_offsets(&self.text, chunks);
+        for provenance in document.provenance.iter_mut() {
+            let Some(bytes) = provenance.bytes.clone() else {
+                continue;
+            };
+            let overlapping: Vec<&ChunkProvenance> = self
+                .provenance
+                .iter()
+                .filter(|old| {
+                    old.bytes
+                        .as_ref()
+                        .is_some_and(|old| old.start < bytes.end && bytes.start < old.end)
+                })
+                .collect();
+            if let Some(first) = overlapping.first() {
+                provenance.heading_path = first.heading_path.clone();
+                provenance.section_path = first.section_path.clone();
+            }
+            provenance.pages = overlapping
+                .iter()
+                .filter_map(|old| old.pages.clone())
+                .reduce(|a, b| *a.start().min(b.start())..=*a.end().max(b.end()));
+        }
+        document.metadata = self.metadata.clone();
+        document
+    }
+
+    /// Appends the chunks of `other`, which was split from text that starts at `byte_offset` and
+    /// `char_offset` of the text of this document. The text of this document is not changed.
+    pub fn append(&mut self, other: Document, byte_offset: usize, char_offset: usize) {
+        let other = other.with_offsets(self.chunks.len(), byte_offset, char_offset);
+        self.chunks.extend(other.chunks);
+        self.provenance.extend(other.provenance);
+        self.metadata.extend(other.metadata);
+    }
+
+    /// Shifts the chunks of this document, split from text that starts at `byte_offset` and
+    /// `char_offset` of a longer text, whose chunks before them number `index`. The text of this
+    /// document is not changed.
+    pub fn with_offsets(mut self, index: usize, byte_offset: usize, char_offset: usize) -> Self {
+        let shift = |range: Option<Range<usize>>, offset: usize| {
+            range.map(|range| range.start + offset..range.end + offset)
+        };
+        for provenance in self.provenance.iter_mut() {
+            provenance.index += index;
+            provenance.bytes = shift(provenance.bytes.take(), byte_offset);
+            provenance.chars = shift(provenance.chars.take(), char_offset);
+        }
+        self
+    }
+
+    pub fn with_metadata(mut self, metadata: HashMap<String, String>) -> Self {
+        self.metadata.extend(metadata);
+        self
+    }
+
+    /// Records the pages of every chunk, given the byte offset each page starts at in the source
+    /// text. Pages are numbered from 1.
+    pub fn with_pages(mut self, page_starts: &[usize]) -> Self {
+        for provenance in self.provenance.iter_mut() {
+            if let Some(bytes) = &provenance.bytes {
+                let first = page_starts.partition_point(|&start| start <= bytes.start).max(1);
+                let last = page_starts.partition_point(|&start| start < bytes.end).max(first);
+                provenance.pages = Some(first..=last);
+            }
+        }
+        self
+    }
+}
+
+/// Where a chunk of a [Document] comes from. Offsets are relative to the text the document was
+/// split from, e.g. the markdown a DOCX file was converted to.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ChunkProvenance {
+    /// The position of the chunk in the document, starting at 0.
+    pub index: usize,
+    pub bytes: Option<Range<usize>>,
+    pub chars: Option<Range<usize>>,
+    /// The first and last page the chunk is on, for paged documents such as PDFs.
+    pub pages: Option<RangeInclusive<usize>>,
+    /// The titles of the markdown headings the chunk is under, outermost first.
+    pub heading_path: Vec<String>,
+    /// Whether the chunk is a table, or rows of a table, e.g. a table of a PDF as markdown.
+    pub table: bool,
+    /// The titles of the outline entries of a PDF the chunk is under, outermost first.
+    pub section_path: Vec<String>,
+}
+
+impl ChunkProvenance {
+    /// The provenance as `EmbedData` metadata. Only what is known is included.
+    pub fn metadata(&self) -> HashMap<String, String> {
+        let mut metadata = HashMap::new();
+        metadata.insert("chunk_index".to_string(), self.index.to_string());
+        if let Some(bytes) = &self.bytes {
+            metadata.insert("byte_start".to_string(), bytes.start.to_string());
+            metadata.insert("byte_end".to_string(), bytes.end.to_string());
+        }
+        if let Some(chars) = &self.chars {
+            metadata.insert("char_start".to_string(), chars.start.to_string());
+            metadata.insert("char_end".to_string(), chars.end.to_string());
+        }
+        if let Some(pages) = &self.pages {
+            metadata.insert("page_start".to_string(), pages.start().to_string());
+            metadata.insert("page_end".to_string(), pages.end().to_string());
+        }
+        if !self.heading_path.is_empty() {
+            metadata.insert("heading_path".to_string(), self.heading_path.join(" > "));
+        }
+        if !self.section_path.is_empty() {
+            metadata.insert("section_path".to_string(), self.section_path.join(" > "));
+        }
+        if self.table {
+            metadata.insert("chunk_type".to_string(), "table".to_string());
+        }
+        metadata
+    }
+}
+
+/// Converts byte offsets to char offsets. Lookups are fastest when offsets only move forward.
+struct CharOffsets<'a> {
+    content: &'a str,
+    byte: usize,
+    char: usize,
+}
+
+impl<'a> CharOffsets<'a> {
+    fn new(content: &'a str) -> Self {
+        Self {
+            content,
+            byte: 0,
+            char: 0,
+        }
+    }
+
+    fn get(&mut self, byte: usize) -> usize {
+        if byte < self.byte {
+            self.byte = 0;
+            self.char = 0;
+        }
+        self.char += self.content[self.byte..byte].chars().count();
+        self.byte = byte;
+        self.char
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_offsets_and_pages() {
+        let content = "Ünïcode page one. Page two.";
+        let first = content.find("page one").unwrap();
+        let second = content.find("Page two").unwrap();
+        let document = Document::from_offsets(
+            content,
+            [(first, "page one."), (second, "Page two.")],
+        )
+        .with_pages(&[0, second]);
+
+        let provenance = &document.provenance[1];
+        assert_eq!(provenance.index, 1);
+        assert_eq!(provenance.bytes, Some(second..content.len()));
+        assert_eq!(provenance.chars, Some(18..27));
+        assert_eq!(provenance.pages, Some(2..=2));
+        assert_eq!(document.provenance[0].pages, Some(1..=1));
+        assert_eq!(document.provenance[0].chars, Some(8..17));
+    }
+
+    #[test]
+    fn test_rechunk() {
+        let content = "Page one. Page two. Page three.";
+        let second = content.find("Page two").unwrap();
+        let third = content.find("Page three").unwrap();
+        let mut document = Document::from_offsets(
+            content,
+            [(0, "Page one."), (second, "Page two."), (third, "Page three.")],
+        )
+        .with_pages(&[0, second, third]);
+        document.provenance[0].heading_path = vec!["One".to_string()];
+
+        let rechunked = document.rechunk([(0, &content[..third - 1]), (third, &content[third..])]);
+        assert_eq!(rechunked.chunks, vec!["Page one. Page two.", "Page three."]);
+        assert_eq!(rechunked.text, content);
+        assert_eq!(rechunked.provenance[0].pages, Some(1..=2));
+        assert_eq!(rechunked.provenance[0].heading_path, vec!["One".to_string()]);
+        assert_eq!(rechunked.provenance[1].pages, Some(3..=3));
+        assert_eq!(rechunked.provenance[1].bytes, Some(third..content.len()));
+    }
 }
@@ -1,5 +1,5 @@
 use text_splitter::ChunkConfigError;
-use crate::file_processor::markdown_processor::MarkdownProcessor;
+use crate::file_processor::markdown_processor::{ChunkSizing, MarkdownProcessor};
 use crate::file_processor::processor::{Document, DocumentProcessor};
 
 /// A struct for processing PDF files.
@@ -9,7 +9,11 @@ pub struct TxtProcessor {
 
 impl TxtProcessor {
     pub fn new(chunk_size: usize, overlap: usize) -> Result<TxtProcessor, ChunkConfigError> {
-        let markdown_processor = MarkdownProcessor::new(chunk_size, overlap)?;
+        Self::with_sizing(chunk_size, overlap, ChunkSizing::Characters)
+    }
+
+    pub fn with_sizing(chunk_size: usize, overlap: usize, sizing: ChunkSizing) -> Result<TxtProcessor, ChunkConfigError> {
+        let markdown_processor = MarkdownProcessor::with_sizing(chunk_size, overlap, sizing)?;
         Ok(TxtProcessor {
             markdown_processor,
         })
@@ -18,6 +22,11 @@ impl TxtProcessor {
 
 impl DocumentProcessor for TxtProcessor {
     fn process_document(&self, content: &str) -> anyhow::Result<Document> {
-        self.markdown_processor.process_document(content)
+        let mut document = self.markdown_processor.process_document(content)?;
+        // Plain text has no headings, even if a line happens to start with '#'.
+        for provenance in document.provenance.iter_mut() {
+            provenance.heading_path.clear();
+        }
+        Ok(document)
     }
 }
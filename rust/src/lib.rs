@@ -61,41 +61,71 @@
 //! let embedding = embed_file("path/to/file.txt", &embedder, None, None);
 //! ```
 
+pub mod adapter;
+pub mod archive;
 pub mod chunkers;
 pub mod config;
+pub mod crawler;
+pub mod dedup;
 pub mod embeddings;
+pub mod feeds;
 pub mod file_loader;
 pub mod file_processor;
+pub mod manifest;
 pub mod models;
+pub mod progress;
 #[cfg(feature = "ort")]
 pub mod reranker;
+pub mod report;
 pub mod tesseract;
 pub mod text_loader;
-
-use std::{collections::HashMap, fs, path::PathBuf, rc::Rc, sync::Arc};
+pub mod watcher;
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    io::Read,
+    path::PathBuf,
+    rc::Rc,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
 use std::fmt::Display;
+use adapter::{Adapter, AdapterSink};
+use archive::ArchiveLimits;
 use anyhow::{Error, Result};
-use config::{ImageEmbedConfig, TextEmbedConfig};
+use chunkers::statistical::StatisticalChunker;
+use config::{ImageEmbedConfig, SplittingStrategy, TextEmbedConfig};
+use crawler::{CrawlConfig, Crawler};
+use dedup::Deduplicator;
+use feeds::{FeedConfig, FeedSource};
 use embeddings::{
     embed::{EmbedData, EmbedImage, Embedder, TextEmbedder, VisionEmbedder},
-    get_text_metadata,
+    get_document_metadata, get_text_metadata,
 };
-use file_loader::FileParser;
+use file_loader::{FileFilter, FileParser};
 use file_processor::audio::audio_processor::AudioDecoderModel;
-use itertools::Itertools;
+use manifest::{FileState, IndexManifest, IndexUpdate};
 use rayon::prelude::*;
+use progress::Progress;
+use report::{FileError, FileReport, FileStatus, FileTracker};
 use text_loader::TextLoader;
+use futures::Stream;
 use tokio::sync::mpsc; // Add this at the top of your file
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use watcher::DirectoryWatcher;
 
 #[cfg(feature = "audio")]
 use embeddings::embed_audio;
 use crate::file_processor::docx_processor::DocxProcessor;
-use crate::file_processor::html_processor::HtmlProcessor;
-use crate::file_processor::markdown_processor::MarkdownProcessor;
-use crate::file_processor::pdf_processor::{OcrConfig, PdfProcessor};
-use crate::file_processor::processor::{Document, FileProcessor, UrlProcessor};
+use crate::file_processor::html_processor::{ContentExtraction, HtmlProcessor};
+use crate::file_processor::markdown_processor::{ChunkSizing, MarkdownProcessor};
+use crate::file_processor::pdf_processor::{OcrConfig, PdfConfig, PdfProcessor};
+use crate::file_processor::processor::{BytesProcessor, Document, DocumentProcessor, FileProcessor, UrlProcessor};
 use crate::file_processor::txt_processor::TxtProcessor;
 
+#[derive(Debug)]
 pub enum Dtype {
     F16,
     INT8,
@@ -185,14 +215,99 @@ pub async fn embed_file<T: AsRef<std::path::Path>>(
     file_name: T,
     embedder: &Embedder,
     config: Option<&TextEmbedConfig>,
-    adapter: Option<Box<dyn FnOnce(Vec<EmbedData>) + Send + Sync>>,
+    adapter: Option<Arc<dyn Adapter>>,
 ) -> Result<Option<Vec<EmbedData>>> {
     match embedder {
         Embedder::Text(embedder) => emb_text(file_name, embedder, config, adapter).await,
-        Embedder::Vision(embedder) => Ok(Some(vec![emb_image(file_name, embedder)?])),
+        Embedder::Vision(embedder) => {
+            send_to_adapter(vec![emb_image(file_name, embedder)?], adapter).await
+        }
     }
 }
 
+/// Embeds a document that is already in memory, e.g. an upload, without writing it to disk.
+///
+/// # Arguments
+///
+/// * `bytes` - The content of the document.
+/// * `content_type` - What kind of document `bytes` holds: a MIME type such as "application/pdf",
+///   a file name such as "report.pdf", or an extension such as "pdf".
+/// * `embedder` - The text embedding model to use.
+/// * `config` - An optional `TextEmbedConfig`. The overrides for the file type apply.
+/// * `metadata` - The metadata stored with every chunk, e.g. the id of the upload. The position
+///   of the chunk in the document is added to it.
+/// * `adapter` - An optional `Adapter` object to send the embeddings to a vector database.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use std::collections::HashMap;
+/// use embed_anything::embed_bytes;
+/// use embed_anything::embeddings::embed::Embedder;
+///
+/// async fn embed_upload(upload: Vec<u8>) {
+///     let embedder = Embedder::from_pretrained_hf("jina", "jinaai/jina-embeddings-v2-small-en", None, None, None).unwrap();
+///     let metadata = HashMap::from([("upload_id".to_string(), "42".to_string())]);
+///     let embeddings = embed_bytes(&upload, "application/pdf", &embedder, None, Some(metadata), None)
+///         .await
+///         .unwrap();
+/// }
+/// ```
+pub async fn embed_bytes(
+    bytes: &[u8],
+    content_type: &str,
+    embedder: &Embedder,
+    config: Option<&TextEmbedConfig>,
+    metadata: Option<HashMap<String, String>>,
+    adapter: Option<Arc<dyn Adapter>>,
+) -> Result<Option<Vec<EmbedData>>> {
+    let Embedder::Text(embedding_model) = embedder else {
+        return Err(anyhow::anyhow!("Embedding bytes is only supported for text models"));
+    };
+    let extension = extension_from_content_type(content_type)
+        .ok_or_else(|| FileLoadingError::UnsupportedFileType(content_type.to_string()))?;
+    let config = config.cloned().unwrap_or_default().for_file_type(&extension);
+    let chunking = Chunking::new(&config, Some(embedding_model))?;
+    let ocr_config = OcrConfig {
+        use_ocr: config.use_ocr.unwrap_or(false),
+        tesseract_path: config.tesseract_path.clone(),
+    };
+    let content_extraction = config.content_extraction.clone().unwrap_or_default();
+    let pdf_config = config.pdf_config.clone().unwrap_or_default();
+    let document = extract_bytes(
+        bytes,
+        &extension,
+        &chunking,
+        ocr_config,
+        &content_extraction,
+        &pdf_config,
+    )?;
+    let semantic_chunker = semantic_chunker(&config)?;
+    emb_document(
+        document,
+        embedding_model,
+        &config,
+        semantic_chunker.as_ref(),
+        metadata,
+        adapter,
+    )
+    .await
+}
+
+/// Embeds a document read to the end from `reader`, like [embed_bytes].
+pub async fn embed_reader(
+    mut reader: impl Read,
+    content_type: &str,
+    embedder: &Embedder,
+    config: Option<&TextEmbedConfig>,
+    metadata: Option<HashMap<String, String>>,
+    adapter: Option<Arc<dyn Adapter>>,
+) -> Result<Option<Vec<EmbedData>>> {
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+    embed_bytes(&bytes, content_type, embedder, config, metadata, adapter).await
+}
+
 /// Embeddings of a webpage using the specified embedding model.
 ///
 /// # Arguments
@@ -202,7 +317,9 @@ pub async fn embed_file<T: AsRef<std::path::Path>>(
 ///
 /// # Returns
 ///
-/// The embeddings of the webpage.
+/// The embeddings of the webpage. Every embedding gets the `url` of the page as metadata, along
+/// with its title, description, canonical URL, language, OpenGraph tags and published date when
+/// the page has them. See [HtmlMetadata](file_processor::html_processor::HtmlMetadata).
 ///
 /// # Errors
 ///
@@ -222,18 +339,25 @@ pub async fn embed_webpage(
     url: String,
     embedder: &Embedder,
     config: Option<&TextEmbedConfig>,
-    // Callback function
-    adapter: Option<Box<dyn FnOnce(Vec<EmbedData>) + Send + Sync>>,
+    adapter: Option<Arc<dyn Adapter>>,
 ) -> Result<Option<Vec<EmbedData>>> {
-    let binding = TextEmbedConfig::default();
-    let config = config.unwrap_or(&binding);
-    let chunk_size = config.chunk_size.unwrap_or(1000);
-    let overlap_ratio = config.overlap_ratio.unwrap_or(0.0);
-    let website_processor = HtmlProcessor::new(chunk_size, (chunk_size as f32 * overlap_ratio) as usize);
+    let config = config.cloned().unwrap_or_default().for_file_type("html");
+    let config = &config;
+    let chunking = Chunking::new(config, text_embedder(embedder))?;
+    let website_processor =
+        HtmlProcessor::with_sizing(chunking.chunk_size, chunking.overlap, chunking.sizing)?
+            .with_content_extraction(&config.content_extraction.clone().unwrap_or_default())?;
 
     let batch_size = config.batch_size;
     let late_chunking = config.late_chunking;
-    let document = website_processor?.process_url(&url)?;
+    let document = website_processor.process_url(&url)?;
+    let semantic_chunker = semantic_chunker(config)?;
+    let document = split_document(
+        document,
+        semantic_chunker.as_ref(),
+        batch_size.unwrap_or(32),
+    )
+    .await?;
     let chunks: Vec<&str> = document.chunks.iter().map(String::as_ref).collect();
 
     let encodings = embedder
@@ -242,14 +366,179 @@ pub async fn embed_webpage(
 
     let mut metadata = HashMap::new();
     metadata.insert("url".into(), url);
-    let embeddings = get_text_metadata(&Rc::new(encodings), &chunks, &Some(metadata))?;
+    let embeddings = get_document_metadata(&Rc::new(encodings), &document, &Some(metadata))?;
 
     // Send embeddings to vector database
-    if let Some(adapter) = adapter {
-        adapter(embeddings);
-        Ok(None)
-    } else {
-        Ok(Some(embeddings))
+    send_to_adapter(embeddings, adapter).await
+}
+
+/// Crawls the pages reachable from `seeds` and embeds them one by one with the [HtmlProcessor].
+///
+/// # Arguments
+///
+/// * `seeds` - The URLs the crawl starts from.
+/// * `embedder` - The text embedding model to use.
+/// * `crawl_config` - Which pages to visit, and how fast. See [CrawlConfig].
+/// * `config` - An optional `TextEmbedConfig`. The overrides for "html" files apply.
+/// * `adapter` - An optional `Adapter` object. The embeddings of every page are sent to it as
+///   soon as the page is embedded.
+///
+/// Every embedding gets the `url` of its page, and the `depth` it was found at, as metadata.
+/// Pages that fail to load or parse are skipped.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use embed_anything::embed_website;
+/// use embed_anything::crawler::CrawlConfig;
+/// use embed_anything::embeddings::embed::Embedder;
+///
+/// async fn embed_docs() {
+///     let embedder = Embedder::from_pretrained_hf("jina", "jinaai/jina-embeddings-v2-small-en", None, None, None).unwrap();
+///     let crawl_config = CrawlConfig::default().with_max_depth(3).with_allowed_path_prefixes(&["/docs"]);
+///     let embeddings = embed_website(vec!["https://example.com/docs/".into()], &embedder, Some(&crawl_config), None, None)
+///         .await
+///         .unwrap();
+/// }
+/// ```
+pub async fn embed_website(
+    seeds: Vec<String>,
+    embedder: &Embedder,
+    crawl_config: Option<&CrawlConfig>,
+    config: Option<&TextEmbedConfig>,
+    adapter: Option<Arc<dyn Adapter>>,
+) -> Result<Option<Vec<EmbedData>>> {
+    let mut pages = HtmlPageEmbedder::new(embedder, config, adapter)?;
+    let mut crawler = Crawler::new(&seeds, crawl_config.cloned().unwrap_or_default())?;
+
+    while let Some(page) = crawler.next_page().await {
+        let metadata = HashMap::from([
+            ("url".to_string(), page.url.clone()),
+            ("depth".to_string(), page.depth.to_string()),
+        ]);
+        pages.embed(&page.url, &page.html, metadata).await?;
+    }
+    Ok(pages.finish())
+}
+
+/// Embeds the pages listed in a sitemap, a sitemap index, or an RSS or Atom feed with the
+/// [HtmlProcessor]. Sitemap indexes are followed.
+///
+/// # Arguments
+///
+/// * `url` - The URL of the sitemap or feed.
+/// * `embedder` - The text embedding model to use.
+/// * `feed_config` - Which pages to fetch, and how fast. See [FeedConfig].
+/// * `config` - An optional `TextEmbedConfig`. The overrides for "html" files apply.
+/// * `adapter` - An optional `Adapter` object. The embeddings of every page are sent to it as
+///   soon as the page is embedded.
+///
+/// Every embedding gets the `url` of its page as metadata, and the `lastmod`, `published` and
+/// `title` the feed lists for it, if any. Pages that fail to load or parse are skipped.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use embed_anything::embed_feed;
+/// use embed_anything::feeds::FeedConfig;
+/// use embed_anything::embeddings::embed::Embedder;
+///
+/// async fn embed_blog() {
+///     let embedder = Embedder::from_pretrained_hf("jina", "jinaai/jina-embeddings-v2-small-en", None, None, None).unwrap();
+///     let feed_config = FeedConfig::default().with_max_pages(50);
+///     let embeddings = embed_feed("https://example.com/sitemap.xml", &embedder, Some(&feed_config), None, None)
+///         .await
+///         .unwrap();
+/// }
+/// ```
+pub async fn embed_feed(
+    url: &str,
+    embedder: &Embedder,
+    feed_config: Option<&FeedConfig>,
+    config: Option<&TextEmbedConfig>,
+    adapter: Option<Arc<dyn Adapter>>,
+) -> Result<Option<Vec<EmbedData>>> {
+    let mut pages = HtmlPageEmbedder::new(embedder, config, adapter)?;
+    let mut source = FeedSource::new(url, feed_config.cloned().unwrap_or_default()).await?;
+
+    while let Some(page) = source.next_page().await {
+        let metadata = page.entry.metadata().into_iter().collect();
+        pages.embed(&page.entry.url, &page.html, metadata).await?;
+    }
+    Ok(pages.finish())
+}
+
+/// Embeds fetched HTML pages one by one with the [HtmlProcessor], for [embed_website] and
+/// [embed_feed].
+struct HtmlPageEmbedder<'a> {
+    embedding_model: &'a TextEmbedder,
+    config: TextEmbedConfig,
+    html_processor: HtmlProcessor,
+    /// Built once and used for every page.
+    semantic_chunker: Option<StatisticalChunker>,
+    adapter: Option<Arc<dyn Adapter>>,
+    embeddings: Vec<EmbedData>,
+}
+
+impl<'a> HtmlPageEmbedder<'a> {
+    fn new(
+        embedder: &'a Embedder,
+        config: Option<&TextEmbedConfig>,
+        adapter: Option<Arc<dyn Adapter>>,
+    ) -> Result<Self> {
+        let Embedder::Text(embedding_model) = embedder else {
+            return Err(anyhow::anyhow!("Embedding web pages is only supported for text models"));
+        };
+        let config = config.cloned().unwrap_or_default().for_file_type("html");
+        let chunking = Chunking::new(&config, Some(embedding_model))?;
+        let html_processor =
+            HtmlProcessor::with_sizing(chunking.chunk_size, chunking.overlap, chunking.sizing)?
+                .with_content_extraction(&config.content_extraction.clone().unwrap_or_default())?;
+        let semantic_chunker = semantic_chunker(&config)?;
+        Ok(Self {
+            embedding_model,
+            config,
+            html_processor,
+            semantic_chunker,
+            adapter,
+            embeddings: Vec::new(),
+        })
+    }
+
+    /// Embeds the page fetched from `url`, and sends its embeddings to the adapter if there is
+    /// one. A page that fails to parse is logged and skipped.
+    async fn embed(
+        &mut self,
+        url: &str,
+        html: &str,
+        metadata: HashMap<String, String>,
+    ) -> Result<()> {
+        let document = match self.html_processor.process_document(html) {
+            Ok(document) => document,
+            Err(e) => {
+                eprintln!("Skipping {}: {:?}", url, e);
+                return Ok(());
+            }
+        };
+        let embeddings = emb_document(
+            document,
+            self.embedding_model,
+            &self.config,
+            self.semantic_chunker.as_ref(),
+            Some(metadata),
+            self.adapter.clone(),
+        )
+        .await?;
+        self.embeddings.extend(embeddings.unwrap_or_default());
+        Ok(())
+    }
+
+    /// The embeddings of every page, or None if they were sent to the adapter.
+    fn finish(self) -> Option<Vec<EmbedData>> {
+        match self.adapter {
+            Some(_) => None,
+            None => Some(self.embeddings),
+        }
     }
 }
 
@@ -258,37 +547,76 @@ async fn emb_text<T: AsRef<std::path::Path>>(
     file: T,
     embedding_model: &TextEmbedder,
     config: Option<&TextEmbedConfig>,
-    adapter: Option<Box<dyn FnOnce(Vec<EmbedData>) + Send + Sync>>,
+    adapter: Option<Arc<dyn Adapter>>,
 ) -> Result<Option<Vec<EmbedData>>> {
-    let binding = TextEmbedConfig::default();
-    let config = config.unwrap_or(&binding);
-    let chunk_size = config.chunk_size.unwrap_or(1000);
-    let overlap_ratio = config.overlap_ratio.unwrap_or(0.0);
-    let batch_size = config.batch_size;
+    let extension = file
+        .as_ref()
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let config = config.cloned().unwrap_or_default().for_file_type(&extension);
+    let config = &config;
+    let chunking = Chunking::new(config, Some(embedding_model))?;
     let use_ocr = config.use_ocr.unwrap_or(false);
     let tesseract_path = config.tesseract_path.clone();
-    let late_chunking = config.late_chunking;
-    let text = extract_document(&file, chunk_size, (chunk_size as f32 * overlap_ratio) as usize, OcrConfig { use_ocr, tesseract_path })?;
-
+    let content_extraction = config.content_extraction.clone().unwrap_or_default();
+    let pdf_config = config.pdf_config.clone().unwrap_or_default();
+    let text = extract_document(
+        &file,
+        &chunking,
+        OcrConfig { use_ocr, tesseract_path },
+        &content_extraction,
+        &pdf_config,
+    )?;
     let metadata = TextLoader::get_metadata(file).ok();
+    let semantic_chunker = semantic_chunker(config)?;
+    emb_document(
+        text,
+        embedding_model,
+        config,
+        semantic_chunker.as_ref(),
+        metadata,
+        adapter,
+    )
+    .await
+}
+
+/// Splits an extracted document with the semantic chunker, if any, and embeds its chunks. The
+/// chunker is built once per job by the caller, see [semantic_chunker].
+async fn emb_document(
+    document: Document,
+    embedding_model: &TextEmbedder,
+    config: &TextEmbedConfig,
+    semantic_chunker: Option<&StatisticalChunker>,
+    metadata: Option<HashMap<String, String>>,
+    adapter: Option<Arc<dyn Adapter>>,
+) -> Result<Option<Vec<EmbedData>>> {
+    let batch_size = config.batch_size;
+    let document = split_document(document, semantic_chunker, batch_size.unwrap_or(32)).await?;
 
     // Convert Vec<String> to Vec<&str> for embedding
-    let chunk_refs: Vec<&str> = text.chunks.iter().map(|s| s.as_str()).collect();
-
-    if let Some(adapter) = adapter {
-        let encodings = embedding_model
-            .embed(&chunk_refs, batch_size, late_chunking)
-            .await?;
-        let embeddings = get_text_metadata(&Rc::new(encodings), &chunk_refs, &metadata)?;
-        adapter(embeddings);
-        Ok(None)
-    } else {
-        let encodings = embedding_model
-            .embed(&chunk_refs, batch_size, late_chunking)
-            .await?;
-        let embeddings = get_text_metadata(&Rc::new(encodings), &chunk_refs, &metadata)?;
+    let chunk_refs: Vec<&str> = document.chunks.iter().map(|s| s.as_str()).collect();
+
+    let encodings = embedding_model
+        .embed(&chunk_refs, batch_size, config.late_chunking)
+        .await?;
+    let embeddings = get_document_metadata(&Rc::new(encodings), &document, &metadata)?;
+    send_to_adapter(embeddings, adapter).await
+}
 
-        Ok(Some(embeddings))
+/// Upserts `embeddings` into `adapter`, or returns them when there is no adapter.
+async fn send_to_adapter(
+    embeddings: Vec<EmbedData>,
+    adapter: Option<Arc<dyn Adapter>>,
+) -> Result<Option<Vec<EmbedData>>> {
+    match adapter {
+        Some(adapter) => {
+            let mut sink = AdapterSink::new(adapter);
+            sink.push(embeddings).await?;
+            sink.flush().await?;
+            Ok(None)
+        }
+        None => Ok(Some(embeddings)),
     }
 }
 
@@ -349,7 +677,7 @@ pub async fn emb_audio<T: AsRef<std::path::Path>>(
 /// * `directory` - A `PathBuf` representing the directory containing the images to embed.
 /// * `embedder` - A reference to the embedding model to use.
 /// * `config` - An optional `ImageEmbedConfig` object specifying the configuration for the embedding model. Default buffer size is 100.
-/// * `adapter` - An optional callback function to handle the embeddings.
+/// * `adapter` - An optional [Adapter] that the embeddings are upserted into.
 ///
 /// # Returns
 /// An `Option` containing a vector of `EmbedData` objects representing the embeddings of the images, or `None` if an adapter is used.
@@ -357,6 +685,10 @@ pub async fn emb_audio<T: AsRef<std::path::Path>>(
 /// # Errors
 /// Returns a `Result` with an error if the embedding process fails.
 ///
+/// # Cancellation
+/// Once the [ImageEmbedConfig::cancellation_token] is cancelled, the images that are already
+/// buffered are embedded and passed on, and the embeddings so far are returned.
+///
 /// # Example
 ///
 /// ```rust
@@ -377,92 +709,124 @@ pub async fn embed_image_directory<T: EmbedImage + Send + Sync + 'static>(
     directory: PathBuf,
     embedding_model: &Arc<T>,
     config: Option<&ImageEmbedConfig>,
-    adapter: Option<Box<dyn FnMut(Vec<EmbedData>) + Send + Sync>>,
+    adapter: Option<Arc<dyn Adapter>>,
 ) -> Result<Option<Vec<EmbedData>>> {
-    let mut file_parser = FileParser::new();
+    let binding = ImageEmbedConfig::default();
+    let config = config.unwrap_or(&binding);
+    let mut file_parser = FileParser::with_filter(config.file_filter.clone().unwrap_or_default());
     file_parser.get_image_paths(&directory)?;
 
-    let buffer_size = config
-        .unwrap_or(&ImageEmbedConfig::default())
-        .buffer_size
-        .unwrap_or(100);
+    let pipeline = spawn_image_pipeline(file_parser.files, embedding_model, config)?;
+
+    pipeline.drain(adapter).await
+}
+
+/// Embeds the images in a directory like [embed_image_directory], but yields the embeddings batch
+/// by batch as a [Stream]. Dropping the stream cancels the job.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use embed_anything::stream_image_directory;
+/// use embed_anything::embeddings::embed::Embedder;
+/// use futures::StreamExt;
+/// use std::path::PathBuf;
+/// use std::sync::Arc;
+///
+/// async fn forward_image_embeddings() {
+///     let embedder = Arc::new(Embedder::from_pretrained_hf("clip", "openai/clip-vit-base-patch16", None, None, None).unwrap());
+///     let mut batches = stream_image_directory(PathBuf::from("images"), &embedder, None).unwrap();
+///     while let Some(batch) = batches.next().await {
+///         println!("Got {} embeddings", batch.unwrap().len());
+///     }
+/// }
+/// ```
+pub fn stream_image_directory<T: EmbedImage + Send + Sync + 'static>(
+    directory: PathBuf,
+    embedding_model: &Arc<T>,
+    config: Option<&ImageEmbedConfig>,
+) -> Result<impl Stream<Item = Result<Vec<EmbedData>>> + Send + 'static> {
+    let binding = ImageEmbedConfig::default();
+    let config = config.unwrap_or(&binding);
+    let mut file_parser = FileParser::with_filter(config.file_filter.clone().unwrap_or_default());
+    file_parser.get_image_paths(&directory)?;
+
+    let pipeline = spawn_image_pipeline(file_parser.files, embedding_model, config)?;
+    Ok(pipeline.into_stream())
+}
+
+/// Number of embedded image batches that may wait for the adapter or the stream consumer.
+const IMAGE_BATCH_QUEUE: usize = 4;
+
+/// Starts embedding `images` in the background.
+fn spawn_image_pipeline<T: EmbedImage + Send + Sync + 'static>(
+    images: Vec<String>,
+    embedding_model: &Arc<T>,
+    config: &ImageEmbedConfig,
+) -> Result<Pipeline> {
+    let buffer_size = config.buffer_size.unwrap_or(100);
+    let cancellation = config
+        .cancellation_token
+        .as_ref()
+        .map_or_else(CancellationToken::new, |token| token.child_token());
 
     let (tx, mut rx) = mpsc::unbounded_channel();
-    let (collector_tx, mut collector_rx) = mpsc::unbounded_channel();
+    let (collector_tx, collector_rx) = mpsc::channel(IMAGE_BATCH_QUEUE);
 
     let embedder = embedding_model.clone();
 
-    let pb = indicatif::ProgressBar::new(file_parser.files.len() as u64);
-    pb.set_style(
-        indicatif::ProgressStyle::with_template(
-            "{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} ({eta})",
-        )?,
-    );
+    let image_paths = images.iter().map(PathBuf::from).collect::<Vec<_>>();
+    let progress = Progress::new(config.progress_callback.clone(), &image_paths)?;
 
     let processing_task = tokio::spawn({
+        let cancellation = cancellation.clone();
         async move {
             // make image buffer
             let mut image_buffer = Vec::with_capacity(buffer_size);
             let mut files_processed: std::collections::HashSet<String> =
                 std::collections::HashSet::new();
-            while let Some(image) = rx.recv().await {
+            loop {
+                let image = tokio::select! {
+                    biased;
+                    _ = cancellation.cancelled() => None,
+                    image = rx.recv() => image,
+                };
+                let Some(image) = image else {
+                    break;
+                };
                 image_buffer.push(image);
 
                 if image_buffer.len() == buffer_size {
-                    // Ensure embedder is mutable and not wrapped in Arc
-                    match process_images(&image_buffer, embedder.clone()).await {
-                        Ok(embeddings) => {
-                            let files = embeddings
-                                .iter()
-                                .cloned()
-                                .map(|e| e.metadata.unwrap().get("file_name").unwrap().to_string())
-                                .collect::<Vec<_>>();
-
-                            let unique_files = files.into_iter().unique().collect::<Vec<_>>();
-                            let old_len = files_processed.len() as u64;
-                            files_processed.extend(unique_files);
-                            let new_len = files_processed.len() as u64;
-
-                            pb.inc(new_len - old_len);
-
-                            if let Err(e) = collector_tx.send(embeddings) {
-                                eprintln!("Error sending embeddings to collector: {:?}", e);
-                            }
-                        }
-                        Err(e) => eprintln!("Error processing images: {:?}", e),
-                    }
-
+                    flush_images(
+                        &image_buffer,
+                        &embedder,
+                        &progress,
+                        &mut files_processed,
+                        &collector_tx,
+                    )
+                    .await;
                     image_buffer.clear();
                 }
             }
 
-            // Process any remaining images
+            // Process any remaining images, including the partial buffer of a cancelled job
             if !image_buffer.is_empty() {
-                match process_images(&image_buffer, embedder).await {
-                    Ok(embeddings) => {
-                        let files = embeddings
-                            .iter()
-                            .cloned()
-                            .map(|e| e.metadata.unwrap().get("file_name").unwrap().to_string())
-                            .collect::<Vec<_>>();
-                        let unique_files = files.into_iter().unique().collect::<Vec<_>>();
-                        let old_len = files_processed.len() as u64;
-                        files_processed.extend(unique_files);
-                        let new_len = files_processed.len() as u64;
-
-                        pb.inc(new_len - old_len);
-
-                        if let Err(e) = collector_tx.send(embeddings) {
-                            eprintln!("Error sending embeddings to collector: {:?}", e);
-                        }
-                    }
-                    Err(e) => eprintln!("Error processing images: {:?}", e),
-                }
+                flush_images(
+                    &image_buffer,
+                    &embedder,
+                    &progress,
+                    &mut files_processed,
+                    &collector_tx,
+                )
+                .await;
+            }
+            if cancellation.is_cancelled() {
+                progress.cancelled();
             }
         }
     });
 
-    file_parser.files.par_iter().for_each(|image| {
+    images.par_iter().for_each(|image| {
         if let Err(e) = tx.send(image.clone()) {
             eprintln!("Error sending image: {:?}", e);
         }
@@ -470,23 +834,52 @@ pub async fn embed_image_directory<T: EmbedImage + Send + Sync + 'static>(
 
     drop(tx);
 
-    let mut all_embeddings = Vec::new();
-    let mut adapter = adapter;
-    while let Some(embeddings) = collector_rx.recv().await {
-        if let Some(adapter) = adapter.as_mut() {
-            adapter(embeddings.to_vec());
-        } else {
-            all_embeddings.extend(embeddings.to_vec());
-        }
-    }
+    let task = tokio::spawn(async move {
+        processing_task.await?;
+        Ok::<_, Error>(())
+    });
 
-    // Wait for the spawned task to complete
-    processing_task.await?;
+    Ok(Pipeline {
+        batches: collector_rx,
+        task,
+        cancellation,
+    })
+}
 
-    if adapter.is_some() {
-        Ok(None)
-    } else {
-        Ok(Some(all_embeddings))
+/// Embeds a buffer of images, sends the embeddings to the collector and reports the images that
+/// are done.
+async fn flush_images<E: EmbedImage>(
+    image_buffer: &[String],
+    embedder: &Arc<E>,
+    progress: &Progress,
+    files_processed: &mut std::collections::HashSet<String>,
+    collector_tx: &mpsc::Sender<Arc<Vec<EmbedData>>>,
+) {
+    match process_images(image_buffer, embedder.clone()).await {
+        Ok(embeddings) => {
+            progress.chunks_embedded(embeddings.len());
+            for file in embeddings
+                .iter()
+                .filter_map(|e| e.metadata.as_ref()?.get("file_name"))
+            {
+                if files_processed.insert(file.clone()) {
+                    progress.file_done(file, None);
+                }
+            }
+
+            if let Err(e) = collector_tx.send(embeddings).await {
+                eprintln!("Error sending embeddings to collector: {:?}", e);
+            }
+        }
+        Err(e) => {
+            eprintln!("Error processing images: {:?}", e);
+            let error = e.to_string();
+            for image in image_buffer {
+                if files_processed.insert(image.clone()) {
+                    progress.file_done(image, Some(&error));
+                }
+            }
+        }
     }
 }
 
@@ -506,7 +899,7 @@ async fn process_images<E: EmbedImage>(
 /// * `embedder` - A reference to the embedding model to use.
 /// * `extensions` - An optional vector of strings representing the file extensions to consider for embedding. If `None`, all files in the directory will be considered.
 /// * `config` - An optional `TextEmbedConfig` object specifying the configuration for the embedding model.
-/// * `adapter` - An optional callback function to handle the embeddings.
+/// * `adapter` - An optional [Adapter] that the embeddings are upserted into.
 ///
 /// # Returns
 /// An `Option` containing a vector of `EmbedData` objects representing the embeddings of the files, or `None` if an adapter is used.
@@ -514,6 +907,11 @@ async fn process_images<E: EmbedImage>(
 /// # Errors
 /// Returns a `Result` with an error if the embedding process fails.
 ///
+/// # Cancellation
+/// Once the [TextEmbedConfig::cancellation_token] is cancelled, no new files are extracted. The
+/// chunks that are already buffered are embedded and passed on, and the embeddings so far are
+/// returned. Files whose chunks were cut off are reported as failed.
+///
 /// # Example
 ///
 /// ```rust
@@ -537,150 +935,417 @@ pub async fn embed_directory_stream(
     embedder: &Arc<Embedder>,
     extensions: Option<Vec<String>>,
     config: Option<&TextEmbedConfig>,
-    adapter: Option<Box<dyn FnMut(Vec<EmbedData>) + Send + Sync>>,
+    adapter: Option<Arc<dyn Adapter>>,
 ) -> Result<Option<Vec<EmbedData>>> {
     println!("Embedding directory: {:?}", directory);
 
-    let binding = TextEmbedConfig::default();
-    let config = config.unwrap_or(&binding);
-    let chunk_size = config.chunk_size.unwrap_or(binding.chunk_size.unwrap());
-    let buffer_size = config.buffer_size.unwrap_or(binding.buffer_size.unwrap());
-    let batch_size = config.batch_size;
-    let use_ocr = config.use_ocr.unwrap_or(false);
-    let tesseract_path = config.tesseract_path.clone();
-    let overlap_ratio = config.overlap_ratio.unwrap_or(0.0);
-    let late_chunking = config.late_chunking;
-    let mut file_parser = FileParser::new();
+    let mut file_parser = FileParser::with_filter(text_file_filter(config));
     file_parser.get_text_files(&directory, extensions)?;
-    let files = file_parser.files.clone();
-    let (tx, mut rx) = mpsc::unbounded_channel();
-    let (collector_tx, mut collector_rx) = mpsc::unbounded_channel();
+    embed_files_batch(file_parser.files, embedder, config, adapter).await
+}
 
-    let embedder = embedder.clone();
-    let files: Vec<_> = files.into_iter().collect();
-    let pb = indicatif::ProgressBar::new(files.len() as u64);
-    pb.set_style(
-        indicatif::ProgressStyle::with_template(
-            "{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} ({eta})",
-        )?,
-    );
+/// The filter of the files picked up when embedding a directory with `config`.
+fn text_file_filter(config: Option<&TextEmbedConfig>) -> FileFilter {
+    config
+        .and_then(|config| config.file_filter.clone())
+        .unwrap_or_default()
+}
 
-    let processing_task = tokio::spawn({
-        async move {
-            let mut chunk_buffer = Vec::with_capacity(buffer_size);
-            let mut metadata_buffer = Vec::with_capacity(buffer_size);
-            let mut files_processed: std::collections::HashSet<String> =
-                std::collections::HashSet::new();
+/// Embeds the files in a directory like [embed_directory_stream], but yields the embeddings batch
+/// by batch as a [Stream] instead of collecting them or passing them to an adapter. See
+/// [stream_files_batch].
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use embed_anything::stream_directory;
+/// use embed_anything::embeddings::embed::Embedder;
+/// use futures::StreamExt;
+/// use std::path::PathBuf;
+/// use std::sync::Arc;
+///
+/// async fn forward_embeddings() {
+///     let embedder = Arc::new(Embedder::from_pretrained_hf("jina", "jinaai/jina-embeddings-v2-small-en", None, None, None).unwrap());
+///     let batches = stream_directory(PathBuf::from("docs"), &embedder, None, None).unwrap();
+///     // Forward at most two batches at a time
+///     batches
+///         .for_each_concurrent(2, |batch| async move {
+///             println!("Forwarding {} embeddings", batch.unwrap().len());
+///         })
+///         .await;
+/// }
+/// ```
+pub fn stream_directory(
+    directory: PathBuf,
+    embedder: &Arc<Embedder>,
+    extensions: Option<Vec<String>>,
+    config: Option<&TextEmbedConfig>,
+) -> Result<impl Stream<Item = Result<Vec<EmbedData>>> + Send + 'static> {
+    let mut file_parser = FileParser::with_filter(text_file_filter(config));
+    file_parser.get_text_files(&directory, extensions)?;
+    stream_files_batch(file_parser.files, embedder, config)
+}
 
-            while let Some((chunk, metadata)) = rx.recv().await {
-                chunk_buffer.push(chunk);
-                metadata_buffer.push(metadata);
+/// The kind of content an embedding was made from. [embed_multimodal_directory] stores it in the
+/// `modality` metadata of every embedding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Modality {
+    Text,
+    Image,
+}
 
-                if chunk_buffer.len() == buffer_size {
-                    match process_chunks(
-                        &chunk_buffer,
-                        &metadata_buffer,
-                        &embedder,
-                        batch_size,
-                        late_chunking,
-                    )
-                    .await
-                    {
-                        Ok(embeddings) => {
-                            let files = embeddings
-                                .iter()
-                                .cloned()
-                                .map(|e| e.metadata.unwrap().get("file_name").unwrap().to_string())
-                                .collect::<Vec<_>>();
-
-                            let unique_files = files.into_iter().unique().collect::<Vec<_>>();
-                            let old_len = files_processed.len() as u64;
-                            files_processed.extend(unique_files);
-                            let new_len = files_processed.len() as u64;
-
-                            pb.inc(new_len - old_len);
-
-                            if let Err(e) = collector_tx.send(embeddings) {
-                                eprintln!("Error sending embeddings to collector: {:?}", e);
-                            }
-                        }
-                        Err(e) => eprintln!("Error processing chunks: {:?}", e),
-                    }
+impl Display for Modality {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Modality::Text => write!(f, "text"),
+            Modality::Image => write!(f, "image"),
+        }
+    }
+}
 
-                    chunk_buffer.clear();
-                    metadata_buffer.clear();
-                }
+/// Embeds a directory that mixes documents and images, so that both end up in one collection.
+///
+/// Images are embedded with `vision_embedder` and every other file with `text_embedder`. Every
+/// embedding is tagged with its [Modality] in the `modality` metadata. Both pipelines run side by
+/// side and share the adapter.
+///
+/// # Arguments
+///
+/// * `directory` - A `PathBuf` representing the directory containing the files to embed.
+/// * `text_embedder` - The text embedding model, for the documents.
+/// * `vision_embedder` - The vision embedding model, e.g. CLIP or ColPali, for the images.
+/// * `extensions` - An optional vector of strings representing the document extensions to consider for embedding.
+/// * `text_config` - An optional `TextEmbedConfig` for the documents.
+/// * `image_config` - An optional `ImageEmbedConfig` for the images.
+/// * `adapter` - An optional [Adapter] that the embeddings are upserted into.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use embed_anything::embed_multimodal_directory;
+/// use embed_anything::embeddings::embed::Embedder;
+/// use std::path::PathBuf;
+/// use std::sync::Arc;
+///
+/// async fn embed_assets() {
+///     let text_embedder = Arc::new(Embedder::from_pretrained_hf("jina", "jinaai/jina-embeddings-v2-small-en", None, None, None).unwrap());
+///     let vision_embedder = Arc::new(Embedder::from_pretrained_hf("clip", "openai/clip-vit-base-patch16", None, None, None).unwrap());
+///     let embeddings = embed_multimodal_directory(PathBuf::from("assets"), &text_embedder, &vision_embedder, None, None, None, None)
+///         .await
+///         .unwrap()
+///         .unwrap();
+///     let images = embeddings
+///         .iter()
+///         .filter(|e| e.metadata.as_ref().unwrap()["modality"] == "image")
+///         .count();
+///     println!("{} image embeddings", images);
+/// }
+/// ```
+pub async fn embed_multimodal_directory(
+    directory: PathBuf,
+    text_embedder: &Arc<Embedder>,
+    vision_embedder: &Arc<Embedder>,
+    extensions: Option<Vec<String>>,
+    text_config: Option<&TextEmbedConfig>,
+    image_config: Option<&ImageEmbedConfig>,
+    adapter: Option<Arc<dyn Adapter>>,
+) -> Result<Option<Vec<EmbedData>>> {
+    if !matches!(text_embedder.as_ref(), Embedder::Text(_)) {
+        return Err(anyhow::anyhow!("The text embedder must be a text model"));
+    }
+    if !matches!(vision_embedder.as_ref(), Embedder::Vision(_)) {
+        return Err(anyhow::anyhow!("The vision embedder must be a vision model"));
+    }
+    println!("Embedding directory: {:?}", directory);
+
+    let images = FileParser::with_filter(
+        image_config
+            .and_then(|config| config.file_filter.clone())
+            .unwrap_or_default(),
+    )
+    .get_image_paths(&directory)?;
+    // An image matching one of the text extensions still goes to the vision embedder only.
+    let image_set: HashSet<&String> = images.iter().collect();
+    let text_files: Vec<PathBuf> = FileParser::with_filter(text_file_filter(text_config))
+        .get_text_files(&directory, extensions)?
+        .into_iter()
+        .filter(|file| !image_set.contains(file))
+        .map(PathBuf::from)
+        .collect();
+
+    let text_binding = TextEmbedConfig::default();
+    let image_binding = ImageEmbedConfig::default();
+    let text_pipeline = spawn_files_pipeline(
+        text_files,
+        text_embedder,
+        text_config.unwrap_or(&text_binding),
+    )?
+    .with_modality(Modality::Text);
+    let image_pipeline = spawn_image_pipeline(
+        images,
+        vision_embedder,
+        image_config.unwrap_or(&image_binding),
+    )?
+    .with_modality(Modality::Image);
+
+    let (text_embeddings, image_embeddings) = tokio::try_join!(
+        text_pipeline.drain(adapter.clone()),
+        image_pipeline.drain(adapter.clone())
+    )?;
+    Ok(match (text_embeddings, image_embeddings) {
+        (Some(mut text_embeddings), Some(image_embeddings)) => {
+            text_embeddings.extend(image_embeddings);
+            Some(text_embeddings)
+        }
+        _ => None,
+    })
+}
+
+/// Number of files embedded between two saves of the manifest in [embed_directory_incremental].
+const MANIFEST_CHECKPOINT_FILES: usize = 16;
+
+/// Embeds the files in a directory that are new or changed since they were recorded in `manifest`.
+///
+/// Files are embedded in small groups and the manifest is saved after each group, so an
+/// interrupted run resumes with the files that were not recorded yet. Files that are in the
+/// manifest but no longer on disk are dropped from it and returned in [IndexUpdate::removed] so
+/// their vectors can be deleted downstream.
+///
+/// # Arguments
+///
+/// * `directory` - A `PathBuf` representing the directory containing the files to embed.
+/// * `embedder` - A reference to the embedding model to use.
+/// * `extensions` - An optional vector of strings representing the file extensions to consider for embedding.
+/// * `config` - An optional `TextEmbedConfig` object specifying the configuration for the embedding model.
+/// * `manifest` - The manifest of what has already been embedded. It is updated and saved in place.
+/// * `adapter` - An optional [Adapter] that the embeddings are upserted into.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use embed_anything::embed_directory_incremental;
+/// use embed_anything::embeddings::embed::Embedder;
+/// use embed_anything::manifest::IndexManifest;
+/// use std::path::PathBuf;
+/// use std::sync::Arc;
+///
+/// async fn index_docs() {
+///     let model_id = "jinaai/jina-embeddings-v2-small-en";
+///     let embedder = Arc::new(Embedder::from_pretrained_hf("jina", model_id, None, None, None).unwrap());
+///     let mut manifest = IndexManifest::open("docs_index.json", model_id).unwrap();
+///     let update = embed_directory_incremental(PathBuf::from("docs"), &embedder, None, None, &mut manifest, None)
+///         .await
+///         .unwrap();
+///     println!("Embedded {} files, {} removed", update.embedded.len(), update.removed.len());
+/// }
+/// ```
+pub async fn embed_directory_incremental(
+    directory: PathBuf,
+    embedder: &Arc<Embedder>,
+    extensions: Option<Vec<String>>,
+    config: Option<&TextEmbedConfig>,
+    manifest: &mut IndexManifest,
+    adapter: Option<Arc<dyn Adapter>>,
+) -> Result<IndexUpdate> {
+    let mut file_parser = FileParser::with_filter(text_file_filter(config));
+    file_parser.get_text_files(&directory, extensions)?;
+    let diff = manifest.diff(&file_parser.files)?;
+
+    // Collect the report of every file, and still pass them on to the caller's callback.
+    let reports = Arc::new(Mutex::new(Vec::<FileReport>::new()));
+    let mut config = config.cloned().unwrap_or_default();
+    let report_callback = config.report_callback.take();
+    config.report_callback = Some({
+        let reports = reports.clone();
+        Arc::new(move |report: FileReport| {
+            if let Some(callback) = &report_callback {
+                callback(report.clone());
             }
+            reports.lock().unwrap().push(report);
+        })
+    });
 
-            // Process any remaining chunks
-            if !chunk_buffer.is_empty() {
-                match process_chunks(
-                    &chunk_buffer,
-                    &metadata_buffer,
-                    &embedder,
-                    batch_size,
-                    late_chunking,
-                )
-                .await
-                {
-                    Ok(embeddings) => {
-                        let files = embeddings
-                            .iter()
-                            .cloned()
-                            .map(|e| e.metadata.unwrap().get("file_name").unwrap().to_string())
-                            .collect::<Vec<_>>();
-                        let unique_files = files.into_iter().unique().collect::<Vec<_>>();
-                        let old_len = files_processed.len() as u64;
-                        files_processed.extend(unique_files);
-                        let new_len = files_processed.len() as u64;
-
-                        pb.inc(new_len - old_len);
-
-                        if let Err(e) = collector_tx.send(embeddings) {
-                            eprintln!("Error sending embeddings to collector: {:?}", e);
-                        }
+    let mut all_embeddings = Vec::new();
+    let mut embedded = Vec::new();
+    let mut failed = Vec::new();
+
+    for files in diff.to_embed.chunks(MANIFEST_CHECKPOINT_FILES) {
+        // The state is read before the files are embedded, so a file that changes meanwhile is
+        // embedded again by the next run.
+        let mut states: HashMap<&String, FileState> = files
+            .iter()
+            .filter_map(|file| Some((file, FileState::read(file).ok()?)))
+            .collect();
+        if let Some(embeddings) =
+            embed_files_batch(files.to_vec(), embedder, Some(&config), adapter.clone()).await?
+        {
+            all_embeddings.extend(embeddings);
+        }
+
+        // Failed files are left out of the manifest so the next run retries them.
+        for report in reports.lock().unwrap().drain(..) {
+            match report.status {
+                FileStatus::Embedded | FileStatus::Empty => {
+                    if let Some(state) = states.remove(&report.file) {
+                        manifest.record(&report.file, state, report.chunk_count);
                     }
-                    Err(e) => eprintln!("Error processing chunks: {:?}", e),
+                    embedded.push(report.file);
                 }
+                FileStatus::Failed => failed.push(report),
             }
         }
-    });
+        manifest.save()?;
+    }
 
-    files.into_iter().for_each(|file| {
-        let text = match extract_document(&file, chunk_size, (chunk_size as f32 * overlap_ratio) as usize, OcrConfig { use_ocr, tesseract_path: tesseract_path.clone() }) {
-            Ok(text) => text,
-            Err(_) => {
-                return;
+    for file in &diff.removed {
+        manifest.forget(file);
+    }
+    manifest.save()?;
+
+    Ok(IndexUpdate {
+        embeddings: if adapter.is_some() {
+            None
+        } else {
+            Some(all_embeddings)
+        },
+        embedded,
+        unchanged: diff.unchanged,
+        removed: diff.removed,
+        failed,
+    })
+}
+
+/// Watches a directory and re-embeds files as they are created or modified.
+///
+/// This runs until the returned future is dropped or the watcher shuts down. A created or
+/// modified file is embedded first, and only once that succeeds are its previous vectors removed
+/// with [Adapter::delete] and its new chunks upserted into `adapter`, so a file that fails to
+/// embed, or has no chunks, keeps its previous vectors. Deleted files only have their vectors
+/// removed. Adapters that do not [support deleting](Adapter::supports_delete) only get the new
+/// chunks upserted, and keep the vectors of modified and deleted files.
+/// Use [embed_directory_incremental] first to index what is already in the directory.
+///
+/// # Arguments
+///
+/// * `directory` - A `PathBuf` representing the directory to watch.
+/// * `embedder` - A reference to the embedding model to use.
+/// * `extensions` - An optional vector of strings representing the file extensions to watch.
+/// * `config` - An optional `TextEmbedConfig` object specifying the configuration for the embedding model.
+///   Only the files that pass its `file_filter` are watched.
+/// * `adapter` - The [Adapter] that the embeddings of changed files are upserted into.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use anyhow::Result;
+/// use async_trait::async_trait;
+/// use embed_anything::adapter::Adapter;
+/// use embed_anything::embeddings::embed::{EmbedData, Embedder};
+/// use embed_anything::watch_directory;
+/// use std::path::PathBuf;
+/// use std::sync::Arc;
+///
+/// struct LoggingAdapter;
+///
+/// #[async_trait]
+/// impl Adapter for LoggingAdapter {
+///     async fn upsert(&self, data: &[EmbedData]) -> Result<()> {
+///         println!("Upserting {} chunks", data.len());
+///         Ok(())
+///     }
+///
+///     async fn delete(&self, files: &[String]) -> Result<()> {
+///         println!("Deleting vectors of {:?}", files);
+///         Ok(())
+///     }
+///
+///     fn supports_delete(&self) -> bool {
+///         true
+///     }
+/// }
+///
+/// async fn watch_docs() {
+///     let embedder = Arc::new(Embedder::from_pretrained_hf("jina", "jinaai/jina-embeddings-v2-small-en", None, None, None).unwrap());
+///     watch_directory(PathBuf::from("docs"), &embedder, None, None, Arc::new(LoggingAdapter))
+///         .await
+///         .unwrap();
+/// }
+/// ```
+pub async fn watch_directory(
+    directory: PathBuf,
+    embedder: &Arc<Embedder>,
+    extensions: Option<Vec<String>>,
+    config: Option<&TextEmbedConfig>,
+    adapter: Arc<dyn Adapter>,
+) -> Result<()> {
+    let mut watcher = DirectoryWatcher::new(&directory, extensions, text_file_filter(config))?;
+    if !adapter.supports_delete() {
+        eprintln!("The adapter does not support deleting embeddings, the vectors of modified and deleted files are kept");
+    }
+
+    while let Some(changes) = watcher.next_changes().await {
+        let changes = match changes {
+            Ok(changes) => changes,
+            Err(e) => {
+                eprintln!("Error watching directory: {:?}", e);
+                continue;
             }
         };
-        let metadata = TextLoader::get_metadata(file).unwrap();
 
-        for chunk in text.chunks {
-            if let Err(e) = tx.send((chunk, Some(metadata.clone()))) {
-                eprintln!("Error sending chunk: {:?}", e);
+        if !changes.removed.is_empty() && adapter.supports_delete() {
+            let deleted = adapter
+                .retry_policy()
+                .retry(|| adapter.delete(&changes.removed))
+                .await;
+            if let Err(e) = deleted {
+                eprintln!("Error deleting the vectors of removed files: {:?}", e);
             }
         }
-    });
 
-    drop(tx);
+        if changes.changed.is_empty() {
+            continue;
+        }
+        // Embedded in memory, so that the previous vectors of a file are only deleted once it has
+        // new ones.
+        let embeddings = match embed_files_batch(changes.changed, embedder, config, None).await {
+            Ok(embeddings) => embeddings.unwrap_or_default(),
+            Err(e) => {
+                eprintln!("Error embedding changed files: {:?}", e);
+                continue;
+            }
+        };
+        if embeddings.is_empty() {
+            continue;
+        }
 
-    let mut all_embeddings = Vec::new();
-    let mut adapter = adapter;
-    while let Some(embeddings) = collector_rx.recv().await {
-        if let Some(adapter) = adapter.as_mut() {
-            adapter(embeddings.to_vec());
-        } else {
-            all_embeddings.extend(embeddings.to_vec());
+        let embedded_files = embeddings
+            .iter()
+            .filter_map(|embedding| embedding.metadata.as_ref()?.get("file_name").cloned())
+            .collect::<std::collections::BTreeSet<_>>()
+            .into_iter()
+            .collect::<Vec<_>>();
+        if adapter.supports_delete() {
+            let deleted = adapter
+                .retry_policy()
+                .retry(|| adapter.delete(&embedded_files))
+                .await;
+            if let Err(e) = deleted {
+                // Upserting anyway would leave both the old and the new vectors in the index.
+                eprintln!("Error deleting the previous vectors of changed files: {:?}", e);
+                continue;
+            }
+        }
+        let mut sink = AdapterSink::new(adapter.clone());
+        let upserted = match sink.push(embeddings).await {
+            Ok(()) => sink.flush().await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = upserted {
+            eprintln!("Error upserting the vectors of changed files {:?}: {:?}", embedded_files, e);
         }
     }
-    // Wait for the spawned task to complete
-    processing_task.await?;
 
-    if adapter.is_some() {
-        Ok(None)
-    } else {
-        Ok(Some(all_embeddings))
-    }
+    Ok(())
 }
 
 /// Embeds a list of files.
@@ -690,7 +1355,7 @@ pub async fn embed_directory_stream(
 /// * `files` - A vector of `PathBuf` objects representing the files to embed.
 /// * `embedder` - A reference to the embedding model to use.
 /// * `config` - An optional `TextEmbedConfig` object specifying the configuration for the embedding model.
-/// * `adapter` - An optional callback function to handle the embeddings.
+/// * `adapter` - An optional [Adapter] that the embeddings are upserted into.
 ///
 /// # Returns
 /// An `Option` containing a vector of `EmbedData` objects representing the embeddings of the files, or `None` if an adapter is used.
@@ -698,6 +1363,11 @@ pub async fn embed_directory_stream(
 /// # Errors
 /// Returns a `Result` with an error if the embedding process fails.
 ///
+/// # Cancellation
+/// Once the [TextEmbedConfig::cancellation_token] is cancelled, no new files are extracted. The
+/// chunks that are already buffered are embedded and passed on, and the embeddings so far are
+/// returned. Files whose chunks were cut off are reported as failed.
+///
 /// # Example
 ///
 /// ```rust
@@ -719,145 +1389,686 @@ pub async fn embed_files_batch(
     files: impl IntoIterator<Item = impl AsRef<std::path::Path>>,
     embedder: &Arc<Embedder>,
     config: Option<&TextEmbedConfig>,
-    adapter: Option<Box<dyn FnMut(Vec<EmbedData>) + Send + Sync>>,
+    adapter: Option<Arc<dyn Adapter>>,
 ) -> Result<Option<Vec<EmbedData>>> {
     let binding = TextEmbedConfig::default();
-    let config = config.unwrap_or(&binding);
-    let chunk_size = config.chunk_size.unwrap_or(binding.chunk_size.unwrap());
+    let files = files
+        .into_iter()
+        .map(|file| file.as_ref().to_path_buf())
+        .collect();
+    let pipeline = spawn_files_pipeline(files, embedder, config.unwrap_or(&binding))?;
+
+    pipeline.drain(adapter).await
+}
+
+/// Embeds a list of files like [embed_files_batch], but yields the embeddings batch by batch as a
+/// [Stream] instead of collecting them or passing them to an adapter.
+///
+/// The stream is back-pressured: when it is not polled, the pipeline pauses once
+/// [TextEmbedConfig::queue_depth] chunks are waiting. Dropping the stream cancels the job.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use embed_anything::stream_files_batch;
+/// use embed_anything::embeddings::embed::Embedder;
+/// use futures::StreamExt;
+/// use std::path::PathBuf;
+/// use std::sync::Arc;
+///
+/// async fn forward_embeddings() {
+///     let files = vec![PathBuf::from("test_files/test.txt"), PathBuf::from("test_files/test.pdf")];
+///     let embedder = Arc::new(Embedder::from_pretrained_hf("bert", "jinaai/jina-embeddings-v2-small-en", None, None, None).unwrap());
+///     let mut batches = stream_files_batch(files, &embedder, None).unwrap();
+///     while let Some(batch) = batches.next().await {
+///         println!("Got {} embeddings", batch.unwrap().len());
+///     }
+/// }
+/// ```
+pub fn stream_files_batch(
+    files: impl IntoIterator<Item = impl AsRef<std::path::Path>>,
+    embedder: &Arc<Embedder>,
+    config: Option<&TextEmbedConfig>,
+) -> Result<impl Stream<Item = Result<Vec<EmbedData>>> + Send + 'static> {
+    let binding = TextEmbedConfig::default();
+    let files = files
+        .into_iter()
+        .map(|file| file.as_ref().to_path_buf())
+        .collect();
+    let pipeline = spawn_files_pipeline(files, embedder, config.unwrap_or(&binding))?;
+    Ok(pipeline.into_stream())
+}
+
+/// The embedded batches of a pipeline running in the background.
+struct Pipeline {
+    batches: mpsc::Receiver<Arc<Vec<EmbedData>>>,
+    /// Ends once every file is done or the job was cancelled.
+    task: JoinHandle<Result<()>>,
+    cancellation: CancellationToken,
+}
+
+impl Pipeline {
+    /// Tags every embedding of the pipeline with `modality` in its `modality` metadata.
+    fn with_modality(self, modality: Modality) -> Pipeline {
+        let Pipeline {
+            mut batches,
+            task,
+            cancellation,
+        } = self;
+        let (tx, rx) = mpsc::channel(1);
+        let task = tokio::spawn(async move {
+            while let Some(embeddings) = batches.recv().await {
+                let mut embeddings = Arc::unwrap_or_clone(embeddings);
+                for embedding in embeddings.iter_mut() {
+                    embedding
+                        .metadata
+                        .get_or_insert_with(HashMap::new)
+                        .insert("modality".to_string(), modality.to_string());
+                }
+                if tx.send(Arc::new(embeddings)).await.is_err() {
+                    break;
+                }
+            }
+            // Closing the channel lets the pipeline finish if the receiver went away early.
+            drop(batches);
+            match task.await {
+                Ok(result) => result,
+                Err(e) => Err(e.into()),
+            }
+        });
+        Pipeline {
+            batches: rx,
+            task,
+            cancellation,
+        }
+    }
+
+    /// Upserts every batch into `adapter` and waits for the pipeline to finish. Without an adapter,
+    /// the batches are collected and returned instead.
+    async fn drain(mut self, adapter: Option<Arc<dyn Adapter>>) -> Result<Option<Vec<EmbedData>>> {
+        let Some(adapter) = adapter else {
+            let mut all_embeddings = Vec::new();
+            while let Some(embeddings) = self.batches.recv().await {
+                all_embeddings.extend(embeddings.to_vec());
+            }
+            // Wait for the spawned tasks to complete
+            self.task.await??;
+            return Ok(Some(all_embeddings));
+        };
+
+        let mut sink = AdapterSink::new(adapter);
+        while let Some(embeddings) = self.batches.recv().await {
+            if let Err(e) = sink.push(Arc::unwrap_or_clone(embeddings)).await {
+                // Closing the channel lets the embedding task finish instead of waiting for us.
+                self.cancellation.cancel();
+                drop(self.batches);
+                let _ = self.task.await;
+                return Err(e);
+            }
+        }
+        self.task.await??;
+        sink.flush().await?;
+        Ok(None)
+    }
+
+    /// Yields the batches as they are embedded. A failure of the pipeline itself ends the stream
+    /// with an error, and dropping the stream cancels the pipeline.
+    fn into_stream(self) -> impl Stream<Item = Result<Vec<EmbedData>>> + Send + 'static {
+        let Pipeline {
+            batches,
+            task,
+            cancellation,
+        } = self;
+        let state = (batches, task, cancellation.drop_guard());
+        futures::stream::unfold(Some(state), |state| async move {
+            let (mut batches, task, guard) = state?;
+            match batches.recv().await {
+                Some(embeddings) => Some((
+                    Ok(Arc::unwrap_or_clone(embeddings)),
+                    Some((batches, task, guard)),
+                )),
+                None => match task.await {
+                    Ok(Ok(())) => None,
+                    Ok(Err(e)) => Some((Err(e), None)),
+                    Err(e) => Some((Err(e.into()), None)),
+                },
+            }
+        })
+    }
+}
+
+/// Starts extracting and embedding `files` in the background.
+fn spawn_files_pipeline(
+    files: Vec<PathBuf>,
+    embedder: &Arc<Embedder>,
+    config: &TextEmbedConfig,
+) -> Result<Pipeline> {
+    let binding = TextEmbedConfig::default();
     let buffer_size = config.buffer_size.unwrap_or(binding.buffer_size.unwrap());
     let batch_size = config.batch_size;
     let late_chunking = config.late_chunking;
-    let use_ocr = config.use_ocr.unwrap_or(false);
-    let tesseract_path = config.tesseract_path.clone();
-    let overlap_ratio = config.overlap_ratio.unwrap_or(0.0);
-
-    let (tx, mut rx) = mpsc::unbounded_channel();
-    let (collector_tx, mut collector_rx) = mpsc::unbounded_channel();
+    let queue_depth = config.queue_depth.unwrap_or(binding.queue_depth.unwrap()).max(1);
+    let workers = config
+        .extraction_workers
+        .unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |n| n.get()))
+        .max(1);
+    let extractor = Arc::new(Extractor::new(config, embedder)?);
+
+    // The extraction workers feed the embedding task, which feeds the adapter. Both channels are
+    // bounded, so a slow model or adapter pauses extraction instead of buffering every chunk.
+    let (tx, mut rx) = mpsc::channel(queue_depth);
+    let (collector_tx, collector_rx) = mpsc::channel(queue_depth.div_ceil(buffer_size).max(1));
+
+    // Late chunking embeds every chunk in the context of its neighbours, so duplicates differ.
+    let deduplication = config
+        .deduplication
+        .filter(|_| !late_chunking.unwrap_or(false));
+    let deduplication_cache_size = config
+        .deduplication_cache_size
+        .unwrap_or(binding.deduplication_cache_size.unwrap());
 
     let embedder = embedder.clone();
-    let files: Vec<_> = files.into_iter().collect();
-    let pb = indicatif::ProgressBar::new(files.len() as u64);
-    pb.set_style(
-        indicatif::ProgressStyle::with_template(
-            "{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} ({eta})",
-        )?,
-    );
+    let progress = Progress::new(config.progress_callback.clone(), &files)?;
+    let tracker = Arc::new(Mutex::new(FileTracker::new(
+        config.report_callback.clone(),
+        progress,
+    )));
+    // A child token, so that dropping a stream cancels its own job but not the caller's token.
+    let cancellation = config
+        .cancellation_token
+        .as_ref()
+        .map_or_else(CancellationToken::new, |token| token.child_token());
 
     let processing_task = tokio::spawn({
+        let tracker = tracker.clone();
+        let cancellation = cancellation.clone();
         async move {
-            let mut chunk_buffer = Vec::with_capacity(buffer_size);
-            let mut metadata_buffer = Vec::with_capacity(buffer_size);
-            let mut files_processed: std::collections::HashSet<String> =
-                std::collections::HashSet::new();
+            let mut buffer = ChunkBuffer::new(
+                buffer_size,
+                deduplication.map(|mode| Deduplicator::new(mode, deduplication_cache_size)),
+            );
+
+            loop {
+                let received = tokio::select! {
+                    biased;
+                    _ = cancellation.cancelled() => None,
+                    received = rx.recv() => received,
+                };
+                let Some((file, chunk, metadata)) = received else {
+                    break;
+                };
+                buffer.push(file, chunk, metadata);
+
+                if buffer.len() == buffer_size {
+                    buffer
+                        .flush(&embedder, batch_size, late_chunking, &tracker, &collector_tx)
+                        .await;
+                }
+            }
 
-            while let Some((chunk, metadata)) = rx.recv().await {
-                chunk_buffer.push(chunk);
-                metadata_buffer.push(metadata);
+            // Process any remaining chunks, including the partial buffer of a cancelled job
+            if !buffer.is_empty() {
+                buffer
+                    .flush(&embedder, batch_size, late_chunking, &tracker, &collector_tx)
+                    .await;
+            }
+        }
+    });
 
-                if chunk_buffer.len() == buffer_size {
-                    match process_chunks(
-                        &chunk_buffer,
-                        &metadata_buffer,
-                        &embedder,
-                        batch_size,
-                        late_chunking,
-                    )
-                    .await
-                    {
-                        Ok(embeddings) => {
-                            let files = embeddings
-                                .iter()
-                                .cloned()
-                                .map(|e| e.metadata.unwrap().get("file_name").unwrap().to_string())
-                                .collect::<Vec<_>>();
-
-                            let unique_files = files.into_iter().unique().collect::<Vec<_>>();
-                            let old_len = files_processed.len() as u64;
-                            files_processed.extend(unique_files);
-                            let new_len = files_processed.len() as u64;
-
-                            pb.inc(new_len - old_len);
-
-                            if let Err(e) = collector_tx.send(embeddings) {
-                                eprintln!("Error sending embeddings to collector: {:?}", e);
-                            }
-                        }
-                        Err(e) => eprintln!("Error processing chunks: {:?}", e),
-                    }
+    let files = Arc::new(Mutex::new(files.into_iter()));
+    let extraction_tasks = (0..workers)
+        .map(|_| {
+            tokio::spawn(extraction_worker(
+                files.clone(),
+                extractor.clone(),
+                tracker.clone(),
+                cancellation.clone(),
+                tx.clone(),
+            ))
+        })
+        .collect::<Vec<_>>();
+    drop(tx);
 
-                    chunk_buffer.clear();
-                    metadata_buffer.clear();
-                }
+    let task = tokio::spawn({
+        let cancellation = cancellation.clone();
+        async move {
+            for task in extraction_tasks {
+                task.await?;
             }
+            processing_task.await?;
 
-            // Process any remaining chunks
-            if !chunk_buffer.is_empty() {
-                match process_chunks(
-                    &chunk_buffer,
-                    &metadata_buffer,
-                    &embedder,
-                    batch_size,
-                    late_chunking,
-                )
-                .await
-                {
-                    Ok(embeddings) => {
-                        let files = embeddings
-                            .iter()
-                            .cloned()
-                            .map(|e| e.metadata.unwrap().get("file_name").unwrap().to_string())
-                            .collect::<Vec<_>>();
-                        let unique_files = files.into_iter().unique().collect::<Vec<_>>();
-                        let old_len = files_processed.len() as u64;
-                        files_processed.extend(unique_files);
-                        let new_len = files_processed.len() as u64;
-
-                        pb.inc(new_len - old_len);
-
-                        if let Err(e) = collector_tx.send(embeddings) {
-                            eprintln!("Error sending embeddings to collector: {:?}", e);
-                        }
-                    }
-                    Err(e) => eprintln!("Error processing chunks: {:?}", e),
-                }
+            if cancellation.is_cancelled() {
+                tracker.lock().unwrap().cancel_pending();
             }
+            Ok::<_, Error>(())
         }
     });
 
-    files.into_iter().for_each(|file| {
-        let text = match extract_document(&file, chunk_size, (chunk_size as f32 * overlap_ratio) as usize, OcrConfig { use_ocr, tesseract_path: tesseract_path.clone() }) {
-            Ok(text) => text,
-            Err(_) => {
+    Ok(Pipeline {
+        batches: collector_rx,
+        task,
+        cancellation,
+    })
+}
+
+/// A chunk on its way to the embedding task: the file it came from, its text and the file's metadata.
+type FileChunk = (String, String, Option<HashMap<String, String>>);
+
+/// Takes files from the shared queue until it is empty, or the job is cancelled, and sends their
+/// chunks to the embedding task. Several workers run side by side, so files finish extraction out
+/// of order.
+async fn extraction_worker(
+    files: Arc<Mutex<std::vec::IntoIter<PathBuf>>>,
+    extractor: Arc<Extractor>,
+    tracker: Arc<Mutex<FileTracker>>,
+    cancellation: CancellationToken,
+    tx: mpsc::Sender<FileChunk>,
+) {
+    while !cancellation.is_cancelled() {
+        let next = files.lock().unwrap().next();
+        let Some(file) = next else {
+            break;
+        };
+        let started = Instant::now();
+        let file_key = file.to_string_lossy().to_string();
+        if extractor.by_page(&file) {
+            if !send_pages(&file, &file_key, &extractor, &tracker, &cancellation, &tx).await {
                 return;
             }
+            continue;
+        }
+        let documents = match extractor.clone().extract(file).await {
+            Ok(extracted) => extracted,
+            Err(error) => {
+                tracker.lock().unwrap().failed(&file_key, error, started);
+                continue;
+            }
         };
-        let metadata = TextLoader::get_metadata(file).unwrap();
 
-        for chunk in text.chunks {
-            if let Err(e) = tx.send((chunk, Some(metadata.clone()))) {
-                eprintln!("Error sending chunk: {:?}", e);
+        let chunk_count = documents.iter().map(|(document, _)| document.chunks.len()).sum();
+        tracker
+            .lock()
+            .unwrap()
+            .extracted(&file_key, chunk_count, started);
+        for (document, metadata) in documents {
+            if !send_document(&file_key, document, &metadata, &cancellation, &tx).await {
+                return;
             }
         }
-    });
+    }
+}
 
-    drop(tx);
+/// Extracts a PDF page by page and sends the chunks of every page as soon as it is split, so the
+/// text of the whole PDF is never held at once. Chunk offsets and indices are relative to the
+/// whole PDF, as with [Extractor::extract]. Returns false if the worker should stop.
+async fn send_pages(
+    file: &std::path::Path,
+    file_key: &str,
+    extractor: &Arc<Extractor>,
+    tracker: &Mutex<FileTracker>,
+    cancellation: &CancellationToken,
+    tx: &mpsc::Sender<FileChunk>,
+) -> bool {
+    tracker.lock().unwrap().extracting(file_key, Instant::now());
+    if !file.exists() {
+        let error = FileError::NotFound(file_key.to_string());
+        tracker.lock().unwrap().extraction_done(file_key, Some(error));
+        return true;
+    }
+    let metadata = match TextLoader::get_metadata(file) {
+        Ok(metadata) => metadata,
+        Err(e) => {
+            let error = FileError::Metadata(e.to_string());
+            tracker.lock().unwrap().extraction_done(file_key, Some(error));
+            return true;
+        }
+    };
+    let mut pages = extractor.clone().extract_pages(file.to_path_buf());
+    let (mut index, mut bytes, mut chars) = (0, 0, 0);
+    loop {
+        let page = tokio::select! {
+            biased;
+            _ = cancellation.cancelled() => return false,
+            page = pages.recv() => page,
+        };
+        let document = match page {
+            Some(Ok(document)) => extractor.split(document).await,
+            Some(Err(error)) => Err(error),
+            None => break,
+        };
+        let document = match document {
+            Ok(document) => document,
+            Err(error) => {
+                tracker.lock().unwrap().extraction_done(file_key, Some(error));
+                return true;
+            }
+        };
+        let (page_bytes, page_chars) = (document.text.len(), document.text.chars().count());
+        let document = document.with_offsets(index, bytes, chars);
+        index += document.chunks.len();
+        bytes += page_bytes;
+        chars += page_chars;
+        tracker
+            .lock()
+            .unwrap()
+            .chunks_extracted(file_key, document.chunks.len());
+        if !send_document(file_key, document, &metadata, cancellation, tx).await {
+            return false;
+        }
+    }
+    tracker.lock().unwrap().extraction_done(file_key, None);
+    true
+}
 
-    let mut all_embeddings = Vec::new();
-    let mut adapter = adapter;
-    while let Some(embeddings) = collector_rx.recv().await {
-        if let Some(adapter) = adapter.as_mut() {
-            adapter(embeddings.to_vec());
-        } else {
-            all_embeddings.extend(embeddings.to_vec());
+/// Sends the chunks of `document` to the embedding task, with the metadata of the document, the
+/// file and the chunk. Returns false if the job was cancelled or the embedding task is gone.
+async fn send_document(
+    file_key: &str,
+    document: Document,
+    metadata: &HashMap<String, String>,
+    cancellation: &CancellationToken,
+    tx: &mpsc::Sender<FileChunk>,
+) -> bool {
+    for (chunk, provenance) in document.chunks.into_iter().zip(document.provenance) {
+        let mut chunk_metadata = document.metadata.clone();
+        chunk_metadata.extend(metadata.clone());
+        chunk_metadata.extend(provenance.metadata());
+        let sent = tokio::select! {
+            biased;
+            _ = cancellation.cancelled() => return false,
+            sent = tx.send((file_key.to_string(), chunk, Some(chunk_metadata))) => sent,
+        };
+        if let Err(e) = sent {
+            eprintln!("Error sending chunk: {:?}", e);
+            return false;
         }
     }
-    // Wait for the spawned task to complete
-    processing_task.await?;
+    true
+}
 
-    if adapter.is_some() {
-        Ok(None)
-    } else {
-        Ok(Some(all_embeddings))
+/// Turns a file into chunks and metadata, with the settings of a [TextEmbedConfig].
+struct Extractor {
+    settings: FileSettings,
+    /// The settings of the file types with overrides in [TextEmbedConfig::file_type_configs].
+    file_type_settings: HashMap<String, FileSettings>,
+    tesseract_path: Option<String>,
+    semantic_chunker: Option<StatisticalChunker>,
+    batch_size: usize,
+    archive_limits: ArchiveLimits,
+    content_extraction: ContentExtraction,
+    pdf_config: PdfConfig,
+}
+
+/// The settings of a [TextEmbedConfig] that can be overridden per file type.
+#[derive(Clone)]
+struct FileSettings {
+    chunking: Arc<Chunking>,
+    use_ocr: bool,
+}
+
+impl FileSettings {
+    fn new(config: &TextEmbedConfig, embedder: &Embedder) -> Result<Self> {
+        Ok(Self {
+            chunking: Arc::new(Chunking::new(config, text_embedder(embedder))?),
+            use_ocr: config.use_ocr.unwrap_or(false),
+        })
+    }
+}
+
+impl Extractor {
+    fn new(config: &TextEmbedConfig, embedder: &Embedder) -> Result<Self> {
+        let file_type_settings = config
+            .file_type_configs
+            .keys()
+            .map(|extension| {
+                let settings = FileSettings::new(&config.for_file_type(extension), embedder)?;
+                Ok::<_, Error>((extension.clone(), settings))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            settings: FileSettings::new(config, embedder)?,
+            file_type_settings,
+            tesseract_path: config.tesseract_path.clone(),
+            semantic_chunker: semantic_chunker(config)?,
+            batch_size: config.batch_size.unwrap_or(32),
+            archive_limits: config.archive_limits.unwrap_or_default(),
+            content_extraction: config.content_extraction.clone().unwrap_or_default(),
+            pdf_config: config.pdf_config.clone().unwrap_or_default(),
+        })
+    }
+
+    /// Extracts the document in `file`, or every supported document in it if it is an archive.
+    /// Parsing runs on the blocking thread pool, as PDF parsing and OCR are CPU bound.
+    async fn extract(
+        self: Arc<Self>,
+        file: PathBuf,
+    ) -> Result<Vec<(Document, HashMap<String, String>)>, FileError> {
+        let extractor = self.clone();
+        let documents = tokio::task::spawn_blocking(move || {
+            if archive::is_archive(&file) {
+                let metadata = TextLoader::get_metadata(&file)
+                    .map_err(|e| FileError::Metadata(e.to_string()))?;
+                return extractor
+                    .extract_archive(&file, &metadata)
+                    .map_err(|e| FileError::from_extraction(&e));
+            }
+            let settings = extractor.settings(&file_extension(&file));
+            let document = extract_document(
+                &file,
+                &settings.chunking,
+                extractor.ocr_config(settings),
+                &extractor.content_extraction,
+                &extractor.pdf_config,
+            )
+            .map_err(|e| FileError::from_extraction(&e))?;
+            let metadata = TextLoader::get_metadata(&file)
+                .map_err(|e| FileError::Metadata(e.to_string()))?;
+            Ok::<_, FileError>(vec![(document, metadata)])
+        })
+        .await
+        .map_err(|e| FileError::Extraction(e.to_string()))??;
+
+        let mut split = Vec::with_capacity(documents.len());
+        for (document, metadata) in documents {
+            split.push((self.split(document).await?, metadata));
+        }
+        Ok(split)
+    }
+
+    /// Splits an extracted document with the semantic chunker, if any.
+    async fn split(&self, document: Document) -> Result<Document, FileError> {
+        split_document(document, self.semantic_chunker.as_ref(), self.batch_size)
+            .await
+            .map_err(|e| FileError::Embedding(e.to_string()))
+    }
+
+    /// Whether `file` is a PDF that is extracted page by page with [Extractor::extract_pages]:
+    /// one whose chunks do not cross pages, without OCR on every page.
+    fn by_page(&self, file: &std::path::Path) -> bool {
+        let extension = file_extension(file);
+        extension == "pdf"
+            && !self.settings(&extension).use_ocr
+            && !self.pdf_config.cross_page_chunks
+    }
+
+    /// Extracts the pages of a PDF on the blocking thread pool with
+    /// [PdfProcessor::process_file_by_page], and sends the document of every page as soon as it
+    /// is extracted. Extraction stops when the receiver is dropped.
+    fn extract_pages(
+        self: Arc<Self>,
+        file: PathBuf,
+    ) -> mpsc::Receiver<Result<Document, FileError>> {
+        let (tx, rx) = mpsc::channel(1);
+        tokio::task::spawn_blocking(move || {
+            let settings = self.settings("pdf");
+            let chunking = &settings.chunking;
+            let processor = PdfProcessor::with_sizing(
+                chunking.chunk_size,
+                chunking.overlap,
+                chunking.sizing.clone(),
+                self.ocr_config(settings),
+            )
+            .map(|processor| processor.with_pdf_config(self.pdf_config.clone()));
+            let processor = match processor {
+                Ok(processor) => processor,
+                Err(e) => {
+                    let _ = tx.blocking_send(Err(FileError::Extraction(e.to_string())));
+                    return;
+                }
+            };
+            let pages = match processor.process_file_by_page(&file) {
+                Ok(pages) => pages,
+                Err(e) => {
+                    let _ = tx.blocking_send(Err(FileError::from_extraction(&e)));
+                    return;
+                }
+            };
+            for page in pages {
+                if tx.blocking_send(Ok(page)).is_err() {
+                    break;
+                }
+            }
+        });
+        rx
+    }
+
+    /// Extracts the supported documents of an archive in memory. Their metadata is the archive's,
+    /// with the archive in `archive_path` and the document's path inside it in `entry_path`.
+    /// Documents that fail to parse are skipped.
+    fn extract_archive(
+        &self,
+        file: &std::path::Path,
+        metadata: &HashMap<String, String>,
+    ) -> Result<Vec<(Document, HashMap<String, String>)>> {
+        let entries = archive::read_archive(file, &self.archive_limits, |entry| {
+            BYTES_FILE_TYPES.contains(&file_extension(entry).as_str())
+        })?;
+
+        let mut documents = Vec::with_capacity(entries.len());
+        for entry in entries {
+            let extension = file_extension(&entry.path);
+            let settings = self.settings(&extension);
+            let document = match extract_bytes(
+                &entry.bytes,
+                &extension,
+                &settings.chunking,
+                self.ocr_config(settings),
+                &self.content_extraction,
+                &self.pdf_config,
+            ) {
+                Ok(document) => document,
+                Err(e) => {
+                    eprintln!("Skipping {} in {}: {:?}", entry.path, file.display(), e);
+                    continue;
+                }
+            };
+            let mut entry_metadata = metadata.clone();
+            if let Some(archive_path) = metadata.get("file_name") {
+                entry_metadata.insert("archive_path".to_string(), archive_path.clone());
+            }
+            entry_metadata.insert("entry_path".to_string(), entry.path);
+            documents.push((document, entry_metadata));
+        }
+        Ok(documents)
+    }
+
+    /// The settings for files with `extension`.
+    fn settings(&self, extension: &str) -> &FileSettings {
+        self.file_type_settings
+            .get(extension)
+            .unwrap_or(&self.settings)
+    }
+
+    fn ocr_config(&self, settings: &FileSettings) -> OcrConfig {
+        OcrConfig {
+            use_ocr: settings.use_ocr,
+            tesseract_path: self.tesseract_path.clone(),
+        }
+    }
+}
+
+/// The lowercase extension of `path`, or an empty string if it has none.
+fn file_extension(path: impl AsRef<std::path::Path>) -> String {
+    path.as_ref()
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+}
+
+/// Chunks waiting to be embedded, together with the file each one came from.
+struct ChunkBuffer {
+    files: Vec<String>,
+    chunks: Vec<String>,
+    metadata: Vec<Option<HashMap<String, String>>>,
+    deduplicator: Option<Deduplicator>,
+}
+
+impl ChunkBuffer {
+    fn new(capacity: usize, deduplicator: Option<Deduplicator>) -> Self {
+        Self {
+            files: Vec::with_capacity(capacity),
+            chunks: Vec::with_capacity(capacity),
+            metadata: Vec::with_capacity(capacity),
+            deduplicator,
+        }
+    }
+
+    fn push(&mut self, file: String, chunk: String, metadata: Option<HashMap<String, String>>) {
+        self.files.push(file);
+        self.chunks.push(chunk);
+        self.metadata.push(metadata);
+    }
+
+    fn len(&self) -> usize {
+        self.chunks.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.chunks.is_empty()
+    }
+
+    /// Embeds the buffered chunks, sends them to the collector and clears the buffer.
+    async fn flush(
+        &mut self,
+        embedder: &Arc<Embedder>,
+        batch_size: Option<usize>,
+        late_chunking: Option<bool>,
+        tracker: &Mutex<FileTracker>,
+        collector_tx: &mpsc::Sender<Arc<Vec<EmbedData>>>,
+    ) {
+        let embeddings = match &mut self.deduplicator {
+            Some(deduplicator) => deduplicator
+                .embed(&self.chunks, &self.metadata, embedder, batch_size)
+                .await
+                .map(Arc::new),
+            None => {
+                process_chunks(
+                    &self.chunks,
+                    &self.metadata,
+                    embedder,
+                    batch_size,
+                    late_chunking,
+                )
+                .await
+            }
+        };
+        let error = match embeddings {
+            Ok(embeddings) => {
+                if let Err(e) = collector_tx.send(embeddings).await {
+                    eprintln!("Error sending embeddings to collector: {:?}", e);
+                }
+                None
+            }
+            Err(e) => Some(FileError::Embedding(e.to_string())),
+        };
+        tracker
+            .lock()
+            .unwrap()
+            .chunks_done(&self.files, error.as_ref());
+
+        self.files.clear();
+        self.chunks.clear();
+        self.metadata.clear();
     }
 }
 
@@ -885,11 +2096,97 @@ pub async fn process_chunks(
     Ok(Arc::new(embeddings))
 }
 
+/// Builds the [StatisticalChunker] for [SplittingStrategy::Semantic], or `None` when the
+/// extracted chunks are used as they are.
+fn semantic_chunker(config: &TextEmbedConfig) -> Result<Option<StatisticalChunker>> {
+    match &config.splitting_strategy {
+        SplittingStrategy::Sentence => Ok(None),
+        SplittingStrategy::Semantic { semantic_encoder } => Ok(Some(
+            StatisticalChunker::from_encoder(semantic_encoder.clone())?,
+        )),
+    }
+}
+
+/// Re-chunks the text of an extracted document semantically when a chunker is given. Semantic
+/// chunks keep their offsets, and the pages and headings of the chunks they overlap.
+async fn split_document(
+    document: Document,
+    semantic_chunker: Option<&StatisticalChunker>,
+    batch_size: usize,
+) -> Result<Document> {
+    match semantic_chunker {
+        Some(chunker) => {
+            let chunks = chunker.chunk_indices(&document.text, batch_size).await?;
+            Ok(document.rechunk(chunks))
+        }
+        None => Ok(document),
+    }
+}
+
+/// How documents are split into chunks, resolved from a [TextEmbedConfig] and the embedder.
+pub(crate) struct Chunking {
+    pub(crate) chunk_size: usize,
+    pub(crate) overlap: usize,
+    pub(crate) sizing: ChunkSizing,
+}
+
+impl Chunking {
+    /// With token chunking, chunks are measured in tokens of the embedder's tokenizer and are never
+    /// longer than the model takes. Without a chunk size, they are as long as the model takes.
+    pub(crate) fn new(config: &TextEmbedConfig, embedder: Option<&TextEmbedder>) -> Result<Self> {
+        let overlap_ratio = config.overlap_ratio.unwrap_or(0.0);
+        if !config.token_chunking.unwrap_or(false) {
+            let chunk_size = config.chunk_size.unwrap_or(1000);
+            return Ok(Self {
+                chunk_size,
+                overlap: (chunk_size as f32 * overlap_ratio) as usize,
+                sizing: ChunkSizing::Characters,
+            });
+        }
+
+        let embedder =
+            embedder.ok_or_else(|| anyhow::anyhow!("Token chunking requires a text embedder"))?;
+        let mut tokenizer = embedder
+            .tokenizer()
+            .ok_or_else(|| anyhow::anyhow!("Token chunking requires an embedder with a tokenizer"))?
+            .clone();
+        // Chunks are measured in full, and the splitter sizes them before the model truncates.
+        tokenizer
+            .with_truncation(None)
+            .map_err(Error::msg)?
+            .with_padding(None);
+
+        let chunk_size = match (config.chunk_size, embedder.max_sequence_length()) {
+            (Some(chunk_size), Some(max_length)) => chunk_size.min(max_length),
+            (Some(chunk_size), None) => chunk_size,
+            (None, Some(max_length)) => max_length,
+            (None, None) => {
+                return Err(anyhow::anyhow!(
+                    "The maximum length of the model is unknown, please set a chunk size"
+                ))
+            }
+        };
+        Ok(Self {
+            chunk_size,
+            overlap: (chunk_size as f32 * overlap_ratio) as usize,
+            sizing: ChunkSizing::Tokens(Arc::new(tokenizer)),
+        })
+    }
+}
+
+fn text_embedder(embedder: &Embedder) -> Option<&TextEmbedder> {
+    match embedder {
+        Embedder::Text(embedder) => Some(embedder),
+        Embedder::Vision(_) => None,
+    }
+}
+
 fn extract_document(
     file: impl AsRef<std::path::Path>,
-    chunk_size: usize,
-    overlap: usize,
+    chunking: &Chunking,
     ocr_config: OcrConfig,
+    content_extraction: &ContentExtraction,
+    pdf_config: &PdfConfig,
 ) -> Result<Document> {
     if !file.as_ref().exists() {
         return Err(FileLoadingError::FileNotFound(
@@ -897,25 +2194,80 @@ fn extract_document(
         )
             .into());
     }
-    let file_extension = file.as_ref().extension().unwrap();
-    match file_extension.to_str().unwrap() {
-        "pdf" => PdfProcessor::new(chunk_size, overlap, ocr_config)?.process_file(file),
-        "md" => MarkdownProcessor::new(chunk_size, overlap)?.process_file(file),
-        "txt" => TxtProcessor::new(chunk_size, overlap)?.process_file(file),
-        "docx" => DocxProcessor::new(chunk_size, overlap)?.process_file(file),
-        "html" => HtmlProcessor::new(chunk_size, overlap)?.process_file(file),
-        _ => Err(FileLoadingError::UnsupportedFileType(
-            file.as_ref()
-                .extension()
-                .unwrap()
-                .to_str()
-                .unwrap()
-                .to_string(),
-        )
-            .into()),
+    let file_extension = file
+        .as_ref()
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let (chunk_size, overlap, sizing) =
+        (chunking.chunk_size, chunking.overlap, chunking.sizing.clone());
+    match file_extension.as_str() {
+        "pdf" => PdfProcessor::with_sizing(chunk_size, overlap, sizing, ocr_config)?
+            .with_pdf_config(pdf_config.clone())
+            .process_file(file),
+        "md" => MarkdownProcessor::with_sizing(chunk_size, overlap, sizing)?.process_file(file),
+        "txt" => TxtProcessor::with_sizing(chunk_size, overlap, sizing)?.process_file(file),
+        "docx" => DocxProcessor::with_sizing(chunk_size, overlap, sizing)?.process_file(file),
+        "html" => HtmlProcessor::with_sizing(chunk_size, overlap, sizing)?
+            .with_content_extraction(content_extraction)?
+            .process_file(file),
+        _ => Err(FileLoadingError::UnsupportedFileType(file_extension).into()),
+    }
+}
+
+/// The extensions [extract_bytes] can parse.
+const BYTES_FILE_TYPES: [&str; 5] = ["pdf", "md", "txt", "docx", "html"];
+
+/// Like [extract_document], for a document of type `extension` that is already in memory.
+fn extract_bytes(
+    bytes: &[u8],
+    extension: &str,
+    chunking: &Chunking,
+    ocr_config: OcrConfig,
+    content_extraction: &ContentExtraction,
+    pdf_config: &PdfConfig,
+) -> Result<Document> {
+    let (chunk_size, overlap, sizing) =
+        (chunking.chunk_size, chunking.overlap, chunking.sizing.clone());
+    match extension {
+        "pdf" => PdfProcessor::with_sizing(chunk_size, overlap, sizing, ocr_config)?
+            .with_pdf_config(pdf_config.clone())
+            .process_bytes(bytes),
+        "md" => MarkdownProcessor::with_sizing(chunk_size, overlap, sizing)?.process_bytes(bytes),
+        "txt" => TxtProcessor::with_sizing(chunk_size, overlap, sizing)?.process_bytes(bytes),
+        "docx" => DocxProcessor::with_sizing(chunk_size, overlap, sizing)?.process_bytes(bytes),
+        "html" => HtmlProcessor::with_sizing(chunk_size, overlap, sizing)?
+            .with_content_extraction(content_extraction)?
+            .process_bytes(bytes),
+        _ => Err(FileLoadingError::UnsupportedFileType(extension.to_string()).into()),
     }
 }
 
+/// Returns the extension of the file type described by a MIME type, a file name or an extension.
+fn extension_from_content_type(content_type: &str) -> Option<String> {
+    let content_type = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_lowercase();
+    let extension = match content_type.as_str() {
+        "application/pdf" => "pdf",
+        "text/markdown" | "text/x-markdown" => "md",
+        "text/plain" => "txt",
+        "text/html" | "application/xhtml+xml" => "html",
+        "application/vnd.openxmlformats-officedocument.wordprocessingml.document" => "docx",
+        _ => {
+            return match std::path::Path::new(&content_type).extension() {
+                Some(extension) => Some(extension.to_string_lossy().to_string()),
+                None if !content_type.is_empty() && !content_type.contains('/') => Some(content_type),
+                None => None,
+            };
+        }
+    };
+    Some(extension.to_string())
+}
+
 #[derive(Debug)]
 pub enum FileLoadingError {
     FileNotFound(String),
@@ -932,16 +2284,18 @@ impl Display for FileLoadingError {
     }
 }
 
+impl std::error::Error for FileLoadingError {}
+
 impl From<FileLoadingError> for Error {
     fn from(error: FileLoadingError) -> Self {
-        match error {
-            FileLoadingError::FileNotFound(file) => {
-                Error::msg(format!("File not found: {:?}", file))
-            }
-            FileLoadingError::UnsupportedFileType(file) => Error::msg(format!(
+        // Keep the original error as the source so it can still be downcast, e.g. for reports.
+        let message = match &error {
+            FileLoadingError::FileNotFound(file) => format!("File not found: {:?}", file),
+            FileLoadingError::UnsupportedFileType(file) => format!(
                 "Unsupported file type: {:?}. Currently supported file types are: pdf, md, txt, docx",
                 file
-            )),
-        }
+            ),
+        };
+        Error::new(error).context(message)
     }
-}
\ No newline at end of file
+}
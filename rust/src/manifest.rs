@@ -0,0 +1,300 @@
+//! An on-disk record of which files have been embedded, so directory runs can skip unchanged
+//! files, pick up where a crashed run stopped, and report files that were deleted.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    io::Read,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::embeddings::embed::EmbedData;
+use crate::report::FileReport;
+
+/// What was recorded for a single file the last time it was embedded.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ManifestEntry {
+    pub path: String,
+    pub size: u64,
+    /// Modification time in nanoseconds since the Unix epoch.
+    pub modified: u64,
+    /// Hex encoded SHA-256 of the file contents.
+    pub content_hash: String,
+    pub model_id: String,
+    pub chunk_count: usize,
+}
+
+/// The manifest of an indexed directory, stored as JSON at `path`.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use embed_anything::manifest::IndexManifest;
+///
+/// let mut manifest = IndexManifest::open("index.json", "jinaai/jina-embeddings-v2-small-en").unwrap();
+/// let files = vec!["/docs/a.md".to_string(), "/docs/b.pdf".to_string()];
+/// let diff = manifest.diff(&files).unwrap();
+/// println!("{} files to embed, {} removed", diff.to_embed.len(), diff.removed.len());
+/// ```
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct IndexManifest {
+    #[serde(skip)]
+    path: PathBuf,
+    /// The model the next files will be embedded with. Entries recorded with another model are
+    /// treated as stale.
+    pub model_id: String,
+    pub files: HashMap<String, ManifestEntry>,
+}
+
+/// The size, modification time and content hash of a file. Taken before the file is embedded, so
+/// a change made while it is being embedded is picked up by the next run.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileState {
+    pub size: u64,
+    /// Modification time in nanoseconds since the Unix epoch.
+    pub modified: u64,
+    /// Hex encoded SHA-256 of the file contents.
+    pub content_hash: String,
+}
+
+impl FileState {
+    pub fn read(file: impl AsRef<Path>) -> Result<Self> {
+        let (size, modified) = file_stat(&file)?;
+        Ok(Self {
+            size,
+            modified,
+            content_hash: hash_file(&file)?,
+        })
+    }
+}
+
+/// How the files found on disk compare to an [IndexManifest].
+#[derive(Debug, Default)]
+pub struct ManifestDiff {
+    /// Files that are new, changed or were embedded with a different model.
+    pub to_embed: Vec<String>,
+    /// Files that are already embedded and have not changed.
+    pub unchanged: Vec<String>,
+    /// Files in the manifest that no longer exist. Their vectors should be deleted downstream.
+    pub removed: Vec<String>,
+}
+
+/// The outcome of an incremental directory run.
+#[derive(Debug, Default)]
+pub struct IndexUpdate {
+    /// The embeddings of the files that were (re-)embedded, or `None` if an adapter was used.
+    pub embeddings: Option<Vec<EmbedData>>,
+    pub embedded: Vec<String>,
+    pub unchanged: Vec<String>,
+    pub removed: Vec<String>,
+    /// Files that could not be embedded. They are not recorded, so the next run retries them.
+    pub failed: Vec<FileReport>,
+}
+
+impl IndexManifest {
+    /// Loads the manifest at `path`, or starts an empty one if the file does not exist yet.
+    pub fn open(path: impl AsRef<Path>, model_id: &str) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let mut manifest = if path.exists() {
+            serde_json::from_slice::<IndexManifest>(&fs::read(&path)?)?
+        } else {
+            IndexManifest::default()
+        };
+        manifest.path = path;
+        manifest.model_id = model_id.to_string();
+        Ok(manifest)
+    }
+
+    /// Writes the manifest to disk. The file is replaced atomically so a crash never leaves a
+    /// truncated manifest behind.
+    pub fn save(&self) -> Result<()> {
+        let tmp_path = self.path.with_extension("tmp");
+        fs::write(&tmp_path, serde_json::to_vec_pretty(self)?)?;
+        fs::rename(tmp_path, &self.path)?;
+        Ok(())
+    }
+
+    /// Compares `files` against the manifest. Only files whose size or modification time changed
+    /// are hashed; if the content turns out to be the same, the entry is refreshed in place.
+    pub fn diff(&mut self, files: &[String]) -> Result<ManifestDiff> {
+        let mut diff = ManifestDiff::default();
+
+        for file in files {
+            let Some(entry) = self
+                .files
+                .get_mut(file)
+                .filter(|entry| entry.model_id == self.model_id)
+            else {
+                diff.to_embed.push(file.clone());
+                continue;
+            };
+
+            let (size, modified) = file_stat(file)?;
+            if entry.size == size && entry.modified == modified {
+                diff.unchanged.push(file.clone());
+            } else if entry.size == size && entry.content_hash == hash_file(file)? {
+                entry.modified = modified;
+                diff.unchanged.push(file.clone());
+            } else {
+                diff.to_embed.push(file.clone());
+            }
+        }
+
+        let current: HashSet<&String> = files.iter().collect();
+        diff.removed = self
+            .files
+            .keys()
+            .filter(|file| !current.contains(file))
+            .cloned()
+            .collect();
+
+        Ok(diff)
+    }
+
+    /// Records that `file`, as it was in `state`, has been embedded into `chunk_count` chunks with
+    /// the manifest's model.
+    pub fn record(&mut self, file: &str, state: FileState, chunk_count: usize) {
+        let entry = ManifestEntry {
+            path: file.to_string(),
+            size: state.size,
+            modified: state.modified,
+            content_hash: state.content_hash,
+            model_id: self.model_id.clone(),
+            chunk_count,
+        };
+        self.files.insert(file.to_string(), entry);
+    }
+
+    /// Drops `file` from the manifest, e.g. once its vectors have been deleted downstream.
+    pub fn forget(&mut self, file: &str) -> Option<ManifestEntry> {
+        self.files.remove(file)
+    }
+}
+
+fn file_stat(file: impl AsRef<Path>) -> Result<(u64, u64)> {
+    let metadata = fs::metadata(file)?;
+    let modified = metadata.modified()?.duration_since(UNIX_EPOCH)?.as_nanos() as u64;
+    Ok((metadata.len(), modified))
+}
+
+/// Returns the hex encoded SHA-256 of the file contents.
+pub fn hash_file(file: impl AsRef<Path>) -> Result<String> {
+    let mut reader = fs::File::open(file)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = [0u8; 64 * 1024];
+    loop {
+        let read = reader.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::io::Write;
+    use std::time::Duration;
+    use tempdir::TempDir;
+
+    fn write_file(path: &Path, content: &str) -> String {
+        let mut file = File::create(path).unwrap();
+        file.write_all(content.as_bytes()).unwrap();
+        path.to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn test_diff() {
+        let temp_dir = TempDir::new("manifest").unwrap();
+        let kept = write_file(&temp_dir.path().join("kept.txt"), "kept");
+        let changed = write_file(&temp_dir.path().join("changed.txt"), "before");
+        let deleted = write_file(&temp_dir.path().join("deleted.txt"), "deleted");
+        let manifest_path = temp_dir.path().join("manifest.json");
+
+        let mut manifest = IndexManifest::open(&manifest_path, "model").unwrap();
+        for file in [&kept, &changed, &deleted] {
+            manifest.record(file, FileState::read(file).unwrap(), 1);
+        }
+        manifest.save().unwrap();
+
+        write_file(Path::new(&changed), "after the change");
+        fs::remove_file(&deleted).unwrap();
+        let added = write_file(&temp_dir.path().join("added.txt"), "added");
+
+        let mut manifest = IndexManifest::open(&manifest_path, "model").unwrap();
+        let diff = manifest
+            .diff(&[kept.clone(), changed.clone(), added.clone()])
+            .unwrap();
+
+        assert_eq!(diff.unchanged, vec![kept]);
+        assert_eq!(diff.to_embed, vec![changed, added]);
+        assert_eq!(diff.removed, vec![deleted]);
+    }
+
+    #[test]
+    fn test_diff_with_new_model() {
+        let temp_dir = TempDir::new("manifest").unwrap();
+        let file = write_file(&temp_dir.path().join("file.txt"), "content");
+
+        let mut manifest = IndexManifest::open(temp_dir.path().join("m.json"), "model").unwrap();
+        manifest.record(&file, FileState::read(&file).unwrap(), 3);
+        manifest.save().unwrap();
+
+        let mut manifest =
+            IndexManifest::open(temp_dir.path().join("m.json"), "other-model").unwrap();
+        let diff = manifest.diff(&[file.clone()]).unwrap();
+        assert_eq!(diff.to_embed, vec![file]);
+    }
+
+    #[test]
+    fn test_diff_within_the_same_second() {
+        let temp_dir = TempDir::new("manifest").unwrap();
+        let path = temp_dir.path().join("file.txt");
+        let file = write_file(&path, "before");
+        let second = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(second + Duration::from_millis(200))
+            .unwrap();
+
+        let mut manifest = IndexManifest::open(temp_dir.path().join("m.json"), "model").unwrap();
+        manifest.record(&file, FileState::read(&file).unwrap(), 1);
+
+        // Same size, and modified again within the same second.
+        write_file(&path, "after!");
+        File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(second + Duration::from_millis(700))
+            .unwrap();
+        let diff = manifest.diff(&[file.clone()]).unwrap();
+        assert_eq!(diff.to_embed, vec![file]);
+    }
+
+    #[test]
+    fn test_change_while_embedding() {
+        let temp_dir = TempDir::new("manifest").unwrap();
+        let path = temp_dir.path().join("file.txt");
+        let file = write_file(&path, "before");
+
+        let mut manifest = IndexManifest::open(temp_dir.path().join("m.json"), "model").unwrap();
+        let state = FileState::read(&file).unwrap();
+        // The file changes after it was read for embedding, but before it is recorded.
+        write_file(&path, "after the change");
+        manifest.record(&file, state, 1);
+
+        let diff = manifest.diff(&[file.clone()]).unwrap();
+        assert_eq!(diff.to_embed, vec![file]);
+    }
+}
@@ -0,0 +1,160 @@
+//! Progress of the directory and batch embedding pipelines, either drawn as a progress bar on the
+//! terminal or sent as [ProgressEvent]s to a callback.
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::Result;
+
+/// Receives a [ProgressEvent] whenever an embedding job makes progress. When a callback is set,
+/// no progress bar is drawn.
+pub type ProgressCallback = Arc<dyn Fn(ProgressEvent) + Send + Sync>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProgressEvent {
+    /// Sent once, before the first file is processed.
+    FilesDiscovered { files: usize, bytes: u64 },
+    /// A file has been processed, successfully or not.
+    FileDone { file: String, bytes: u64 },
+    /// A batch of chunks has been embedded.
+    ChunksEmbedded { chunks: usize },
+    /// Something went wrong. `file` is `None` when the error concerns a batch of several files.
+    Error { file: Option<String>, error: String },
+    /// The job was cancelled. Files that had not been started by then are not reported.
+    Cancelled,
+}
+
+pub(crate) struct Progress {
+    callback: Option<ProgressCallback>,
+    bar: Option<indicatif::ProgressBar>,
+    sizes: HashMap<String, u64>,
+}
+
+impl Progress {
+    /// Starts following a job over `files`. Without a callback, a progress bar is drawn instead.
+    pub(crate) fn new(callback: Option<ProgressCallback>, files: &[PathBuf]) -> Result<Self> {
+        let sizes: HashMap<String, u64> = files
+            .iter()
+            .map(|file| (file.to_string_lossy().to_string(), file_size(file)))
+            .collect();
+
+        let bar = match &callback {
+            Some(callback) => {
+                callback(ProgressEvent::FilesDiscovered {
+                    files: files.len(),
+                    bytes: sizes.values().sum(),
+                });
+                None
+            }
+            None => {
+                let bar = indicatif::ProgressBar::new(files.len() as u64);
+                bar.set_style(indicatif::ProgressStyle::with_template(
+                    "{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} ({eta})",
+                )?);
+                Some(bar)
+            }
+        };
+
+        Ok(Self {
+            callback,
+            bar,
+            sizes,
+        })
+    }
+
+    pub(crate) fn file_done(&self, file: &str, error: Option<&str>) {
+        if let Some(bar) = &self.bar {
+            bar.inc(1);
+        }
+        if let Some(error) = error {
+            self.error(Some(file), error);
+        }
+        self.send(|| ProgressEvent::FileDone {
+            file: file.to_string(),
+            bytes: self
+                .sizes
+                .get(file)
+                .copied()
+                .unwrap_or_else(|| file_size(file)),
+        });
+    }
+
+    pub(crate) fn chunks_embedded(&self, chunks: usize) {
+        self.send(|| ProgressEvent::ChunksEmbedded { chunks });
+    }
+
+    pub(crate) fn error(&self, file: Option<&str>, error: &str) {
+        self.send(|| ProgressEvent::Error {
+            file: file.map(|file| file.to_string()),
+            error: error.to_string(),
+        });
+    }
+
+    pub(crate) fn cancelled(&self) {
+        if let Some(bar) = &self.bar {
+            bar.abandon_with_message("cancelled");
+        }
+        self.send(|| ProgressEvent::Cancelled);
+    }
+
+    fn send(&self, event: impl FnOnce() -> ProgressEvent) {
+        if let Some(callback) = &self.callback {
+            callback(event());
+        }
+    }
+}
+
+fn file_size(file: impl AsRef<Path>) -> u64 {
+    fs::metadata(file).map(|metadata| metadata.len()).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_progress_events() {
+        let temp_dir = TempDir::new("progress").unwrap();
+        let file = temp_dir.path().join("notes.txt");
+        fs::write(&file, "twelve bytes").unwrap();
+        let file_key = file.to_string_lossy().to_string();
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = events.clone();
+        let progress = Progress::new(
+            Some(Arc::new(move |event: ProgressEvent| {
+                sink.lock().unwrap().push(event)
+            })),
+            &[file],
+        )
+        .unwrap();
+        progress.chunks_embedded(3);
+        progress.file_done(&file_key, Some("model unavailable"));
+
+        let events = events.lock().unwrap();
+        assert_eq!(
+            *events,
+            vec![
+                ProgressEvent::FilesDiscovered {
+                    files: 1,
+                    bytes: 12
+                },
+                ProgressEvent::ChunksEmbedded { chunks: 3 },
+                ProgressEvent::Error {
+                    file: Some(file_key.clone()),
+                    error: "model unavailable".to_string()
+                },
+                ProgressEvent::FileDone {
+                    file: file_key,
+                    bytes: 12
+                },
+            ]
+        );
+    }
+}
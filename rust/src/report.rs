@@ -0,0 +1,292 @@
+//! Per-file outcome of the batch and directory embedding pipelines.
+
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use crate::progress::Progress;
+use crate::FileLoadingError;
+
+/// Receives a [FileReport] as soon as a file has been fully processed.
+pub type ReportCallback = Arc<dyn Fn(FileReport) + Send + Sync>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileStatus {
+    /// Every chunk of the file was embedded.
+    Embedded,
+    /// The file was read, but produced no chunks.
+    Empty,
+    /// The file could not be extracted or some of its chunks could not be embedded.
+    Failed,
+}
+
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum FileError {
+    #[error("File not found: {0}")]
+    NotFound(String),
+    #[error("Unsupported file type: {0}")]
+    UnsupportedFileType(String),
+    #[error("Error extracting text: {0}")]
+    Extraction(String),
+    #[error("Error reading file metadata: {0}")]
+    Metadata(String),
+    #[error("Error embedding chunks: {0}")]
+    Embedding(String),
+    #[error("Cancelled before all chunks were embedded")]
+    Cancelled,
+}
+
+impl FileError {
+    /// Classifies an error returned while extracting a document.
+    pub fn from_extraction(error: &anyhow::Error) -> Self {
+        match error.downcast_ref::<FileLoadingError>() {
+            Some(FileLoadingError::FileNotFound(file)) => FileError::NotFound(file.clone()),
+            Some(FileLoadingError::UnsupportedFileType(ext)) => {
+                FileError::UnsupportedFileType(ext.clone())
+            }
+            None => FileError::Extraction(error.to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FileReport {
+    pub file: String,
+    pub status: FileStatus,
+    /// Number of chunks the file was split into.
+    pub chunk_count: usize,
+    /// Time from the start of the extraction until the last chunk was embedded.
+    pub elapsed: Duration,
+    pub error: Option<FileError>,
+}
+
+struct PendingFile {
+    started: Instant,
+    chunk_count: usize,
+    chunks_done: usize,
+    error: Option<FileError>,
+    /// Whether more chunks of the file may still be extracted.
+    extracting: bool,
+}
+
+/// Follows the chunks of every file through the pipeline and reports each file once all of its
+/// chunks have been embedded or have failed.
+pub(crate) struct FileTracker {
+    callback: Option<ReportCallback>,
+    progress: Progress,
+    pending: HashMap<String, PendingFile>,
+}
+
+impl FileTracker {
+    pub(crate) fn new(callback: Option<ReportCallback>, progress: Progress) -> Self {
+        Self {
+            callback,
+            progress,
+            pending: HashMap::new(),
+        }
+    }
+
+    /// Registers a file before its chunks are sent. A file without chunks is reported right away.
+    pub(crate) fn extracted(&mut self, file: &str, chunk_count: usize, started: Instant) {
+        self.extracting(file, started);
+        self.chunks_extracted(file, chunk_count);
+        self.extraction_done(file, None);
+    }
+
+    /// Registers a file whose chunks are sent as they are extracted, e.g. page by page. The file
+    /// is not reported before [FileTracker::extraction_done].
+    pub(crate) fn extracting(&mut self, file: &str, started: Instant) {
+        self.pending.insert(
+            file.to_string(),
+            PendingFile {
+                started,
+                chunk_count: 0,
+                chunks_done: 0,
+                error: None,
+                extracting: true,
+            },
+        );
+    }
+
+    /// Adds `chunk_count` chunks to a file that is being extracted, before they are sent.
+    pub(crate) fn chunks_extracted(&mut self, file: &str, chunk_count: usize) {
+        if let Some(pending) = self.pending.get_mut(file) {
+            pending.chunk_count += chunk_count;
+        }
+    }
+
+    /// Marks the extraction of a file as over, or as failed with `error` after some of its chunks
+    /// may have been sent. A file without chunks is reported right away.
+    pub(crate) fn extraction_done(&mut self, file: &str, error: Option<FileError>) {
+        let Some(pending) = self.pending.get_mut(file) else {
+            return;
+        };
+        pending.extracting = false;
+        if pending.error.is_none() {
+            pending.error = error;
+        }
+        self.report_if_done(file);
+    }
+
+    /// Reports a file that failed before any of its chunks were sent.
+    pub(crate) fn failed(&mut self, file: &str, error: FileError, started: Instant) {
+        self.report(FileReport {
+            file: file.to_string(),
+            status: FileStatus::Failed,
+            chunk_count: 0,
+            elapsed: started.elapsed(),
+            error: Some(error),
+        });
+    }
+
+    /// Marks one chunk per entry in `files` as done.
+    pub(crate) fn chunks_done(&mut self, files: &[String], error: Option<&FileError>) {
+        if error.is_none() {
+            self.progress.chunks_embedded(files.len());
+        }
+        for file in files {
+            let Some(pending) = self.pending.get_mut(file) else {
+                continue;
+            };
+            pending.chunks_done += 1;
+            if pending.error.is_none() {
+                pending.error = error.cloned();
+            }
+            self.report_if_done(file);
+        }
+    }
+
+    /// Reports a file once it is extracted and all of its chunks are done.
+    fn report_if_done(&mut self, file: &str) {
+        let Some(pending) = self.pending.get(file) else {
+            return;
+        };
+        if pending.extracting || pending.chunks_done < pending.chunk_count {
+            return;
+        }
+        let pending = self.pending.remove(file).unwrap();
+        let status = if pending.error.is_some() {
+            FileStatus::Failed
+        } else if pending.chunk_count == 0 {
+            FileStatus::Empty
+        } else {
+            FileStatus::Embedded
+        };
+        self.report(FileReport {
+            file: file.to_string(),
+            status,
+            chunk_count: pending.chunk_count,
+            elapsed: pending.started.elapsed(),
+            error: pending.error,
+        });
+    }
+
+    /// Reports the files whose chunks were still in the pipeline when the job was cancelled.
+    pub(crate) fn cancel_pending(&mut self) {
+        self.progress.cancelled();
+        for (file, pending) in std::mem::take(&mut self.pending) {
+            self.report(FileReport {
+                file,
+                status: FileStatus::Failed,
+                chunk_count: pending.chunk_count,
+                elapsed: pending.started.elapsed(),
+                error: Some(FileError::Cancelled),
+            });
+        }
+    }
+
+    fn report(&self, report: FileReport) {
+        let error = report.error.as_ref().map(|error| error.to_string());
+        self.progress.file_done(&report.file, error.as_deref());
+        if let Some(callback) = &self.callback {
+            callback(report);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::progress::ProgressEvent;
+    use std::sync::Mutex;
+
+    fn tracker() -> (FileTracker, Arc<Mutex<Vec<FileReport>>>) {
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let sink = reports.clone();
+        let progress = Progress::new(Some(Arc::new(|_: ProgressEvent| {})), &[]).unwrap();
+        let tracker = FileTracker::new(
+            Some(Arc::new(move |report: FileReport| {
+                sink.lock().unwrap().push(report)
+            })),
+            progress,
+        );
+        (tracker, reports)
+    }
+
+    #[test]
+    fn test_report_after_last_chunk() {
+        let (mut tracker, reports) = tracker();
+        let started = Instant::now();
+        tracker.extracted("a.txt", 2, started);
+        tracker.extracted("b.txt", 1, started);
+
+        let error = FileError::Embedding("model unavailable".to_string());
+        tracker.chunks_done(&["a.txt".to_string(), "b.txt".to_string()], Some(&error));
+        assert_eq!(reports.lock().unwrap().len(), 1);
+        tracker.chunks_done(&["a.txt".to_string()], None);
+
+        let reports = reports.lock().unwrap();
+        assert_eq!(reports.len(), 2);
+        assert_eq!(reports[0].file, "b.txt");
+        assert_eq!(reports[0].status, FileStatus::Failed);
+        assert_eq!(reports[0].error, Some(error.clone()));
+        // An error in one batch fails the whole file, even if later chunks succeed.
+        assert_eq!(reports[1].file, "a.txt");
+        assert_eq!(reports[1].status, FileStatus::Failed);
+        assert_eq!(reports[1].chunk_count, 2);
+    }
+
+    #[test]
+    fn test_report_empty_and_failed_files() {
+        let (mut tracker, reports) = tracker();
+        tracker.extracted("empty.txt", 0, Instant::now());
+        let error = FileError::UnsupportedFileType("xyz".to_string());
+        tracker.failed("file.xyz", error, Instant::now());
+        tracker.extracted("partial.txt", 2, Instant::now());
+        tracker.chunks_done(&["partial.txt".to_string()], None);
+        tracker.cancel_pending();
+
+        let reports = reports.lock().unwrap();
+        assert_eq!(reports[0].status, FileStatus::Empty);
+        assert_eq!(reports[1].status, FileStatus::Failed);
+        assert_eq!(reports[2].file, "partial.txt");
+        assert_eq!(reports[2].error, Some(FileError::Cancelled));
+    }
+
+    #[test]
+    fn test_report_after_extraction() {
+        let (mut tracker, reports) = tracker();
+        tracker.extracting("book.pdf", Instant::now());
+        tracker.chunks_extracted("book.pdf", 1);
+        tracker.chunks_done(&["book.pdf".to_string()], None);
+        // The first page is embedded before the second one is extracted.
+        assert!(reports.lock().unwrap().is_empty());
+        tracker.chunks_extracted("book.pdf", 1);
+        tracker.extraction_done("book.pdf", None);
+        assert!(reports.lock().unwrap().is_empty());
+        tracker.chunks_done(&["book.pdf".to_string()], None);
+
+        tracker.extracting("broken.pdf", Instant::now());
+        let error = FileError::Extraction("invalid PDF".to_string());
+        tracker.extraction_done("broken.pdf", Some(error.clone()));
+
+        let reports = reports.lock().unwrap();
+        assert_eq!(reports[0].file, "book.pdf");
+        assert_eq!(reports[0].status, FileStatus::Embedded);
+        assert_eq!(reports[0].chunk_count, 2);
+        assert_eq!(reports[1].status, FileStatus::Failed);
+        assert_eq!(reports[1].error, Some(error));
+    }
+}
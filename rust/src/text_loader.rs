@@ -32,12 +32,15 @@ impl TextLoader {
     pub fn get_metadata<T: AsRef<std::path::Path>>(
         file: T,
     ) -> Result<HashMap<String, String>, Error> {
-        let metadata = fs::metadata(&file).unwrap();
+        let metadata = fs::metadata(&file)?;
         let mut metadata_map = HashMap::new();
-        metadata_map.insert(
-            "created".to_string(),
-            format!("{}", DateTime::<Local>::from(metadata.created()?)),
-        );
+        // Not every platform and file system records the creation time.
+        if let Ok(created) = metadata.created() {
+            metadata_map.insert(
+                "created".to_string(),
+                format!("{}", DateTime::<Local>::from(created)),
+            );
+        }
         metadata_map.insert(
             "modified".to_string(),
             format!("{}", DateTime::<Local>::from(metadata.modified()?)),
@@ -45,7 +48,7 @@ impl TextLoader {
 
         metadata_map.insert(
             "file_name".to_string(),
-            fs::canonicalize(file)?.to_str().unwrap().to_string(),
+            fs::canonicalize(file)?.to_string_lossy().to_string(),
         );
         Ok(metadata_map)
     }
@@ -0,0 +1,239 @@
+//! Watches a directory for files that are created, modified or deleted, so they can be
+//! re-embedded without re-running a full directory embedding.
+
+use std::{
+    collections::BTreeSet,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::Result;
+use notify::{event::ModifyKind, Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use tokio::sync::mpsc;
+
+use crate::file_loader::{FileFilter, FileParser};
+
+/// The files that changed in a directory since the last call to [DirectoryWatcher::next_changes].
+#[derive(Debug, Default, PartialEq)]
+pub struct FileChanges {
+    /// Files that were created or modified and need to be (re-)embedded.
+    pub changed: Vec<String>,
+    /// Files that were deleted or moved out of the directory.
+    pub removed: Vec<String>,
+}
+
+impl FileChanges {
+    pub fn is_empty(&self) -> bool {
+        self.changed.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// Watches a directory recursively (using inotify on Linux) and reports changes to the files
+/// [FileParser::get_text_files] would pick up with the same [FileFilter].
+///
+/// Events are debounced: a burst of writes to the same file, as editors and sync tools produce,
+/// is reported once.
+pub struct DirectoryWatcher {
+    // Dropping the watcher stops the notifications.
+    _watcher: RecommendedWatcher,
+    events: mpsc::UnboundedReceiver<notify::Result<Event>>,
+    directory: PathBuf,
+    file_parser: FileParser,
+    extensions: Option<Vec<String>>,
+    debounce: Duration,
+    /// The watched files in the directory, so that the files of a directory that is deleted or
+    /// moved away, which only reports itself, can be reported as removed.
+    known_files: BTreeSet<String>,
+}
+
+impl DirectoryWatcher {
+    pub fn new(
+        directory: impl AsRef<Path>,
+        extensions: Option<Vec<String>>,
+        filter: FileFilter,
+    ) -> Result<Self> {
+        let directory = directory.as_ref().canonicalize()?;
+        let (tx, events) = mpsc::unbounded_channel();
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+            let _ = tx.send(event);
+        })?;
+        watcher.watch(&directory, RecursiveMode::Recursive)?;
+        let mut file_parser = FileParser::with_filter(filter);
+        let known_files = file_parser
+            .get_text_files(&directory, extensions.clone())?
+            .into_iter()
+            .collect();
+
+        Ok(Self {
+            _watcher: watcher,
+            events,
+            directory,
+            file_parser,
+            extensions,
+            debounce: Duration::from_millis(500),
+            known_files,
+        })
+    }
+
+    /// Sets how long the directory has to be quiet before a batch of changes is reported.
+    /// Defaults to 500ms.
+    pub fn with_debounce(mut self, debounce: Duration) -> Self {
+        self.debounce = debounce;
+        self
+    }
+
+    /// Waits for the next batch of changes. Returns `None` once the watcher has shut down.
+    ///
+    /// Whether a path changed or was removed is decided by looking at the file system once the
+    /// burst of events is over, so renames show up as a removal and a change.
+    pub async fn next_changes(&mut self) -> Option<Result<FileChanges>> {
+        loop {
+            let mut paths = BTreeSet::new();
+            let event = self.events.recv().await?;
+            if let Err(e) = collect_paths(event, &mut paths) {
+                return Some(Err(e));
+            }
+            while let Ok(Some(event)) = tokio::time::timeout(self.debounce, self.events.recv()).await
+            {
+                if let Err(e) = collect_paths(event, &mut paths) {
+                    return Some(Err(e));
+                }
+            }
+
+            let changes = self.classify(paths);
+            if !changes.is_empty() {
+                return Some(Ok(changes));
+            }
+        }
+    }
+
+    fn classify(&mut self, paths: BTreeSet<PathBuf>) -> FileChanges {
+        let mut changed = BTreeSet::new();
+        let mut removed = BTreeSet::new();
+
+        for path in paths {
+            if path.exists() {
+                // A directory moved into the tree only reports itself, not its files. Files are
+                // checked against the filter of the whole directory, as its patterns are relative
+                // to it.
+                let files = self.file_parser.get_text_files_under(
+                    &self.directory,
+                    &path,
+                    self.extensions.clone(),
+                );
+                if let Ok(files) = files {
+                    changed.extend(files);
+                }
+            } else {
+                // A deleted file or directory can no longer be checked against the filter, so
+                // the known files at or under it are the ones removed.
+                removed.extend(
+                    self.known_files
+                        .iter()
+                        .filter(|file| Path::new(file).starts_with(&path))
+                        .cloned(),
+                );
+            }
+        }
+
+        // A file removed and created again within the same burst is only changed.
+        removed.retain(|file| !changed.contains(file));
+        for file in &removed {
+            self.known_files.remove(file);
+        }
+        self.known_files.extend(changed.iter().cloned());
+
+        FileChanges {
+            changed: changed.into_iter().collect(),
+            removed: removed.into_iter().collect(),
+        }
+    }
+}
+
+fn collect_paths(event: notify::Result<Event>, paths: &mut BTreeSet<PathBuf>) -> Result<()> {
+    let event = event?;
+    match event.kind {
+        EventKind::Access(_) | EventKind::Modify(ModifyKind::Metadata(_)) => {}
+        _ => paths.extend(event.paths),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempdir::TempDir;
+
+    async fn next_changes(watcher: &mut DirectoryWatcher) -> FileChanges {
+        tokio::time::timeout(Duration::from_secs(10), watcher.next_changes())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_watch_directory() {
+        let temp_dir = TempDir::new("watch").unwrap();
+        let mut watcher = DirectoryWatcher::new(temp_dir.path(), None, FileFilter::default())
+            .unwrap()
+            .with_debounce(Duration::from_millis(100));
+
+        let txt_file = temp_dir.path().canonicalize().unwrap().join("notes.txt");
+        fs::write(&txt_file, "first draft").unwrap();
+        fs::write(temp_dir.path().join("image.jpg"), "not watched").unwrap();
+
+        let changes = next_changes(&mut watcher).await;
+        assert_eq!(changes.changed, vec![txt_file.to_string_lossy().to_string()]);
+        assert!(changes.removed.is_empty());
+
+        fs::remove_file(&txt_file).unwrap();
+
+        let changes = next_changes(&mut watcher).await;
+        assert!(changes.changed.is_empty());
+        assert_eq!(changes.removed, vec![txt_file.to_string_lossy().to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_file_filter() {
+        let temp_dir = TempDir::new("watch").unwrap();
+        let root = temp_dir.path().canonicalize().unwrap();
+        fs::create_dir(root.join("drafts")).unwrap();
+        let filter = FileFilter::default().with_exclude(&["drafts"]);
+        let mut watcher = DirectoryWatcher::new(&root, None, filter)
+            .unwrap()
+            .with_debounce(Duration::from_millis(100));
+
+        fs::write(root.join("drafts").join("todo.md"), "excluded").unwrap();
+        fs::write(root.join("notes.md"), "watched").unwrap();
+
+        let changes = next_changes(&mut watcher).await;
+        assert_eq!(changes.changed, vec![root.join("notes.md").to_string_lossy().to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_removed_directory() {
+        let temp_dir = TempDir::new("watch").unwrap();
+        let notes = temp_dir.path().canonicalize().unwrap().join("notes");
+        fs::create_dir(&notes).unwrap();
+        fs::write(notes.join("a.txt"), "a").unwrap();
+        fs::write(notes.join("b.md"), "b").unwrap();
+        let mut watcher = DirectoryWatcher::new(temp_dir.path(), None, FileFilter::default())
+            .unwrap()
+            .with_debounce(Duration::from_millis(100));
+
+        // Moved out of the watched tree, which only reports the directory itself.
+        let outside = TempDir::new("outside").unwrap();
+        fs::rename(&notes, outside.path().join("notes")).unwrap();
+
+        let changes = next_changes(&mut watcher).await;
+        assert!(changes.changed.is_empty());
+        let mut expected = vec![
+            notes.join("a.txt").to_string_lossy().to_string(),
+            notes.join("b.md").to_string_lossy().to_string(),
+        ];
+        expected.sort();
+        assert_eq!(changes.removed, expected);
+    }
+}